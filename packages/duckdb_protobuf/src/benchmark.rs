@@ -0,0 +1,194 @@
+use crate::io::{Format, OpenFilesLimit};
+use crate::read::MyFlatVector;
+use crate::validate::ScratchDecoder;
+use crate::vtab::{format_error_with_causes, Handle, Parameters};
+use anyhow::format_err;
+use duckdb::vtab::{BindInfo, DataChunk, FunctionInfo, InitInfo, LogicalType, LogicalTypeId, VTab};
+use prost_reflect::MessageDescriptor;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Instant;
+
+pub struct BenchmarkState {
+    open_files: Arc<OpenFilesLimit>,
+    decoder: ScratchDecoder,
+    text_descriptor: Option<MessageDescriptor>,
+    done: bool,
+}
+
+/// Totals of a run over every record of the files.
+struct Throughput {
+    records: u64,
+    bytes: u64,
+    seconds: f64,
+}
+
+/// `protobuf_benchmark(...)`, reading and decoding every record of the files
+/// like `protobuf(...)` would with the same parameters, into a chunk which is
+/// thrown away, giving a single row with the throughput. It measures the cost
+/// of a scan without the rest of the query.
+pub struct ProtobufBenchmarkVTab;
+
+impl VTab for ProtobufBenchmarkVTab {
+    type InitData = Handle<BenchmarkState>;
+    type BindData = Handle<Parameters>;
+
+    unsafe fn bind(
+        bind: &BindInfo,
+        data: *mut Self::BindData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::bind(bind, data).map_err(format_error_with_causes)?)
+    }
+
+    unsafe fn init(
+        init_info: &InitInfo,
+        data: *mut Self::InitData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::init(init_info, data).map_err(format_error_with_causes)?)
+    }
+
+    unsafe fn func(
+        func: &FunctionInfo,
+        output: &mut DataChunk,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::func(func, output).map_err(format_error_with_causes)?)
+    }
+
+    fn named_parameters() -> Option<Vec<(String, LogicalType)>> {
+        Some(Parameters::values())
+    }
+}
+
+impl ProtobufBenchmarkVTab {
+    fn bind(bind: &BindInfo, data: *mut <Self as VTab>::BindData) -> Result<(), anyhow::Error> {
+        let data = unsafe { &mut *data };
+        data.init();
+
+        let params = Parameters::from_bind_info(bind)?;
+
+        bind.add_result_column("records", LogicalType::new(LogicalTypeId::UBigint));
+        bind.add_result_column("bytes", LogicalType::new(LogicalTypeId::UBigint));
+        bind.add_result_column("seconds", LogicalType::new(LogicalTypeId::Double));
+        bind.add_result_column(
+            "records_per_second",
+            LogicalType::new(LogicalTypeId::Double),
+        );
+        bind.add_result_column("bytes_per_second", LogicalType::new(LogicalTypeId::Double));
+
+        data.assign(params);
+
+        Ok(())
+    }
+
+    fn init(
+        init_info: &InitInfo,
+        data: *mut <Self as VTab>::InitData,
+    ) -> Result<(), anyhow::Error> {
+        let data = unsafe { &mut *data };
+        data.init();
+
+        let bind_data = unsafe { &*init_info.get_bind_data::<<Self as VTab>::BindData>() };
+
+        let text_descriptor = match bind_data.format {
+            Format::Binary => None,
+            Format::Text | Format::Jsonl => Some(bind_data.record_descriptor()),
+        };
+
+        init_info.set_max_threads(1);
+        data.assign(BenchmarkState {
+            open_files: OpenFilesLimit::new(bind_data.max_open_files),
+            decoder: ScratchDecoder::new(bind_data)?,
+            text_descriptor,
+            done: false,
+        });
+
+        Ok(())
+    }
+
+    fn func(func: &FunctionInfo, output: &mut DataChunk) -> Result<(), anyhow::Error> {
+        let bind_data = unsafe { &*func.get_bind_data::<<Self as VTab>::BindData>() };
+        let init_data = unsafe { &mut *func.get_init_data::<<Self as VTab>::InitData>() };
+
+        if std::mem::replace(&mut init_data.done, true) {
+            output.set_len(0);
+            return Ok(());
+        }
+
+        let throughput = init_data.run(bind_data)?;
+        let available_chunk_size = output.flat_vector(0).capacity();
+
+        for (column_idx, value) in [(0, throughput.records), (1, throughput.bytes)] {
+            let mut vector = unsafe {
+                MyFlatVector::<u64>::with_capacity(
+                    output.get_vector(column_idx),
+                    available_chunk_size,
+                )
+            };
+            vector.as_mut_slice()[0] = value;
+        }
+
+        // an empty run takes no time, its rates are 0 rather than NaN
+        let per_second = |value: u64| match throughput.seconds {
+            seconds if seconds > 0.0 => value as f64 / seconds,
+            _ => 0.0,
+        };
+
+        for (column_idx, value) in [
+            (2, throughput.seconds),
+            (3, per_second(throughput.records)),
+            (4, per_second(throughput.bytes)),
+        ] {
+            let mut vector = unsafe {
+                MyFlatVector::<f64>::with_capacity(
+                    output.get_vector(column_idx),
+                    available_chunk_size,
+                )
+            };
+            vector.as_mut_slice()[0] = value;
+        }
+
+        output.set_len(1);
+
+        Ok(())
+    }
+}
+
+impl BenchmarkState {
+    /// Reads and decodes every record of every file, failing on the first
+    /// record which can't be.
+    fn run(&mut self, parameters: &Parameters) -> Result<Throughput, anyhow::Error> {
+        let mut throughput = Throughput {
+            records: 0,
+            bytes: 0,
+            seconds: 0.0,
+        };
+
+        let start = Instant::now();
+
+        for paths in parameters.tasks()? {
+            let mut reader = parameters.open_records(
+                paths,
+                None,
+                &self.open_files,
+                self.text_descriptor.as_ref(),
+            )?;
+
+            while let Some(record) = reader.try_get_next()? {
+                if let Some(message) = record.trailer_error {
+                    return Err(format_err!("position {}: {}", record.position, message));
+                }
+
+                self.decoder
+                    .decode(parameters, &record.bytes)
+                    .map_err(|err| err.context(format!("position {}", record.position)))?;
+
+                throughput.records += 1;
+                throughput.bytes += record.bytes.len() as u64;
+            }
+        }
+
+        throughput.seconds = start.elapsed().as_secs_f64();
+
+        Ok(throughput)
+    }
+}
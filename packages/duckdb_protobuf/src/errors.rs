@@ -0,0 +1,181 @@
+use crate::read::{MyFlatVector, VectorAccessor};
+use crate::scans::{ScanHistory, ScanId};
+use crate::vtab::{format_error_with_causes, Handle};
+use duckdb::vtab::{BindInfo, DataChunk, FunctionInfo, InitInfo, LogicalType, LogicalTypeId, VTab};
+use std::error::Error;
+use std::ffi::CString;
+use std::sync::{Arc, Mutex};
+use strum::{AsRefStr, EnumIter, EnumString};
+
+/// What to do with a record which fails to decode.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum OnError {
+    /// Fail the query.
+    Fail,
+    /// Skip the record and make the error available through
+    /// `protobuf_errors()`.
+    Skip,
+}
+
+pub struct RecordError {
+    pub filename: String,
+    pub position: u64,
+    pub message: String,
+}
+
+/// Collects the errors of records skipped during a single scan, along with
+/// the problems the scan reports without failing, like a `trailing_bytes =
+/// 'warn'` file. Every scan starts a new collector and the collectors of the
+/// most recent scans are kept around by scan id so they can be queried after
+/// the scans finish.
+#[derive(Default)]
+pub struct ErrorCollector {
+    errors: Mutex<Vec<RecordError>>,
+}
+
+static SCAN_ERRORS: ScanHistory<ErrorCollector> = ScanHistory::new();
+
+impl ErrorCollector {
+    pub fn start_scan(scan_id: ScanId) -> Arc<ErrorCollector> {
+        SCAN_ERRORS.start_scan(scan_id, ErrorCollector::default())
+    }
+
+    pub fn push(&self, error: RecordError) {
+        self.errors.lock().unwrap().push(error);
+    }
+
+    /// The errors of scan `scan_id`, or of the most recently started scan.
+    fn scan(scan_id: Option<ScanId>) -> Vec<(String, u64, String, ScanId)> {
+        let Some((scan_id, collector)) = SCAN_ERRORS.get(scan_id) else {
+            return vec![];
+        };
+
+        let errors = collector.errors.lock().unwrap();
+        errors
+            .iter()
+            .map(|it| {
+                (
+                    it.filename.clone(),
+                    it.position,
+                    it.message.clone(),
+                    scan_id,
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct ErrorsScanState {
+    errors: Vec<(String, u64, String, ScanId)>,
+    offset: usize,
+}
+
+/// `protobuf_errors()`, listing the records skipped and the problems reported
+/// by the most recent `protobuf()` scan, or by the scan given as `scan_id`.
+pub struct ProtobufErrorsVTab;
+
+impl VTab for ProtobufErrorsVTab {
+    type InitData = Handle<ErrorsScanState>;
+    type BindData = Handle<Option<ScanId>>;
+
+    unsafe fn bind(
+        bind: &BindInfo,
+        data: *mut Self::BindData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        let data = &mut *data;
+        data.init();
+
+        bind.add_result_column("filename", LogicalType::new(LogicalTypeId::Varchar));
+        bind.add_result_column("position", LogicalType::new(LogicalTypeId::UBigint));
+        bind.add_result_column("error", LogicalType::new(LogicalTypeId::Varchar));
+        bind.add_result_column("scan_id", LogicalType::new(LogicalTypeId::UBigint));
+
+        let scan_id = bind
+            .get_named_parameter("scan_id")
+            .map(|value| value.to_int64() as ScanId);
+        data.assign(scan_id);
+
+        Ok(())
+    }
+
+    unsafe fn init(
+        init_info: &InitInfo,
+        data: *mut Self::InitData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        let data = &mut *data;
+        data.init();
+
+        let bind_data = &*init_info.get_bind_data::<Self::BindData>();
+
+        init_info.set_max_threads(1);
+        data.assign(ErrorsScanState {
+            errors: ErrorCollector::scan(**bind_data),
+            offset: 0,
+        });
+
+        Ok(())
+    }
+
+    unsafe fn func(
+        func: &FunctionInfo,
+        output: &mut DataChunk,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::func(func, output).map_err(format_error_with_causes)?)
+    }
+
+    fn named_parameters() -> Option<Vec<(String, LogicalType)>> {
+        Some(vec![(
+            "scan_id".to_string(),
+            LogicalType::new(LogicalTypeId::UBigint),
+        )])
+    }
+}
+
+impl ProtobufErrorsVTab {
+    fn func(func: &FunctionInfo, output: &mut DataChunk) -> Result<(), anyhow::Error> {
+        let init_data = unsafe { &mut *func.get_init_data::<<Self as VTab>::InitData>() };
+
+        let available_chunk_size = output.flat_vector(0).capacity();
+        let remaining = &init_data.errors[init_data.offset..];
+        let items = remaining.len().min(available_chunk_size);
+
+        for (row_idx, (filename, position, message, scan_id)) in
+            remaining[..items].iter().enumerate()
+        {
+            let filename = CString::new(filename.as_str())?;
+            let message = CString::new(message.as_str())?;
+
+            unsafe {
+                duckdb::ffi::duckdb_vector_assign_string_element(
+                    output.get_vector(0),
+                    row_idx as _,
+                    filename.as_ptr(),
+                );
+            }
+
+            let mut positions = unsafe {
+                MyFlatVector::<u64>::with_capacity(output.get_vector(1), available_chunk_size)
+            };
+            positions.as_mut_slice()[row_idx] = *position;
+
+            let mut scan_ids = unsafe {
+                MyFlatVector::<u64>::with_capacity(output.get_vector(3), available_chunk_size)
+            };
+            scan_ids.as_mut_slice()[row_idx] = *scan_id;
+
+            unsafe {
+                duckdb::ffi::duckdb_vector_assign_string_element(
+                    output.get_vector(2),
+                    row_idx as _,
+                    message.as_ptr(),
+                );
+            }
+        }
+
+        init_data.offset += items;
+        output.set_len(items);
+
+        Ok(())
+    }
+}
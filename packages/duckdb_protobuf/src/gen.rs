@@ -0,0 +1,1515 @@
+//! Decodes messages from the protobuf wire format directly into duckdb
+//! vectors, without materializing an intermediate `DynamicMessage`.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::str;
+
+use anyhow::{bail, format_err, Context};
+use prost::bytes::Buf;
+use prost::encoding::{decode_key, decode_varint, skip_field, DecodeContext, WireType};
+use prost_reflect::{
+    Cardinality, DynamicMessage, EnumDescriptor, FieldDescriptor, Kind, MessageDescriptor,
+    SerializeOptions, Value,
+};
+
+use crate::read::{ColumnKey, ColumnKeyElement, MyFlatVector, StructVector, VectorAccessor};
+use crate::types::{
+    any_members, is_duration, is_json_value, is_timestamp, oneof_fields, row_columns,
+    struct_fields, wrapped_field, Column, DecodeOptions, InvalidUtf8, TimestampUnit, UnknownEnum,
+};
+use crate::wire::read_group;
+
+/// State carried across the rows of a single output chunk. Each chunk's list
+/// vectors start with empty child vectors, so a new state is used for every
+/// chunk rather than carrying offsets over from the previous one.
+#[derive(Default)]
+pub struct ParserState {
+    /// The next free offset in the child vector of every list column.
+    pub column_state: HashMap<ColumnKey, u64>,
+}
+
+impl ParserState {
+    /// The list offsets to return to with [`ParserState::rollback`].
+    pub fn checkpoint(&self) -> HashMap<ColumnKey, u64> {
+        self.column_state.clone()
+    }
+
+    /// Forgets the list elements pushed since `checkpoint`, like those of a
+    /// record which failed partway through. Its row is written over by the
+    /// next record, which then pushes its elements where the failed record's
+    /// started.
+    pub fn rollback(&mut self, checkpoint: HashMap<ColumnKey, u64>) {
+        self.column_state = checkpoint;
+    }
+}
+
+/// How the fields of a message map onto output vectors, computed once from
+/// the descriptor so decoding a record only needs to look up field numbers.
+pub struct MessageLayout {
+    descriptor: MessageDescriptor,
+    fields: Vec<FieldLayout>,
+    by_number: HashMap<u32, usize>,
+    oneofs: Vec<OneofLayout>,
+    /// See [`DecodeOptions::lenient`].
+    lenient: bool,
+}
+
+struct FieldLayout {
+    descriptor: FieldDescriptor,
+    column_idx: usize,
+    /// Key of the vector holding this field's values. For repeated fields
+    /// this is the key of the list's child vector.
+    column_key: ColumnKey,
+    value: ValueLayout,
+    /// Set for fields read as a member of their oneof's union, in which case
+    /// `column_idx` is the oneof's column.
+    oneof: Option<OneofMember>,
+    /// Whether the field is `NULL` rather than its default value when it's
+    /// absent from a record, see [`DecodeOptions::use_defaults`]. Always set
+    /// for proto3 `optional` fields.
+    null_when_absent: bool,
+}
+
+/// A oneof read as a struct, see [`Column::Oneof`].
+struct OneofLayout {
+    column_idx: usize,
+    /// Name of each member, indexed by union tag.
+    names: Vec<String>,
+}
+
+struct OneofMember {
+    /// Index of the oneof in [`MessageLayout::oneofs`].
+    oneof_idx: usize,
+    tag: u8,
+}
+
+enum ValueLayout {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String(InvalidUtf8),
+    Enum(EnumLayout),
+    EnumName(EnumNameLayout),
+    Timestamp(TimestampUnit),
+    Duration,
+    /// `google.protobuf.*Value` wrappers, read as the layout of their value.
+    Wrapper(Box<FieldLayout>),
+    Message(MessageLayout),
+    Any(AnyLayout),
+    /// Messages read as JSON text, see [`DecodeOptions::as_json`], and the
+    /// well-known types holding JSON, see [`is_json_value`].
+    Json(MessageDescriptor),
+    /// Messages nested deeper than [`DecodeOptions::max_depth`], read as
+    /// their encoded bytes.
+    Raw,
+    /// 64 bit integers read as their decimal text, holding the layout they're
+    /// decoded with, see [`DecodeOptions::int64_as_string`].
+    Int64String(Box<ValueLayout>),
+}
+
+/// `google.protobuf.Any` values read into a union with a member for each of
+/// the expected message types, followed by a member for any other type.
+/// Without expected types, values are read into the struct of that last
+/// member directly.
+struct AnyLayout {
+    /// Union tag of each expected message type, by full name.
+    tags: HashMap<String, u8>,
+    /// Layout of each expected message type, indexed by tag.
+    members: Vec<MessageLayout>,
+}
+
+struct EnumLayout {
+    /// Index of each enum number in duckdb's enum dictionary.
+    indices: HashMap<i32, u32>,
+    /// Index unknown numbers are read as, `None` when they're read as `NULL`.
+    default_idx: Option<u32>,
+    width: EnumWidth,
+}
+
+/// Enums read as text, see [`UnknownEnum::Preserve`].
+struct EnumNameLayout {
+    names: HashMap<i32, String>,
+}
+
+/// The physical type duckdb picks to store an enum, based on its number of
+/// values.
+enum EnumWidth {
+    U8,
+    U16,
+    U32,
+}
+
+impl MessageLayout {
+    /// Layout for the top level message, where only the projected fields are
+    /// decoded and each is written to the output column at its position in
+    /// `column_indices`.
+    pub fn for_columns(
+        descriptor: &MessageDescriptor,
+        column_indices: &[u64],
+        options: &DecodeOptions,
+    ) -> Result<MessageLayout, anyhow::Error> {
+        let fields = row_columns(descriptor, options)?;
+        let columns = column_indices
+            .iter()
+            .enumerate()
+            .filter_map(|(column_idx, field_idx)| {
+                Some((fields.get(*field_idx as usize)?.clone(), column_idx))
+            });
+
+        MessageLayout::new(descriptor, columns, &ColumnKey::empty(), options, 0)
+    }
+
+    /// Layout for a message written into a struct vector, where every field
+    /// is a child vector. `depth` is the number of messages it's nested in.
+    fn nested(
+        descriptor: &MessageDescriptor,
+        column_key: &ColumnKey,
+        options: &DecodeOptions,
+        depth: usize,
+    ) -> Result<MessageLayout, anyhow::Error> {
+        let columns = struct_fields(descriptor, options)
+            .into_iter()
+            .enumerate()
+            .map(|(column_idx, field)| (field, column_idx));
+
+        MessageLayout::new(descriptor, columns, column_key, options, depth)
+    }
+
+    fn new(
+        descriptor: &MessageDescriptor,
+        columns: impl Iterator<Item = (Column, usize)>,
+        column_key: &ColumnKey,
+        options: &DecodeOptions,
+        depth: usize,
+    ) -> Result<MessageLayout, anyhow::Error> {
+        let mut fields = vec![];
+        let mut by_number = HashMap::new();
+        let mut oneofs = vec![];
+
+        for (column, column_idx) in columns {
+            let members = match column {
+                Column::Field(field) => vec![(field, None)],
+                Column::Oneof(oneof) => {
+                    let members = oneof_fields(&oneof, options);
+                    let oneof_idx = oneofs.len();
+                    oneofs.push(OneofLayout {
+                        column_idx,
+                        names: members
+                            .iter()
+                            .map(|field| options.column_name(field).into_owned())
+                            .collect(),
+                    });
+
+                    members
+                        .into_iter()
+                        .enumerate()
+                        .map(|(tag, field)| {
+                            let tag = u8::try_from(tag).map_err(|_| {
+                                format_err!("oneof `{}`: too many fields", oneof.name())
+                            })?;
+                            Ok((field, Some(OneofMember { oneof_idx, tag })))
+                        })
+                        .collect::<Result<Vec<_>, anyhow::Error>>()?
+                }
+            };
+
+            for (field, oneof) in members {
+                let mut field_column_key = column_key.field(&field);
+                if is_repeated(&field) {
+                    field_column_key = field_column_key.extending(ColumnKeyElement::List);
+                }
+
+                let value = ValueLayout::new(&field, &field_column_key, options, depth)?;
+
+                // members of a oneof are already null unless they're set,
+                // proto3 `optional` fields track whether they're set
+                let null_when_absent = oneof.is_none()
+                    && !is_repeated(&field)
+                    && (!options.use_defaults || is_proto3_optional(&field));
+
+                by_number.insert(field.number(), fields.len());
+                fields.push(FieldLayout {
+                    descriptor: field,
+                    column_idx,
+                    column_key: field_column_key,
+                    value,
+                    oneof,
+                    null_when_absent,
+                });
+            }
+        }
+
+        Ok(MessageLayout {
+            descriptor: descriptor.clone(),
+            fields,
+            by_number,
+            oneofs,
+            lenient: options.lenient,
+        })
+    }
+
+    pub fn descriptor(&self) -> &MessageDescriptor {
+        &self.descriptor
+    }
+}
+
+impl ValueLayout {
+    /// Layout of a field of a message nested `depth` levels deep.
+    fn new(
+        field: &FieldDescriptor,
+        column_key: &ColumnKey,
+        options: &DecodeOptions,
+        depth: usize,
+    ) -> Result<ValueLayout, anyhow::Error> {
+        let value = match options.field_kind(field)? {
+            Kind::Message(message_descriptor) if options.as_json(field) => {
+                ValueLayout::Json(message_descriptor)
+            }
+            Kind::Message(message_descriptor) if is_timestamp(&message_descriptor) => {
+                ValueLayout::Timestamp(options.timestamp_unit)
+            }
+            Kind::Message(message_descriptor) if is_duration(&message_descriptor) => {
+                ValueLayout::Duration
+            }
+            Kind::Message(message_descriptor) if is_json_value(&message_descriptor) => {
+                ValueLayout::Json(message_descriptor)
+            }
+            Kind::Message(message_descriptor) if wrapped_field(&message_descriptor).is_some() => {
+                let value_field = wrapped_field(&message_descriptor).unwrap();
+                let value = ValueLayout::new(&value_field, column_key, options, depth)?;
+
+                ValueLayout::Wrapper(Box::new(FieldLayout {
+                    descriptor: value_field,
+                    column_idx: 0,
+                    column_key: column_key.clone(),
+                    value,
+                    oneof: None,
+                    // a present wrapper without its value holds the default
+                    null_when_absent: false,
+                }))
+            }
+            Kind::Message(..) if depth >= options.max_depth => ValueLayout::Raw,
+            Kind::Message(message_descriptor)
+                if message_descriptor.full_name() == "google.protobuf.Any" =>
+            {
+                ValueLayout::Any(AnyLayout::new(
+                    &message_descriptor,
+                    column_key,
+                    options,
+                    depth,
+                )?)
+            }
+            // map fields are repeated map entry messages, their key and value
+            // are found by the field numbers in the entry's descriptor
+            Kind::Message(message_descriptor) => ValueLayout::Message(MessageLayout::nested(
+                &message_descriptor,
+                column_key,
+                options,
+                depth + 1,
+            )?),
+            Kind::Enum(enum_descriptor) => match options.unknown_enum {
+                UnknownEnum::Default => ValueLayout::Enum(EnumLayout::new(&enum_descriptor, false)),
+                UnknownEnum::Null => ValueLayout::Enum(EnumLayout::new(&enum_descriptor, true)),
+                UnknownEnum::Preserve => {
+                    ValueLayout::EnumName(EnumNameLayout::new(&enum_descriptor))
+                }
+            },
+            Kind::Double => ValueLayout::Double,
+            Kind::Float => ValueLayout::Float,
+            Kind::Int32 => ValueLayout::Int32,
+            Kind::Int64 => ValueLayout::Int64,
+            Kind::Uint32 => ValueLayout::Uint32,
+            Kind::Uint64 => ValueLayout::Uint64,
+            Kind::Sint32 => ValueLayout::Sint32,
+            Kind::Sint64 => ValueLayout::Sint64,
+            Kind::Fixed32 => ValueLayout::Fixed32,
+            Kind::Fixed64 => ValueLayout::Fixed64,
+            Kind::Sfixed32 => ValueLayout::Sfixed32,
+            Kind::Sfixed64 => ValueLayout::Sfixed64,
+            Kind::Bool => ValueLayout::Bool,
+            Kind::String => ValueLayout::String(options.invalid_utf8),
+            kind => bail!("unhandled field: {}, type: {:?}", field.name(), kind),
+        };
+
+        // decimals and times keep their integer layout, see
+        // `DecodeOptions::decimal_scale` and `DecodeOptions::time_fields`
+        if options.int64_as_string
+            && options.decimal_scale(field)?.is_none()
+            && !options.is_time(field)
+        {
+            if let ValueLayout::Int64
+            | ValueLayout::Uint64
+            | ValueLayout::Sint64
+            | ValueLayout::Fixed64
+            | ValueLayout::Sfixed64 = value
+            {
+                return Ok(ValueLayout::Int64String(Box::new(value)));
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// The wire type a single value is encoded with.
+    fn wire_type(&self) -> WireType {
+        self.packed_wire_type().unwrap_or(WireType::LengthDelimited)
+    }
+
+    /// The wire type of a single value, used to decode the elements of packed
+    /// repeated fields. `None` for values which can't be packed.
+    fn packed_wire_type(&self) -> Option<WireType> {
+        match self {
+            ValueLayout::Int32
+            | ValueLayout::Int64
+            | ValueLayout::Uint32
+            | ValueLayout::Uint64
+            | ValueLayout::Sint32
+            | ValueLayout::Sint64
+            | ValueLayout::Bool
+            | ValueLayout::Enum(..)
+            | ValueLayout::EnumName(..) => Some(WireType::Varint),
+            ValueLayout::Double | ValueLayout::Fixed64 | ValueLayout::Sfixed64 => {
+                Some(WireType::SixtyFourBit)
+            }
+            ValueLayout::Float | ValueLayout::Fixed32 | ValueLayout::Sfixed32 => {
+                Some(WireType::ThirtyTwoBit)
+            }
+            ValueLayout::String(..)
+            | ValueLayout::Timestamp(..)
+            | ValueLayout::Duration
+            | ValueLayout::Wrapper(..)
+            | ValueLayout::Message(..)
+            | ValueLayout::Any(..)
+            | ValueLayout::Json(..)
+            | ValueLayout::Raw => None,
+            ValueLayout::Int64String(value) => value.packed_wire_type(),
+        }
+    }
+}
+
+impl FieldLayout {
+    /// Whether an occurrence of the field can be encoded with `wire_type`.
+    /// Repeated scalars may be packed and proto2 groups may be encoded as
+    /// length delimited messages.
+    fn accepts_wire_type(&self, wire_type: WireType) -> bool {
+        match self.value.packed_wire_type() {
+            Some(expected) => {
+                wire_type == expected
+                    || (is_repeated(&self.descriptor) && wire_type == WireType::LengthDelimited)
+            }
+            None => {
+                wire_type == WireType::LengthDelimited
+                    || (self.descriptor.is_group() && wire_type == WireType::StartGroup)
+            }
+        }
+    }
+}
+
+impl EnumLayout {
+    fn new(enum_descriptor: &EnumDescriptor, unknown_null: bool) -> EnumLayout {
+        let mut indices = HashMap::new();
+        for (idx, value) in enum_descriptor.values().enumerate() {
+            indices.entry(value.number()).or_insert(idx as u32);
+        }
+
+        let default_idx =
+            (!unknown_null).then(|| indices[&enum_descriptor.default_value().number()]);
+
+        let len = enum_descriptor.values().len();
+        let width = if len <= u8::MAX as usize {
+            EnumWidth::U8
+        } else if len <= u16::MAX as usize {
+            EnumWidth::U16
+        } else {
+            EnumWidth::U32
+        };
+
+        EnumLayout {
+            indices,
+            default_idx,
+            width,
+        }
+    }
+}
+
+impl AnyLayout {
+    fn new(
+        any_descriptor: &MessageDescriptor,
+        column_key: &ColumnKey,
+        options: &DecodeOptions,
+        depth: usize,
+    ) -> Result<AnyLayout, anyhow::Error> {
+        let mut tags = HashMap::new();
+        let mut members = vec![];
+
+        for (tag, member) in any_members(any_descriptor, options)?.iter().enumerate() {
+            let tag = u8::try_from(tag).map_err(|_| format_err!("too many `any_types`"))?;
+            let member_key = column_key.extending(ColumnKeyElement::UnionMember { tag });
+
+            tags.insert(member.full_name().to_string(), tag);
+            members.push(MessageLayout::nested(
+                member,
+                &member_key,
+                options,
+                depth + 1,
+            )?);
+        }
+
+        Ok(AnyLayout { tags, members })
+    }
+
+    fn raw_tag(&self) -> u8 {
+        self.members.len() as u8
+    }
+}
+
+impl EnumNameLayout {
+    fn new(enum_descriptor: &EnumDescriptor) -> EnumNameLayout {
+        let mut names = HashMap::new();
+        for value in enum_descriptor.values() {
+            names
+                .entry(value.number())
+                .or_insert_with(|| value.name().to_string());
+        }
+
+        EnumNameLayout { names }
+    }
+}
+
+/// Whether a field is read as a list, a repeated field or a map. Maps are
+/// read as a list of their entries, each entry being decoded like any other
+/// message, by the field numbers of the entry's descriptor. prost-reflect's
+/// `is_list` is false for them.
+fn is_repeated(field: &FieldDescriptor) -> bool {
+    field.cardinality() == Cardinality::Repeated
+}
+
+/// Whether a field is declared `optional` in a proto3 file, which gives it
+/// explicit presence through a synthetic oneof.
+fn is_proto3_optional(field: &FieldDescriptor) -> bool {
+    field
+        .containing_oneof()
+        .map_or(false, |oneof| oneof.is_synthetic())
+}
+
+/// Decodes an encoded message into row `row_idx` of `output`. Fields absent
+/// from the message are filled with their default values, or `NULL` unless
+/// [`DecodeOptions::use_defaults`] is set. Absent proto3 `optional` fields
+/// are always `NULL`.
+pub fn parse_message(
+    state: &mut ParserState,
+    layout: &MessageLayout,
+    bytes: &[u8],
+    output: &impl VectorAccessor,
+    max_rows: usize,
+    row_idx: usize,
+) -> Result<(), anyhow::Error> {
+    let mut seen = vec![false; layout.fields.len()];
+    // fields skipped for being encoded with the wrong wire type
+    let mut mismatched = vec![false; layout.fields.len()];
+    // the tag of the member last set of each oneof
+    let mut active = vec![None; layout.oneofs.len()];
+
+    // Singular message fields are decoded once all of their occurrences are
+    // known, as repeated occurrences are merged.
+    let mut messages: Vec<(usize, Cow<[u8]>)> = vec![];
+
+    let mut buf = bytes;
+    while buf.has_remaining() {
+        let (number, wire_type) = decode_key(&mut buf)?;
+        let Some(&field_idx) = layout.by_number.get(&number) else {
+            skip_field(wire_type, number, &mut buf, DecodeContext::default())?;
+            continue;
+        };
+
+        let field = &layout.fields[field_idx];
+        if !field.accepts_wire_type(wire_type) {
+            if !layout.lenient {
+                return Err(format_err!(
+                    "expected wire type {:?}, got {:?}",
+                    field.value.wire_type(),
+                    wire_type
+                ))
+                .with_context(|| field_context(field));
+            }
+
+            skip_field(wire_type, number, &mut buf, DecodeContext::default())?;
+            mismatched[field_idx] = true;
+            continue;
+        }
+
+        let column = field_vector(field, output);
+        let first_occurrence = !std::mem::replace(&mut seen[field_idx], true);
+
+        if first_occurrence && field.null_when_absent {
+            set_valid(column, row_idx);
+        }
+
+        if let Some(member) = &field.oneof {
+            // setting a member clears the others, so a message member set
+            // before another one isn't merged into
+            if active[member.oneof_idx].replace(member.tag) != Some(member.tag) {
+                messages.retain(|(idx, _)| match &layout.fields[*idx].oneof {
+                    Some(other) => other.oneof_idx != member.oneof_idx,
+                    None => true,
+                });
+            }
+        }
+
+        if is_repeated(&field.descriptor) {
+            handle_repeated_field(
+                state,
+                field,
+                wire_type,
+                &mut buf,
+                column,
+                max_rows,
+                row_idx,
+                first_occurrence,
+            )
+            .with_context(|| field_context(field))?;
+        } else if let ValueLayout::Message(..) | ValueLayout::Any(..) | ValueLayout::Raw =
+            field.value
+        {
+            let payload =
+                read_message(field, wire_type, &mut buf).with_context(|| field_context(field))?;
+            match messages.iter_mut().find(|(idx, _)| *idx == field_idx) {
+                Some((_, existing)) => existing.to_mut().extend_from_slice(payload),
+                None => messages.push((field_idx, Cow::Borrowed(payload))),
+            }
+        } else {
+            parse_field(state, field, wire_type, &mut buf, column, max_rows, row_idx)
+                .with_context(|| field_context(field))?;
+        }
+    }
+
+    for (field_idx, payload) in messages {
+        let field = &layout.fields[field_idx];
+        let column = field_vector(field, output);
+        write_message(state, field, &payload, column, max_rows, row_idx)
+            .with_context(|| field_context(field))?;
+    }
+
+    for (field, seen) in layout.fields.iter().zip(seen) {
+        match &field.oneof {
+            None if !seen => {
+                let column = output.get_vector(field.column_idx);
+                write_default(state, field, column, max_rows, row_idx)?;
+                if field.null_when_absent {
+                    set_null(column, row_idx);
+                }
+            }
+            None => {}
+            // every member but the active one is null, including members
+            // written before another one was set
+            Some(member) if active[member.oneof_idx] != Some(member.tag) => {
+                let column = field_vector(field, output);
+                write_default(state, field, column, max_rows, row_idx)?;
+                set_null(column, row_idx);
+            }
+            Some(..) => set_valid(field_vector(field, output), row_idx),
+        }
+    }
+
+    // members of a oneof skipped this way are left unset instead
+    for (field, mismatched) in layout.fields.iter().zip(mismatched) {
+        if mismatched && field.oneof.is_none() {
+            set_null(output.get_vector(field.column_idx), row_idx);
+        }
+    }
+
+    for (oneof, active) in layout.oneofs.iter().zip(active) {
+        write_oneof(
+            oneof,
+            active,
+            output.get_vector(oneof.column_idx),
+            max_rows,
+            row_idx,
+        );
+    }
+
+    Ok(())
+}
+
+/// Context of the errors decoding a field. Errors of nested messages get the
+/// context of each enclosing field, naming the path to the offending field.
+fn field_context(field: &FieldLayout) -> anyhow::Error {
+    format_err!("field `{}`", field.descriptor.name())
+}
+
+/// The vector a field's values are written to, the union member of fields
+/// read as part of a oneof.
+fn field_vector(field: &FieldLayout, output: &impl VectorAccessor) -> duckdb::ffi::duckdb_vector {
+    let column = output.get_vector(field.column_idx);
+    let Some(member) = &field.oneof else {
+        return column;
+    };
+
+    let value = unsafe { StructVector::new(column) }.get_vector(1);
+    unsafe { StructVector::new(value) }.get_vector(member.tag as usize + 1)
+}
+
+/// Writes the `tag` of a oneof's struct and the tag of its `value` union,
+/// once its members are written.
+fn write_oneof(
+    oneof: &OneofLayout,
+    active: Option<u8>,
+    column: duckdb::ffi::duckdb_vector,
+    max_rows: usize,
+    row_idx: usize,
+) {
+    let source = unsafe { StructVector::new(column) };
+    let tag = source.get_vector(0);
+    let value = source.get_vector(1);
+    let union_tag = unsafe { StructVector::new(value) }.get_vector(0);
+
+    match active {
+        Some(active) => {
+            write_str(tag, row_idx, &oneof.names[active as usize]);
+            write_value(union_tag, max_rows, row_idx, active);
+            set_valid(tag, row_idx);
+            set_valid(value, row_idx);
+            set_valid(column, row_idx);
+        }
+        None => {
+            write_value(union_tag, max_rows, row_idx, 0u8);
+            set_null(tag, row_idx);
+            set_null(value, row_idx);
+            set_null(column, row_idx);
+        }
+    }
+}
+
+/// Appends the element(s) of a repeated field occurrence to the row's list.
+/// Scalars may arrive packed, several elements in a single length delimited
+/// record, or one element per record.
+#[allow(clippy::too_many_arguments)]
+fn handle_repeated_field(
+    state: &mut ParserState,
+    field: &FieldLayout,
+    wire_type: WireType,
+    buf: &mut &[u8],
+    column: duckdb::ffi::duckdb_vector,
+    max_rows: usize,
+    row_idx: usize,
+    first_occurrence: bool,
+) -> Result<(), anyhow::Error> {
+    let mut list_entries =
+        unsafe { MyFlatVector::<duckdb::ffi::duckdb_list_entry>::with_capacity(column, max_rows) };
+    let list_entry = &mut list_entries.as_mut_slice()[row_idx];
+
+    if first_occurrence {
+        list_entry.offset = next_list_offset(state, &field.column_key);
+        list_entry.length = 0;
+    }
+
+    let child_vector = unsafe { duckdb::ffi::duckdb_list_vector_get_child(column) };
+
+    match field.value.packed_wire_type() {
+        // elements read as text are converted one at a time below
+        Some(element_wire_type @ (WireType::SixtyFourBit | WireType::ThirtyTwoBit))
+            if wire_type == WireType::LengthDelimited
+                && !matches!(field.value, ValueLayout::Int64String(..)) =>
+        {
+            let packed = read_length_delimited(wire_type, buf)?;
+            let element_size = match element_wire_type {
+                WireType::SixtyFourBit => 8,
+                _ => 4,
+            };
+
+            if packed.len() % element_size != 0 {
+                bail!(
+                    "packed length {} isn't a multiple of {}",
+                    packed.len(),
+                    element_size
+                );
+            }
+
+            // the number of elements is known up front, so the child vector
+            // is grown once and the elements are copied in bulk, they're
+            // stored by duckdb with the same width and byte order as on the
+            // wire
+            let count = packed.len() / element_size;
+            let first_child_idx =
+                push_list_elements(state, &field.column_key, column, list_entry, count);
+
+            unsafe {
+                let data = duckdb::ffi::duckdb_vector_get_data(child_vector).cast::<u8>();
+                copy_le_elements(
+                    packed,
+                    data.add(first_child_idx * element_size),
+                    element_size,
+                );
+            }
+        }
+        // each element is read like an unpacked one, so `sint` elements are
+        // zigzag decoded
+        Some(element_wire_type) if wire_type == WireType::LengthDelimited => {
+            let mut packed = read_length_delimited(wire_type, buf)?;
+            while packed.has_remaining() {
+                let child_idx = push_list_element(state, &field.column_key, column, list_entry);
+                parse_field(
+                    state,
+                    field,
+                    element_wire_type,
+                    &mut packed,
+                    child_vector,
+                    child_idx + 1,
+                    child_idx,
+                )?;
+            }
+        }
+        _ => {
+            let child_idx = push_list_element(state, &field.column_key, column, list_entry);
+            parse_field(
+                state,
+                field,
+                wire_type,
+                buf,
+                child_vector,
+                child_idx + 1,
+                child_idx,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn next_list_offset(state: &ParserState, column_key: &ColumnKey) -> u64 {
+    state.column_state.get(column_key).copied().unwrap_or(0)
+}
+
+/// Grows the list's child vector by one element, returning the index of the
+/// new element.
+fn push_list_element(
+    state: &mut ParserState,
+    column_key: &ColumnKey,
+    column: duckdb::ffi::duckdb_vector,
+    list_entry: &mut duckdb::ffi::duckdb_list_entry,
+) -> usize {
+    push_list_elements(state, column_key, column, list_entry, 1)
+}
+
+/// Grows the list's child vector by `count` elements, returning the index of
+/// the first new element.
+fn push_list_elements(
+    state: &mut ParserState,
+    column_key: &ColumnKey,
+    column: duckdb::ffi::duckdb_vector,
+    list_entry: &mut duckdb::ffi::duckdb_list_entry,
+    count: usize,
+) -> usize {
+    let child_idx = list_entry.offset + list_entry.length;
+    // a list's elements are contiguous, nothing else may have been pushed to
+    // the child vector since its last element
+    debug_assert_eq!(child_idx, next_list_offset(state, column_key));
+    list_entry.length += count as u64;
+
+    let new_length = child_idx + count as u64;
+    match state.column_state.get_mut(column_key) {
+        Some(it) => *it = new_length,
+        None => {
+            state.column_state.insert(column_key.clone(), new_length);
+        }
+    }
+
+    unsafe {
+        duckdb::ffi::duckdb_list_vector_reserve(column, new_length);
+        duckdb::ffi::duckdb_list_vector_set_size(column, new_length);
+    }
+
+    child_idx as usize
+}
+
+/// Copies little endian fixed width values into a vector's data.
+///
+/// # Safety
+///
+/// `data` must have room for all of `packed`.
+unsafe fn copy_le_elements(packed: &[u8], data: *mut u8, element_size: usize) {
+    if cfg!(target_endian = "little") {
+        std::ptr::copy_nonoverlapping(packed.as_ptr(), data, packed.len());
+        return;
+    }
+
+    for (idx, element) in packed.chunks_exact(element_size).enumerate() {
+        for (byte_idx, byte) in element.iter().rev().enumerate() {
+            *data.add(idx * element_size + byte_idx) = *byte;
+        }
+    }
+}
+
+/// Decodes a single value of a field and writes it to `column` at `row_idx`.
+fn parse_field(
+    state: &mut ParserState,
+    field: &FieldLayout,
+    wire_type: WireType,
+    buf: &mut &[u8],
+    column: duckdb::ffi::duckdb_vector,
+    max_rows: usize,
+    row_idx: usize,
+) -> Result<(), anyhow::Error> {
+    match &field.value {
+        ValueLayout::Double => {
+            check_wire_type(WireType::SixtyFourBit, wire_type)?;
+            let value = f64::from_le_bytes(read_fixed_bytes(buf)?);
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Float => {
+            check_wire_type(WireType::ThirtyTwoBit, wire_type)?;
+            let value = f32::from_le_bytes(read_fixed_bytes(buf)?);
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Int32 => {
+            let value = read_varint_value(wire_type, buf)? as i32;
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Int64 => {
+            let value = read_varint_value(wire_type, buf)? as i64;
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Uint32 => {
+            let value = read_varint_value(wire_type, buf)? as u32;
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Uint64 => {
+            let value = read_varint_value(wire_type, buf)?;
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Sint32 => {
+            let value = read_sint_value(wire_type, buf)? as i32;
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Sint64 => {
+            let value = read_sint_value(wire_type, buf)?;
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Fixed32 => {
+            check_wire_type(WireType::ThirtyTwoBit, wire_type)?;
+            let value = u32::from_le_bytes(read_fixed_bytes(buf)?);
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Fixed64 => {
+            check_wire_type(WireType::SixtyFourBit, wire_type)?;
+            let value = u64::from_le_bytes(read_fixed_bytes(buf)?);
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Sfixed32 => {
+            check_wire_type(WireType::ThirtyTwoBit, wire_type)?;
+            let value = i32::from_le_bytes(read_fixed_bytes(buf)?);
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Sfixed64 => {
+            check_wire_type(WireType::SixtyFourBit, wire_type)?;
+            let value = i64::from_le_bytes(read_fixed_bytes(buf)?);
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Int64String(value) => {
+            let text = match **value {
+                ValueLayout::Int64 => (read_varint_value(wire_type, buf)? as i64).to_string(),
+                ValueLayout::Uint64 => read_varint_value(wire_type, buf)?.to_string(),
+                ValueLayout::Sint64 => read_sint_value(wire_type, buf)?.to_string(),
+                ValueLayout::Fixed64 => {
+                    check_wire_type(WireType::SixtyFourBit, wire_type)?;
+                    u64::from_le_bytes(read_fixed_bytes(buf)?).to_string()
+                }
+                ValueLayout::Sfixed64 => {
+                    check_wire_type(WireType::SixtyFourBit, wire_type)?;
+                    i64::from_le_bytes(read_fixed_bytes(buf)?).to_string()
+                }
+                _ => unreachable!("field `{}` isn't a 64 bit integer", field.descriptor.name()),
+            };
+            write_str(column, row_idx, &text);
+        }
+        ValueLayout::Bool => {
+            let value = read_varint_value(wire_type, buf)? != 0;
+            write_value(column, max_rows, row_idx, value);
+        }
+        ValueLayout::Enum(enum_layout) => {
+            let value = read_varint_value(wire_type, buf)? as i32;
+            write_enum(enum_layout, column, max_rows, row_idx, value);
+        }
+        ValueLayout::EnumName(enum_layout) => {
+            let value = read_varint_value(wire_type, buf)? as i32;
+            write_enum_name(enum_layout, column, row_idx, value);
+        }
+        ValueLayout::String(invalid_utf8) => {
+            let value = read_length_delimited(wire_type, buf)?;
+            let value = match invalid_utf8 {
+                InvalidUtf8::Error => Cow::Borrowed(str::from_utf8(value)?),
+                InvalidUtf8::Replace => String::from_utf8_lossy(value),
+            };
+            write_str(column, row_idx, &value);
+        }
+        ValueLayout::Timestamp(unit) => {
+            let message = read_length_delimited(wire_type, buf)?;
+            write_value(column, max_rows, row_idx, timestamp_value(message, *unit)?);
+        }
+        ValueLayout::Duration => {
+            let message = read_length_delimited(wire_type, buf)?;
+            write_value(column, max_rows, row_idx, duration_interval(message)?);
+        }
+        ValueLayout::Wrapper(value_field) => {
+            let mut message = read_length_delimited(wire_type, buf)?;
+
+            // a present wrapper without its value holds the value's default
+            set_valid(column, row_idx);
+            write_default(state, value_field, column, max_rows, row_idx)?;
+
+            while message.has_remaining() {
+                let (number, wire_type) = decode_key(&mut message)?;
+                if number == 1 {
+                    parse_field(
+                        state,
+                        value_field,
+                        wire_type,
+                        &mut message,
+                        column,
+                        max_rows,
+                        row_idx,
+                    )?;
+                } else {
+                    skip_field(wire_type, number, &mut message, DecodeContext::default())?;
+                }
+            }
+        }
+        ValueLayout::Message(..) | ValueLayout::Any(..) | ValueLayout::Raw => {
+            let message = read_message(field, wire_type, buf)?;
+            write_message(state, field, message, column, max_rows, row_idx)?;
+        }
+        ValueLayout::Json(message_descriptor) => {
+            let message = read_message(field, wire_type, buf)?;
+            let message = DynamicMessage::decode(message_descriptor.clone(), message)?;
+
+            let mut json = serde_json::Serializer::new(vec![]);
+            message.serialize_with_options(
+                &mut json,
+                &SerializeOptions::new().use_proto_field_name(true),
+            )?;
+
+            write_bytes(column, row_idx, &json.into_inner());
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the encoded value of a message field, `Message`, `Any` or `Raw`,
+/// to `column` at `row_idx`.
+fn write_message(
+    state: &mut ParserState,
+    field: &FieldLayout,
+    mut message: &[u8],
+    column: duckdb::ffi::duckdb_vector,
+    max_rows: usize,
+    row_idx: usize,
+) -> Result<(), anyhow::Error> {
+    match &field.value {
+        ValueLayout::Message(message_layout) => {
+            let source = unsafe { StructVector::new(column) };
+
+            parse_message(state, message_layout, message, &source, max_rows, row_idx)?;
+        }
+        ValueLayout::Any(any_layout) => {
+            let mut type_url: &[u8] = &[];
+            let mut value: &[u8] = &[];
+            while message.has_remaining() {
+                let (number, wire_type) = decode_key(&mut message)?;
+                match (number, wire_type) {
+                    (1, WireType::LengthDelimited) => {
+                        type_url = read_length_delimited(wire_type, &mut message)?
+                    }
+                    (2, WireType::LengthDelimited) => {
+                        value = read_length_delimited(wire_type, &mut message)?
+                    }
+                    (number, wire_type) => {
+                        skip_field(wire_type, number, &mut message, DecodeContext::default())?
+                    }
+                }
+            }
+
+            let type_url = str::from_utf8(type_url)?;
+
+            write_any(
+                state, any_layout, type_url, value, column, max_rows, row_idx,
+            )?;
+        }
+        ValueLayout::Raw => {
+            set_valid(column, row_idx);
+            write_bytes(column, row_idx, message);
+        }
+        _ => unreachable!("field `{}` isn't a message", field.descriptor.name()),
+    }
+
+    Ok(())
+}
+
+/// Converts an encoded `google.protobuf.Timestamp` to the units since the
+/// epoch duckdb stores a timestamp of that precision as. `nanos` is always
+/// positive, even before 1970, so it's added to the seconds, and is truncated
+/// to the unit.
+fn timestamp_value(message: &[u8], unit: TimestampUnit) -> Result<i64, anyhow::Error> {
+    let (seconds, nanos) = read_seconds_and_nanos(message)?;
+    let per_second = unit.per_second();
+
+    seconds
+        .checked_mul(per_second)
+        .and_then(|value| value.checked_add(nanos as i64 / (1_000_000_000 / per_second)))
+        .ok_or_else(|| format_err!("timestamp out of range: {} seconds", seconds))
+}
+
+/// Converts an encoded `google.protobuf.Duration` to an `INTERVAL` of only
+/// microseconds. `nanos` has the same sign as `seconds`, so negative
+/// durations are truncated towards zero too.
+fn duration_interval(message: &[u8]) -> Result<duckdb::ffi::duckdb_interval, anyhow::Error> {
+    let (seconds, nanos) = read_seconds_and_nanos(message)?;
+
+    let micros = seconds
+        .checked_mul(1000000)
+        .and_then(|micros| micros.checked_add(nanos as i64 / 1000))
+        .ok_or_else(|| format_err!("duration out of range: {} seconds", seconds))?;
+
+    Ok(duckdb::ffi::duckdb_interval {
+        months: 0,
+        days: 0,
+        micros,
+    })
+}
+
+/// Reads the `seconds` and `nanos` fields shared by `Timestamp` and
+/// `Duration`.
+fn read_seconds_and_nanos(mut message: &[u8]) -> Result<(i64, i32), anyhow::Error> {
+    let mut seconds = 0i64;
+    let mut nanos = 0i32;
+    while message.has_remaining() {
+        let (number, wire_type) = decode_key(&mut message)?;
+        match (number, wire_type) {
+            (1, WireType::Varint) => seconds = decode_varint(&mut message)? as i64,
+            (2, WireType::Varint) => nanos = decode_varint(&mut message)? as i32,
+            (number, wire_type) => {
+                skip_field(wire_type, number, &mut message, DecodeContext::default())?
+            }
+        }
+    }
+
+    Ok((seconds, nanos))
+}
+
+/// Writes an `Any` value to its union member, the one for its message type if
+/// it's one of the expected types, the raw member otherwise. The other
+/// members are `NULL`.
+fn write_any(
+    state: &mut ParserState,
+    any_layout: &AnyLayout,
+    type_url: &str,
+    value: &[u8],
+    column: duckdb::ffi::duckdb_vector,
+    max_rows: usize,
+    row_idx: usize,
+) -> Result<(), anyhow::Error> {
+    if any_layout.members.is_empty() {
+        write_any_raw(column, row_idx, type_url, value);
+        return Ok(());
+    }
+
+    let source = unsafe { StructVector::new(column) };
+
+    // type urls are like `type.googleapis.com/package.Message`
+    let type_name = type_url.rsplit('/').next().unwrap_or_default();
+    let active_tag = any_layout
+        .tags
+        .get(type_name)
+        .copied()
+        .unwrap_or(any_layout.raw_tag());
+
+    write_value(source.get_vector(0), max_rows, row_idx, active_tag);
+
+    for (tag, member_layout) in any_layout.members.iter().enumerate() {
+        let member = source.get_vector(tag + 1);
+        let member_source = unsafe { StructVector::new(member) };
+
+        if tag as u8 == active_tag {
+            set_valid(member, row_idx);
+            parse_message(
+                state,
+                member_layout,
+                value,
+                &member_source,
+                max_rows,
+                row_idx,
+            )?;
+        } else {
+            write_defaults(state, member_layout, &member_source, max_rows, row_idx)?;
+            set_null(member, row_idx);
+        }
+    }
+
+    let raw = source.get_vector(any_layout.raw_tag() as usize + 1);
+    if active_tag == any_layout.raw_tag() {
+        set_valid(raw, row_idx);
+        write_any_raw(raw, row_idx, type_url, value);
+    } else {
+        write_any_raw(raw, row_idx, "", &[]);
+        set_null(raw, row_idx);
+    }
+
+    Ok(())
+}
+
+/// Writes the `type_url` and encoded `value` of an `Any` to a struct of the
+/// two.
+fn write_any_raw(column: duckdb::ffi::duckdb_vector, row_idx: usize, type_url: &str, value: &[u8]) {
+    let source = unsafe { StructVector::new(column) };
+    write_str(source.get_vector(0), row_idx, type_url);
+    write_bytes(source.get_vector(1), row_idx, value);
+}
+
+/// Writes the value of a field absent from the message.
+fn write_default(
+    state: &mut ParserState,
+    field: &FieldLayout,
+    column: duckdb::ffi::duckdb_vector,
+    max_rows: usize,
+    row_idx: usize,
+) -> Result<(), anyhow::Error> {
+    if is_repeated(&field.descriptor) {
+        let mut list_entries = unsafe {
+            MyFlatVector::<duckdb::ffi::duckdb_list_entry>::with_capacity(column, max_rows)
+        };
+        let list_entry = &mut list_entries.as_mut_slice()[row_idx];
+        list_entry.offset = next_list_offset(state, &field.column_key);
+        list_entry.length = 0;
+
+        return Ok(());
+    }
+
+    let value = field.descriptor.default_value();
+
+    match &field.value {
+        ValueLayout::Double => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value.as_f64().unwrap_or_default(),
+        ),
+        ValueLayout::Float => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value.as_f32().unwrap_or_default(),
+        ),
+        // enums read as their number, see `DecodeOptions::enum_as_int`
+        ValueLayout::Int32 => write_value(column, max_rows, row_idx, enum_number(&value)),
+        ValueLayout::Int64 => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value.as_i64().unwrap_or_default(),
+        ),
+        ValueLayout::Uint32 => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value.as_u32().unwrap_or_default(),
+        ),
+        ValueLayout::Uint64 => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value.as_u64().unwrap_or_default(),
+        ),
+        ValueLayout::Sint32 => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value.as_i32().unwrap_or_default(),
+        ),
+        ValueLayout::Sint64 => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value.as_i64().unwrap_or_default(),
+        ),
+        // fields can be read with the other signedness, see
+        // `DecodeOptions::fixed_signedness`
+        ValueLayout::Fixed32 => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value
+                .as_u32()
+                .or_else(|| value.as_i32().map(|it| it as u32))
+                .unwrap_or_default(),
+        ),
+        ValueLayout::Fixed64 => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value
+                .as_u64()
+                .or_else(|| value.as_i64().map(|it| it as u64))
+                .unwrap_or_default(),
+        ),
+        ValueLayout::Sfixed32 => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value
+                .as_i32()
+                .or_else(|| value.as_u32().map(|it| it as i32))
+                .unwrap_or_default(),
+        ),
+        ValueLayout::Sfixed64 => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value
+                .as_i64()
+                .or_else(|| value.as_u64().map(|it| it as i64))
+                .unwrap_or_default(),
+        ),
+        ValueLayout::Int64String(layout) => {
+            let text = match **layout {
+                ValueLayout::Uint64 | ValueLayout::Fixed64 => value
+                    .as_u64()
+                    .or_else(|| value.as_i64().map(|it| it as u64))
+                    .unwrap_or_default()
+                    .to_string(),
+                _ => value
+                    .as_i64()
+                    .or_else(|| value.as_u64().map(|it| it as i64))
+                    .unwrap_or_default()
+                    .to_string(),
+            };
+            write_str(column, row_idx, &text);
+        }
+        ValueLayout::Bool => write_value(
+            column,
+            max_rows,
+            row_idx,
+            value.as_bool().unwrap_or_default(),
+        ),
+        ValueLayout::Enum(enum_layout) => {
+            write_enum(enum_layout, column, max_rows, row_idx, enum_number(&value))
+        }
+        ValueLayout::EnumName(enum_layout) => {
+            write_enum_name(enum_layout, column, row_idx, enum_number(&value))
+        }
+        ValueLayout::String(..) => write_str(column, row_idx, value.as_str().unwrap_or_default()),
+        ValueLayout::Timestamp(..) => write_value(column, max_rows, row_idx, 0i64),
+        ValueLayout::Duration => write_value(
+            column,
+            max_rows,
+            row_idx,
+            duckdb::ffi::duckdb_interval {
+                months: 0,
+                days: 0,
+                micros: 0,
+            },
+        ),
+        ValueLayout::Wrapper(value_field) => {
+            write_default(state, value_field, column, max_rows, row_idx)?;
+            set_null(column, row_idx);
+        }
+        ValueLayout::Json(message_descriptor) => {
+            let value = match message_descriptor.full_name() {
+                "google.protobuf.Value" => "null",
+                "google.protobuf.ListValue" => "[]",
+                _ => "{}",
+            };
+            write_str(column, row_idx, value);
+        }
+        ValueLayout::Raw => {
+            write_bytes(column, row_idx, &[]);
+            set_null(column, row_idx);
+        }
+        ValueLayout::Any(any_layout) => {
+            write_any(state, any_layout, "", &[], column, max_rows, row_idx)?
+        }
+        ValueLayout::Message(message_layout) => {
+            let source = unsafe { StructVector::new(column) };
+            write_defaults(state, message_layout, &source, max_rows, row_idx)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fills every field of a message with its default value, as decoding an
+/// empty message does.
+fn write_defaults(
+    state: &mut ParserState,
+    layout: &MessageLayout,
+    output: &impl VectorAccessor,
+    max_rows: usize,
+    row_idx: usize,
+) -> Result<(), anyhow::Error> {
+    parse_message(state, layout, &[], output, max_rows, row_idx)
+}
+
+/// The number of an enum field's default value. Fields read as enums through
+/// [`DecodeOptions::enum_mappings`] have an `int32` default instead, as do
+/// plain `int32` fields.
+fn enum_number(value: &Value) -> i32 {
+    value
+        .as_enum_number()
+        .or_else(|| value.as_i32())
+        .unwrap_or_default()
+}
+
+fn write_value<T>(column: duckdb::ffi::duckdb_vector, max_rows: usize, row_idx: usize, value: T) {
+    let mut vector = unsafe { MyFlatVector::<T>::with_capacity(column, max_rows) };
+    vector.as_mut_slice()[row_idx] = value;
+}
+
+fn write_str(column: duckdb::ffi::duckdb_vector, row_idx: usize, value: &str) {
+    write_bytes(column, row_idx, value.as_bytes());
+}
+
+fn write_bytes(column: duckdb::ffi::duckdb_vector, row_idx: usize, value: &[u8]) {
+    unsafe {
+        duckdb::ffi::duckdb_vector_assign_string_element_len(
+            column,
+            row_idx as _,
+            value.as_ptr().cast(),
+            value.len() as _,
+        )
+    };
+}
+
+pub fn set_null(column: duckdb::ffi::duckdb_vector, row_idx: usize) {
+    unsafe {
+        duckdb::ffi::duckdb_vector_ensure_validity_writable(column);
+        let validity = duckdb::ffi::duckdb_vector_get_validity(column);
+        duckdb::ffi::duckdb_validity_set_row_invalid(validity, row_idx as _);
+    }
+}
+
+/// Undoes [`set_null`], for rows which may have been written to before, like
+/// a row left behind by a record which failed to decode.
+fn set_valid(column: duckdb::ffi::duckdb_vector, row_idx: usize) {
+    unsafe {
+        let validity = duckdb::ffi::duckdb_vector_get_validity(column);
+        if !validity.is_null() {
+            duckdb::ffi::duckdb_validity_set_row_valid(validity, row_idx as _);
+        }
+    }
+}
+
+fn write_enum(
+    enum_layout: &EnumLayout,
+    column: duckdb::ffi::duckdb_vector,
+    max_rows: usize,
+    row_idx: usize,
+    number: i32,
+) {
+    let Some(idx) = enum_layout
+        .indices
+        .get(&number)
+        .copied()
+        .or(enum_layout.default_idx)
+    else {
+        set_null(column, row_idx);
+        return;
+    };
+
+    if enum_layout.default_idx.is_none() {
+        set_valid(column, row_idx);
+    }
+
+    match enum_layout.width {
+        EnumWidth::U8 => write_value(column, max_rows, row_idx, idx as u8),
+        EnumWidth::U16 => write_value(column, max_rows, row_idx, idx as u16),
+        EnumWidth::U32 => write_value(column, max_rows, row_idx, idx),
+    }
+}
+
+fn write_enum_name(
+    enum_layout: &EnumNameLayout,
+    column: duckdb::ffi::duckdb_vector,
+    row_idx: usize,
+    number: i32,
+) {
+    match enum_layout.names.get(&number) {
+        Some(name) => write_str(column, row_idx, name),
+        None => write_str(column, row_idx, &number.to_string()),
+    }
+}
+
+fn check_wire_type(expected: WireType, actual: WireType) -> Result<(), anyhow::Error> {
+    if expected != actual {
+        bail!("expected wire type {:?}, got {:?}", expected, actual);
+    }
+
+    Ok(())
+}
+
+fn read_varint_value(wire_type: WireType, buf: &mut &[u8]) -> Result<u64, anyhow::Error> {
+    check_wire_type(WireType::Varint, wire_type)?;
+
+    Ok(decode_varint(buf)?)
+}
+
+/// Reads a zigzag encoded varint, as used by `sint32` and `sint64`, where
+/// small negative numbers take few bytes. Values of `sint32` fields are
+/// encoded from 32 bits so the result fits when truncated.
+fn read_sint_value(wire_type: WireType, buf: &mut &[u8]) -> Result<i64, anyhow::Error> {
+    let value = read_varint_value(wire_type, buf)?;
+
+    Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+}
+
+fn read_fixed_bytes<const N: usize>(buf: &mut &[u8]) -> Result<[u8; N], anyhow::Error> {
+    if buf.remaining() < N {
+        bail!("unexpected end of message reading {} byte value", N);
+    }
+
+    let mut bytes = [0; N];
+    buf.copy_to_slice(&mut bytes);
+
+    Ok(bytes)
+}
+
+/// Reads the encoded value of a message field, which is length delimited, or
+/// a group for proto2 group fields.
+fn read_message<'a>(
+    field: &FieldLayout,
+    wire_type: WireType,
+    buf: &mut &'a [u8],
+) -> Result<&'a [u8], anyhow::Error> {
+    match wire_type {
+        WireType::StartGroup if field.descriptor.is_group() => {
+            read_group(buf, field.descriptor.number())
+        }
+        _ => read_length_delimited(wire_type, buf),
+    }
+}
+
+fn read_length_delimited<'a>(
+    wire_type: WireType,
+    buf: &mut &'a [u8],
+) -> Result<&'a [u8], anyhow::Error> {
+    check_wire_type(WireType::LengthDelimited, wire_type)?;
+
+    let len = decode_varint(buf)? as usize;
+    if len > buf.remaining() {
+        bail!("length {} extends past the end of the message", len);
+    }
+
+    let (value, rest) = buf.split_at(len);
+    *buf = rest;
+
+    Ok(value)
+}
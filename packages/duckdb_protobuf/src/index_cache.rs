@@ -0,0 +1,200 @@
+//! Sidecar files of record offsets kept in the `index_cache` directory. The
+//! first scan of a file writes down where each record starts, later scans of
+//! the unchanged file use it to split the file into chunks read by different
+//! threads.
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Start of every sidecar file, bumped when the layout changes.
+const MAGIC: &[u8; 8] = b"PBIDX001";
+
+/// Bytes of records read by a single task when a file is split into chunks.
+const CHUNK_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RecordOffset {
+    /// Position of the record's length prefix.
+    pub position: u64,
+    /// Size of the record's message.
+    pub size: u64,
+}
+
+/// Part of a file read as its own task.
+#[derive(Copy, Clone, Debug)]
+pub struct Chunk {
+    /// Position of the chunk's first record.
+    pub position: u64,
+    /// Index of the chunk's first record in the file.
+    pub index: u64,
+    /// Position following the chunk's last record, `None` for the last chunk
+    /// which is read up to the end of the file.
+    pub end: Option<u64>,
+}
+
+/// What a sidecar file is checked against to tell whether the file it
+/// indexes changed since it was written.
+#[derive(Copy, Clone, PartialEq)]
+struct FileStamp {
+    len: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Result<FileStamp, io::Error> {
+        let metadata = path.metadata()?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_err(io::Error::other)?;
+
+        Ok(FileStamp {
+            len: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+        })
+    }
+}
+
+/// Path of the sidecar file of `path`. `framing` identifies the delimiter
+/// and trailer records were read with, as they change where records start.
+fn sidecar_path(dir: &Path, path: &Path, framing: &str) -> PathBuf {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let key = format!("{}\0{}", path.display(), framing);
+
+    let file_name = path
+        .file_name()
+        .map(|it| it.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    dir.join(format!(
+        "{}.{:08x}.idx",
+        file_name,
+        crc32fast::hash(key.as_bytes())
+    ))
+}
+
+/// Offsets of the records of `path`, `None` when it wasn't indexed yet or
+/// changed since.
+pub fn load(dir: &Path, path: &Path, framing: &str) -> Option<Vec<RecordOffset>> {
+    let stamp = FileStamp::of(path).ok()?;
+    let mut reader = BufReader::new(File::open(sidecar_path(dir, path, framing)).ok()?);
+
+    let mut read = || -> Result<Option<Vec<RecordOffset>>, io::Error> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Ok(None);
+        }
+
+        let written = FileStamp {
+            len: reader.read_u64::<LittleEndian>()?,
+            mtime_secs: reader.read_u64::<LittleEndian>()?,
+            mtime_nanos: reader.read_u32::<LittleEndian>()?,
+        };
+        if written != stamp {
+            return Ok(None);
+        }
+
+        let count = reader.read_u64::<LittleEndian>()?;
+        // a record takes up at least a byte of its file
+        if count > stamp.len {
+            return Ok(None);
+        }
+
+        let mut offsets = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            offsets.push(RecordOffset {
+                position: reader.read_u64::<LittleEndian>()?,
+                size: reader.read_u64::<LittleEndian>()?,
+            });
+        }
+
+        Ok(Some(offsets))
+    };
+
+    read().ok().flatten()
+}
+
+/// Splits a file into chunks of about [`CHUNK_BYTES`] at record boundaries.
+pub fn chunks(offsets: &[RecordOffset]) -> Vec<Chunk> {
+    let mut chunks = vec![];
+    let mut current = Chunk {
+        position: 0,
+        index: 0,
+        end: None,
+    };
+
+    for (index, offset) in offsets.iter().enumerate() {
+        if offset.position - current.position >= CHUNK_BYTES {
+            chunks.push(Chunk {
+                end: Some(offset.position),
+                ..current
+            });
+
+            current = Chunk {
+                position: offset.position,
+                index: index as u64,
+                end: None,
+            };
+        }
+    }
+    chunks.push(current);
+
+    chunks
+}
+
+/// Collects the offsets of the records of a file as it's read, written to
+/// its sidecar file once the whole file is read.
+pub struct IndexBuilder {
+    sidecar: PathBuf,
+    // taken before reading, so a file changing while it's read doesn't get
+    // an index matching its new contents
+    stamp: FileStamp,
+    offsets: Vec<RecordOffset>,
+}
+
+impl IndexBuilder {
+    pub fn start(dir: &Path, path: &Path, framing: &str) -> Result<IndexBuilder, io::Error> {
+        Ok(IndexBuilder {
+            sidecar: sidecar_path(dir, path, framing),
+            stamp: FileStamp::of(path)?,
+            offsets: vec![],
+        })
+    }
+
+    pub fn push(&mut self, offset: RecordOffset) {
+        self.offsets.push(offset);
+    }
+
+    /// Writes the sidecar file. It's written next to its final path and
+    /// moved in place, so concurrent scans never see a partial index.
+    pub fn finish(self) -> Result<(), io::Error> {
+        let mut temp = self.sidecar.clone().into_os_string();
+        temp.push(format!(".{}.tmp", std::process::id()));
+        let temp = PathBuf::from(temp);
+
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        writer.write_all(MAGIC)?;
+        writer.write_u64::<LittleEndian>(self.stamp.len)?;
+        writer.write_u64::<LittleEndian>(self.stamp.mtime_secs)?;
+        writer.write_u32::<LittleEndian>(self.stamp.mtime_nanos)?;
+        writer.write_u64::<LittleEndian>(self.offsets.len() as u64)?;
+        for offset in &self.offsets {
+            writer.write_u64::<LittleEndian>(offset.position)?;
+            writer.write_u64::<LittleEndian>(offset.size)?;
+        }
+        writer.into_inner().map_err(|err| err.into_error())?;
+
+        std::fs::rename(&temp, &self.sidecar)
+    }
+
+    pub fn sidecar(&self) -> &Path {
+        &self.sidecar
+    }
+}
@@ -1,20 +1,65 @@
-use anyhow::format_err;
-use byteorder::{BigEndian, ReadBytesExt};
+use crate::remote;
+use anyhow::{format_err, Context};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use flate2::read::MultiGzDecoder;
+use memmap2::Mmap;
 use ouroboros::self_referencing;
+use prost::Message;
+use prost_reflect::{DynamicMessage, MessageDescriptor};
 use protobuf::CodedInputStream;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fs::File;
 use std::io;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
 use strum::{AsRefStr, EnumIter, EnumString, IntoEnumIterator};
 
 #[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
 pub enum LengthKind {
     BigEndianFixed,
+    LittleEndianFixed,
+    /// Like `BigEndianFixed`, with a u16 prefix.
+    BigEndianFixed16,
+    /// Like `LittleEndianFixed`, with a u16 prefix.
+    LittleEndianFixed16,
+    /// Like `BigEndianFixed`, with a u64 prefix.
+    BigEndianFixed64,
+    /// Like `LittleEndianFixed`, with a u64 prefix.
+    LittleEndianFixed64,
+    AutoEndianFixed,
+    /// Guessed per file among `BigEndianFixed`, `Varint` and
+    /// `SingleMessagePerFile`, see [`sniff_length_kind`].
+    Auto,
+    /// A varint prefix, the framing of protobuf's own delimited streams like
+    /// Java's `writeDelimitedTo`.
     Varint,
+    /// Like `Varint`, but allowing lengths past `u32::MAX`.
+    Varint64,
     SingleMessagePerFile,
 }
 
+impl LengthKind {
+    /// The length prefixes of every file read with this `delimiter`, `None`
+    /// for delimiters guessed per file and for `SingleMessagePerFile`.
+    pub fn delimited(self) -> Option<DelimitedLengthKind> {
+        match self {
+            LengthKind::BigEndianFixed => Some(DelimitedLengthKind::BigEndianFixed),
+            LengthKind::LittleEndianFixed => Some(DelimitedLengthKind::LittleEndianFixed),
+            LengthKind::BigEndianFixed16 => Some(DelimitedLengthKind::BigEndianFixed16),
+            LengthKind::LittleEndianFixed16 => Some(DelimitedLengthKind::LittleEndianFixed16),
+            LengthKind::BigEndianFixed64 => Some(DelimitedLengthKind::BigEndianFixed64),
+            LengthKind::LittleEndianFixed64 => Some(DelimitedLengthKind::LittleEndianFixed64),
+            LengthKind::Varint => Some(DelimitedLengthKind::Varint),
+            LengthKind::Varint64 => Some(DelimitedLengthKind::Varint64),
+            LengthKind::AutoEndianFixed | LengthKind::Auto | LengthKind::SingleMessagePerFile => {
+                None
+            }
+        }
+    }
+}
+
 pub fn parse<T: std::str::FromStr<Err = impl Error> + IntoEnumIterator + AsRef<str>>(
     value: &str,
 ) -> Result<T, anyhow::Error> {
@@ -31,17 +76,472 @@ pub fn parse<T: std::str::FromStr<Err = impl Error> + IntoEnumIterator + AsRef<s
     })?)
 }
 
+/// Encoding of the messages in the input files.
+#[derive(Copy, Clone, PartialEq, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Format {
+    Binary,
+    /// Protobuf text format, one message per file or separated by lines
+    /// holding only `---`.
+    Text,
+    /// Newline delimited JSON, one message per line in protobuf's JSON
+    /// mapping.
+    Jsonl,
+}
+
+/// Whether `files` names standard input, `-` or `stdin`, rather than a glob.
+pub fn is_stdin(path: impl AsRef<Path>) -> bool {
+    matches!(path.as_ref().to_str(), Some("-" | "stdin"))
+}
+
+/// Expands `{a,b}` alternations of a glob pattern into a pattern per
+/// alternative, like a shell would, as `glob` doesn't support them. Braces
+/// nest, and braces without a comma are left as is.
+pub fn expand_braces(pattern: &str) -> Vec<String> {
+    for (open, _) in pattern.match_indices('{') {
+        let mut depth = 0;
+        let mut commas = vec![];
+        let mut close = None;
+
+        for (idx, c) in pattern[open..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + idx);
+                        break;
+                    }
+                }
+                ',' if depth == 1 => commas.push(open + idx),
+                _ => {}
+            }
+        }
+
+        let Some(close) = close else {
+            break;
+        };
+
+        if commas.is_empty() {
+            continue;
+        }
+
+        let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+        let starts = std::iter::once(open).chain(commas.iter().copied());
+        let ends = commas.iter().copied().chain(std::iter::once(close));
+
+        // the alternatives and suffix may hold more braces
+        return starts
+            .zip(ends)
+            .flat_map(|(start, end)| {
+                expand_braces(&format!("{prefix}{}{suffix}", &pattern[start + 1..end]))
+            })
+            .collect();
+    }
+
+    vec![pattern.to_string()]
+}
+
+/// Order files matching `files` are read and numbered in.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum FileOrder {
+    Name,
+    /// Oldest first, files without a modification time come first.
+    Mtime,
+    /// As listed, the order of the alternatives of `files` then of the glob.
+    None,
+}
+
+/// What to do with bytes following the last complete record of a file.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum TrailingBytes {
+    Ignore,
+    Error,
+    /// List them among the scan's `protobuf_errors()` and otherwise ignore
+    /// them.
+    Warn,
+}
+
+/// Data following each record's message.
+#[derive(Copy, Clone, PartialEq, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum RecordTrailer {
+    None,
+    /// Big endian CRC-32 (IEEE) of the message.
+    Crc32,
+}
+
+/// How files are compressed.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    /// Picked per file by extension, `.gz` for gzip, `.zst` for zstd and
+    /// uncompressed otherwise.
+    Auto,
+}
+
+impl Compression {
+    fn for_path(self, path: &Path) -> Compression {
+        let Compression::Auto = self else {
+            return self;
+        };
+
+        match path.extension().and_then(|it| it.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Opens a file, an object when `path` is a URL, or standard input,
+    /// decompressing its contents. Local files are memory mapped when `mmap`
+    /// is set.
+    pub fn open(self, path: &Path, mmap: bool) -> Result<Box<dyn Read>, io::Error> {
+        let file: Box<dyn Read> = if is_stdin(path) {
+            Box::new(io::stdin())
+        } else if remote::is_url(path) {
+            remote::open(path)?
+        } else if mmap {
+            let file = File::open(path)?;
+            // the mapping is only valid as long as nothing truncates the
+            // file while it's read, which `mmap` documents
+            Box::new(io::Cursor::new(unsafe { Mmap::map(&file)? }))
+        } else {
+            Box::new(File::open(path)?)
+        };
+
+        Ok(match self.for_path(path) {
+            Compression::None | Compression::Auto => file,
+            Compression::Gzip => Box::new(MultiGzDecoder::new(file)),
+            // concatenated frames are read one after another, like gzip
+            // members
+            Compression::Zstd => Box::new(zstd::Decoder::new(file)?),
+        })
+    }
+}
+
 #[derive(Copy, Clone)]
 pub enum DelimitedLengthKind {
     BigEndianFixed,
+    LittleEndianFixed,
+    BigEndianFixed16,
+    LittleEndianFixed16,
+    BigEndianFixed64,
+    LittleEndianFixed64,
     Varint,
+    Varint64,
+}
+
+/// Number of records followed from the start of a file when guessing the byte
+/// order of its length prefixes.
+const SNIFF_RECORDS: usize = 16;
+
+/// Number of bytes read from the start of a file when guessing the byte order
+/// of its length prefixes.
+const SNIFF_BYTES: u64 = 64 * 1024;
+
+/// Guesses whether a stream of u32 length prefixed records uses big or little
+/// endian prefixes. The first few prefixes are followed under each
+/// interpretation and the one which stays within the bounds of the stream
+/// wins. When both are plausible, big endian is preferred. `stream` is only
+/// used to look at the first bytes, records should be read from a fresh one.
+///
+/// This only looks at the start of the stream so it can be fooled by files
+/// where small records make the wrong interpretation land in bounds too.
+pub fn sniff_fixed_length_kind(
+    stream: impl Read,
+    file_len: u64,
+) -> Result<DelimitedLengthKind, io::Error> {
+    let mut head = Vec::new();
+    stream.take(SNIFF_BYTES).read_to_end(&mut head)?;
+
+    let length_kind = if prefixes_fit::<BigEndian>(&head, file_len) {
+        DelimitedLengthKind::BigEndianFixed
+    } else if prefixes_fit::<LittleEndian>(&head, file_len) {
+        DelimitedLengthKind::LittleEndianFixed
+    } else {
+        DelimitedLengthKind::BigEndianFixed
+    };
+
+    Ok(length_kind)
+}
+
+fn prefixes_fit<B: ByteOrder>(head: &[u8], file_len: u64) -> bool {
+    let mut offset = 0u64;
+
+    for _ in 0..SNIFF_RECORDS {
+        if offset == file_len {
+            return true;
+        }
+
+        let start = offset as usize;
+        let Some(prefix) = head.get(start..start + 4) else {
+            // past what was read, as long as a prefix fits it's plausible
+            return offset + 4 <= file_len;
+        };
+
+        offset += 4 + B::read_u32(prefix) as u64;
+        if offset > file_len {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Guesses the framing of a stream for [`LengthKind::Auto`]. u32 big endian
+/// prefixes are tried first, then varint prefixes, like
+/// [`sniff_fixed_length_kind`]. When neither stays within the bounds of the
+/// stream it's taken to hold a single message, returned as `None`.
+pub fn sniff_length_kind(
+    stream: impl Read,
+    file_len: u64,
+) -> Result<Option<DelimitedLengthKind>, io::Error> {
+    let mut head = Vec::new();
+    stream.take(SNIFF_BYTES).read_to_end(&mut head)?;
+
+    let length_kind = if prefixes_fit::<BigEndian>(&head, file_len) {
+        Some(DelimitedLengthKind::BigEndianFixed)
+    } else if varint_prefixes_fit(&head, file_len) {
+        Some(DelimitedLengthKind::Varint)
+    } else {
+        None
+    };
+
+    Ok(length_kind)
+}
+
+fn varint_prefixes_fit(head: &[u8], file_len: u64) -> bool {
+    let mut offset = 0u64;
+
+    for _ in 0..SNIFF_RECORDS {
+        if offset == file_len {
+            return true;
+        }
+
+        let mut len = 0u64;
+        let mut prefix_len = 0;
+        loop {
+            let Some(byte) = head.get(offset as usize + prefix_len) else {
+                // past what was read, as long as the prefix fits it's
+                // plausible
+                return offset + prefix_len as u64 <= file_len;
+            };
+
+            // a u32 length takes at most 5 bytes
+            if prefix_len == 5 {
+                return false;
+            }
+
+            len |= ((byte & 0x7f) as u64) << (7 * prefix_len);
+            prefix_len += 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        offset += prefix_len as u64 + len;
+        if offset > file_len {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Caps the number of files open at once across all threads. Threads opening
+/// a file while the cap is reached wait for another file to be closed.
+pub struct OpenFilesLimit {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl OpenFilesLimit {
+    pub fn new(max_open_files: usize) -> Arc<OpenFilesLimit> {
+        Arc::new(OpenFilesLimit {
+            available: Mutex::new(max_open_files),
+            released: Condvar::new(),
+        })
+    }
+
+    fn acquire(self: &Arc<Self>) -> OpenFilePermit {
+        let mut available = self.available.lock().unwrap();
+        while *available == 0 {
+            available = self.released.wait(available).unwrap();
+        }
+        *available -= 1;
+
+        OpenFilePermit(self.clone())
+    }
+}
+
+/// Held while a file is open, giving its slot back once dropped.
+struct OpenFilePermit(Arc<OpenFilesLimit>);
+
+impl Drop for OpenFilePermit {
+    fn drop(&mut self) {
+        *self.0.available.lock().unwrap() += 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// Files which can be open at once when `max_open_files` isn't given, half
+/// the soft limit on file descriptors, leaving the rest to duckdb and the
+/// rest of the process.
+pub fn default_max_open_files() -> usize {
+    #[cfg(unix)]
+    {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } == 0
+            && limit.rlim_cur != libc::RLIM_INFINITY
+        {
+            return (limit.rlim_cur / 2).max(1) as usize;
+        }
+    }
+
+    usize::MAX
+}
+
+/// Reads files one after another as a single stream, opening each once the
+/// previous one is exhausted.
+pub struct ConcatReader {
+    paths: VecDeque<PathBuf>,
+    compression: Compression,
+    /// Whether local files are memory mapped rather than read.
+    mmap: bool,
+    open_files: Arc<OpenFilesLimit>,
+    // the file is declared before its permit so it's closed first
+    current: Option<(Box<dyn Read>, OpenFilePermit)>,
+}
+
+impl ConcatReader {
+    pub fn new(
+        paths: impl IntoIterator<Item = PathBuf>,
+        compression: Compression,
+        mmap: bool,
+        open_files: &Arc<OpenFilesLimit>,
+    ) -> ConcatReader {
+        ConcatReader {
+            paths: paths.into_iter().collect(),
+            compression,
+            mmap,
+            open_files: open_files.clone(),
+            current: None,
+        }
+    }
+
+    /// Reads part of an uncompressed local file, from byte `start` up to
+    /// `end` or the end of the file.
+    pub fn range(
+        path: PathBuf,
+        start: u64,
+        end: Option<u64>,
+        mmap: bool,
+        open_files: &Arc<OpenFilesLimit>,
+    ) -> Result<ConcatReader, io::Error> {
+        let permit = open_files.acquire();
+
+        let file: Box<dyn Read> = if mmap {
+            let mut file = io::Cursor::new(unsafe { Mmap::map(&File::open(&path)?)? });
+            file.set_position(start);
+            Box::new(file)
+        } else {
+            let mut file = File::open(&path)?;
+            file.seek(SeekFrom::Start(start))?;
+            Box::new(file)
+        };
+
+        let file: Box<dyn Read> = match end {
+            Some(end) => Box::new(file.take(end - start)),
+            None => file,
+        };
+
+        Ok(ConcatReader {
+            paths: VecDeque::new(),
+            compression: Compression::None,
+            mmap,
+            open_files: open_files.clone(),
+            current: Some((file, permit)),
+        })
+    }
+
+    /// Combined length of the files once decompressed. Compressed files are
+    /// decompressed to find it.
+    pub fn total_len(
+        paths: &[PathBuf],
+        compression: Compression,
+        open_files: &Arc<OpenFilesLimit>,
+    ) -> Result<u64, io::Error> {
+        let mut len = 0;
+        for path in paths {
+            len += match compression.for_path(path) {
+                Compression::None | Compression::Auto if remote::is_url(path) => remote::len(path)?,
+                Compression::None | Compression::Auto => path.metadata()?.len(),
+                _ => {
+                    let _permit = open_files.acquire();
+                    io::copy(&mut compression.open(path, false)?, &mut io::sink())?
+                }
+            };
+        }
+
+        Ok(len)
+    }
 }
 
+impl Read for ConcatReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.current.is_none() {
+                let Some(path) = self.paths.pop_front() else {
+                    return Ok(0);
+                };
+
+                let permit = self.open_files.acquire();
+                self.current = Some((self.compression.open(&path, self.mmap)?, permit));
+            }
+
+            let read = self.current.as_mut().unwrap().0.read(buf)?;
+            if read == 0 && !buf.is_empty() {
+                self.current = None;
+                continue;
+            }
+
+            return Ok(read);
+        }
+    }
+}
+
+/// Largest buffer allocated up front for a record, larger records grow their
+/// buffer as they're read.
+const PREALLOCATE_LIMIT: u64 = 64 * 1024 * 1024;
+
 #[self_referencing]
 pub struct LengthDelimitedRecordsReader {
     length_kind: DelimitedLengthKind,
+    trailing_bytes: TrailingBytes,
+    record_trailer: RecordTrailer,
     path: PathBuf,
-    inner: File,
+    /// Index of the next record in the stream.
+    next_index: u64,
+    /// Position of the start of the stream in its file, set when reading a
+    /// chunk of a file.
+    offset: u64,
+    /// Buffer of a record which has been consumed, reused for the next one,
+    /// see [`RecordsReader::recycle`].
+    spare: Vec<u8>,
+    /// Position and message of the incomplete record ending the stream with
+    /// [`TrailingBytes::Warn`], see [`RecordsReader::take_warning`].
+    warning: Option<(u64, String)>,
+    inner: ConcatReader,
 
     #[borrows(mut inner)]
     #[not_covariant]
@@ -51,45 +551,162 @@ pub struct LengthDelimitedRecordsReader {
 pub struct Record {
     pub bytes: Vec<u8>,
     pub position: u64,
-    pub size: u32,
+    /// Index of the record in its stream, from 0.
+    pub index: u64,
+    pub size: u64,
+    /// Number of bytes taken up by the length prefix preceding the message.
+    pub prefix_size: u32,
+    /// Set when the record's trailer doesn't match its message, the record
+    /// is read but shouldn't be decoded.
+    pub trailer_error: Option<String>,
 }
 
 impl LengthDelimitedRecordsReader {
-    pub fn create(inner: File, length_kind: DelimitedLengthKind, path: PathBuf) -> Self {
+    pub fn create(
+        inner: ConcatReader,
+        length_kind: DelimitedLengthKind,
+        trailing_bytes: TrailingBytes,
+        record_trailer: RecordTrailer,
+        path: PathBuf,
+    ) -> Self {
         LengthDelimitedRecordsReaderBuilder {
             length_kind,
+            trailing_bytes,
+            record_trailer,
             path,
+            next_index: 0,
+            offset: 0,
+            spare: Vec::new(),
+            warning: None,
             inner,
             reader_builder: |it| CodedInputStream::new(it),
         }
         .build()
     }
 
-    fn get_next(&mut self) -> Result<Record, io::Error> {
-        let length_kind = *self.borrow_length_kind();
-        Ok(self.with_reader_mut(move |reader| {
-            let position = reader.pos();
-            let len = match length_kind {
-                DelimitedLengthKind::BigEndianFixed => reader.read_u32::<BigEndian>()?,
-                DelimitedLengthKind::Varint => reader.read_raw_varint32()?,
+    /// Makes positions and indices of records count from `position` and
+    /// `index`, for streams starting part way through a file.
+    pub fn starting_at(mut self, position: u64, index: u64) -> Self {
+        self.with_mut(|fields| {
+            *fields.offset = position;
+            *fields.next_index = index;
+        });
+
+        self
+    }
+
+    /// Reads the next record, or `None` when the end of the file is reached
+    /// at a record boundary.
+    fn get_next(&mut self) -> Result<Option<Record>, io::Error> {
+        Ok(self.with_mut(|fields| {
+            let reader = fields.reader;
+            let position = reader.pos() + *fields.offset;
+            if reader.eof()? {
+                return Ok(None);
+            }
+
+            let len = match fields.length_kind {
+                DelimitedLengthKind::BigEndianFixed => reader.read_u32::<BigEndian>()? as u64,
+                DelimitedLengthKind::LittleEndianFixed => reader.read_u32::<LittleEndian>()? as u64,
+                DelimitedLengthKind::BigEndianFixed16 => reader.read_u16::<BigEndian>()? as u64,
+                DelimitedLengthKind::LittleEndianFixed16 => {
+                    reader.read_u16::<LittleEndian>()? as u64
+                }
+                DelimitedLengthKind::BigEndianFixed64 => reader.read_u64::<BigEndian>()?,
+                DelimitedLengthKind::LittleEndianFixed64 => reader.read_u64::<LittleEndian>()?,
+                DelimitedLengthKind::Varint | DelimitedLengthKind::Varint64 => {
+                    let len = match fields.length_kind {
+                        DelimitedLengthKind::Varint => reader.read_raw_varint32().map(u64::from),
+                        _ => reader.read_raw_varint64(),
+                    };
+
+                    match len {
+                        Ok(len) => len,
+                        // a varint cut short by the end of the file
+                        Err(_) if reader.eof()? => return Err(io::ErrorKind::UnexpectedEof.into()),
+                        Err(err) => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!(
+                                    "{}: malformed varint length prefix at position {}: {}",
+                                    fields.path.display(),
+                                    position,
+                                    err
+                                ),
+                            ))
+                        }
+                    }
+                }
+            };
+            let prefix_size = (reader.pos() + *fields.offset - position) as u32;
+
+            // the buffer grows as the record is read rather than trusting the
+            // prefix up front, so a corrupt length fails at the end of the
+            // file instead of allocating it
+            let mut buf = std::mem::take(fields.spare);
+            buf.clear();
+            buf.reserve(len.min(PREALLOCATE_LIMIT) as usize);
+            io::Read::by_ref(reader).take(len).read_to_end(&mut buf)?;
+            if (buf.len() as u64) < len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            let trailer_error = match fields.record_trailer {
+                RecordTrailer::None => None,
+                RecordTrailer::Crc32 => {
+                    let expected = reader.read_u32::<BigEndian>()?;
+                    let actual = crc32fast::hash(&buf);
+
+                    (expected != actual).then(|| {
+                        format!(
+                            "crc32 mismatch, expected {:08x}, got {:08x}",
+                            expected, actual
+                        )
+                    })
+                }
             };
 
-            let mut buf = vec![0; len as usize];
-            <CodedInputStream as io::Read>::read_exact(reader, &mut buf)?;
+            let index = *fields.next_index;
+            *fields.next_index += 1;
 
-            Ok::<_, io::Error>(Record {
+            Ok::<_, io::Error>(Some(Record {
                 bytes: buf,
                 position,
+                index,
                 size: len,
-            })
+                prefix_size,
+                trailer_error,
+            }))
         })?)
     }
 
     pub fn try_get_next(&mut self) -> Result<Option<Record>, io::Error> {
+        let position = self.with_reader(|reader| reader.pos()) + self.borrow_offset();
+
         match self.get_next() {
-            Ok(it) => Ok(Some(it)),
-            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
-            Err(err) => Err(err.into()),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                match self.borrow_trailing_bytes() {
+                    TrailingBytes::Ignore => Ok(None),
+                    TrailingBytes::Error => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{}: incomplete record at position {} after the last complete record",
+                            self.path().display(),
+                            position
+                        ),
+                    )),
+                    TrailingBytes::Warn => {
+                        self.with_warning_mut(|it| {
+                            *it = Some((
+                                position,
+                                "incomplete record after the last complete record".to_string(),
+                            ))
+                        });
+                        Ok(None)
+                    }
+                }
+            }
+            result => result,
         }
     }
 
@@ -97,3 +714,232 @@ impl LengthDelimitedRecordsReader {
         self.borrow_path().as_path()
     }
 }
+
+/// Separates messages in a text format file.
+const TEXT_SEPARATOR: &str = "---";
+
+/// Reads text format messages, re-encoding each into the binary format so they
+/// go through the same decoding as binary records. A whole stream is parsed
+/// up front when opened.
+pub struct TextRecordsReader {
+    path: PathBuf,
+    records: VecDeque<Record>,
+}
+
+impl TextRecordsReader {
+    pub fn create(
+        mut inner: ConcatReader,
+        descriptor: &MessageDescriptor,
+        path: PathBuf,
+    ) -> Result<Self, anyhow::Error> {
+        let mut text = String::new();
+        inner
+            .read_to_string(&mut text)
+            .with_context(|| format_err!("{}", path.display()))?;
+
+        let records = split_text_messages(&text)
+            .into_iter()
+            .enumerate()
+            .map(|(index, (position, message_text))| {
+                let message = DynamicMessage::parse_text_format(descriptor.clone(), message_text)
+                    .with_context(|| {
+                    format_err!(
+                        "{}: invalid text format message at position {}",
+                        path.display(),
+                        position
+                    )
+                })?;
+
+                Ok(Record {
+                    bytes: message.encode_to_vec(),
+                    position: position as u64,
+                    index: index as u64,
+                    size: message_text.len() as u64,
+                    prefix_size: 0,
+                    trailer_error: None,
+                })
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+
+        Ok(TextRecordsReader { path, records })
+    }
+
+    pub fn try_get_next(&mut self) -> Result<Option<Record>, io::Error> {
+        Ok(self.records.pop_front())
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+}
+
+/// Splits text on separator lines, returning the byte offset of each message
+/// along with its text. Messages holding only whitespace are left out.
+fn split_text_messages(text: &str) -> Vec<(usize, &str)> {
+    let mut messages = vec![];
+    let mut start = 0;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        if line.trim() == TEXT_SEPARATOR {
+            messages.push((start, &text[start..offset]));
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    messages.push((start, &text[start..]));
+
+    messages
+        .into_iter()
+        .filter(|(_, message)| !message.trim().is_empty())
+        .collect()
+}
+
+/// Reads newline delimited JSON messages, re-encoding each into the binary
+/// format like [`TextRecordsReader`]. Unlike text format files, lines are
+/// parsed as they're read. Blank lines are skipped.
+pub struct JsonRecordsReader {
+    path: PathBuf,
+    inner: BufReader<ConcatReader>,
+    descriptor: MessageDescriptor,
+    line: String,
+    position: u64,
+    index: u64,
+}
+
+impl JsonRecordsReader {
+    pub fn create(inner: ConcatReader, descriptor: &MessageDescriptor, path: PathBuf) -> Self {
+        JsonRecordsReader {
+            path,
+            inner: BufReader::new(inner),
+            descriptor: descriptor.clone(),
+            line: String::new(),
+            position: 0,
+            index: 0,
+        }
+    }
+
+    pub fn try_get_next(&mut self) -> Result<Option<Record>, io::Error> {
+        loop {
+            self.line.clear();
+            let read = self.inner.read_line(&mut self.line)?;
+            if read == 0 {
+                return Ok(None);
+            }
+
+            let position = self.position;
+            self.position += read as u64;
+
+            let line = self.line.trim_end();
+            if line.trim_start().is_empty() {
+                continue;
+            }
+
+            let mut deserializer = serde_json::Deserializer::from_str(line);
+            let message = DynamicMessage::deserialize(self.descriptor.clone(), &mut deserializer)
+                .and_then(|message| deserializer.end().map(|_| message))
+                .map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{}: invalid JSON message at position {}: {}",
+                            self.path.display(),
+                            position,
+                            err
+                        ),
+                    )
+                })?;
+
+            let index = self.index;
+            self.index += 1;
+
+            return Ok(Some(Record {
+                bytes: message.encode_to_vec(),
+                position,
+                index,
+                size: line.len() as u64,
+                prefix_size: 0,
+                trailer_error: None,
+            }));
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+}
+
+/// A stream holding a single message, see [`LengthKind::SingleMessagePerFile`].
+pub struct SingleRecordReader {
+    path: PathBuf,
+    record: Option<Record>,
+}
+
+impl SingleRecordReader {
+    pub fn create(bytes: Vec<u8>, path: PathBuf) -> Self {
+        let size = bytes.len() as u64;
+
+        SingleRecordReader {
+            path,
+            record: Some(Record {
+                bytes,
+                position: 0,
+                index: 0,
+                size,
+                prefix_size: 0,
+                trailer_error: None,
+            }),
+        }
+    }
+}
+
+/// Source of records for a single stream.
+pub enum RecordsReader {
+    LengthDelimited(LengthDelimitedRecordsReader),
+    Text(TextRecordsReader),
+    Json(JsonRecordsReader),
+    Single(SingleRecordReader),
+}
+
+impl RecordsReader {
+    pub fn try_get_next(&mut self) -> Result<Option<Record>, io::Error> {
+        match self {
+            RecordsReader::LengthDelimited(it) => it.try_get_next(),
+            RecordsReader::Text(it) => it.try_get_next(),
+            RecordsReader::Json(it) => it.try_get_next(),
+            RecordsReader::Single(it) => Ok(it.record.take()),
+        }
+    }
+
+    /// Hands back the bytes of a record which is no longer needed, so the
+    /// next record is read into the same allocation instead of a new one.
+    pub fn recycle(&mut self, bytes: Vec<u8>) {
+        let RecordsReader::LengthDelimited(it) = self else {
+            return;
+        };
+
+        // a single huge record shouldn't keep its buffer for the whole stream
+        if bytes.capacity() as u64 <= PREALLOCATE_LIMIT {
+            it.with_spare_mut(|spare| *spare = bytes);
+        }
+    }
+
+    /// Takes the warning about the end of the stream, once it's exhausted,
+    /// as the position it's about and a message.
+    pub fn take_warning(&mut self) -> Option<(u64, String)> {
+        let RecordsReader::LengthDelimited(it) = self else {
+            return None;
+        };
+
+        it.with_warning_mut(|it| it.take())
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            RecordsReader::LengthDelimited(it) => it.path(),
+            RecordsReader::Text(it) => it.path(),
+            RecordsReader::Json(it) => it.path(),
+            RecordsReader::Single(it) => it.path.as_path(),
+        }
+    }
+}
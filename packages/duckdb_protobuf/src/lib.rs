@@ -1,11 +1,28 @@
-mod filtered_dynamic_message;
+mod benchmark;
+mod errors;
+mod gen;
+mod index_cache;
 mod io;
+mod messages;
 mod read;
+mod remote;
+mod root_path;
+mod scans;
+mod schema;
+mod stats;
 mod types;
+mod validate;
 mod vtab;
+mod wire;
 
 use std::error::Error;
 
+use crate::benchmark::ProtobufBenchmarkVTab;
+use crate::errors::ProtobufErrorsVTab;
+use crate::messages::ProtobufMessagesVTab;
+use crate::schema::ProtobufSchemaVTab;
+use crate::stats::ProtobufLastScanStatsVTab;
+use crate::validate::ProtobufValidateVTab;
 use crate::vtab::ProtobufVTab;
 use duckdb::ffi;
 use duckdb::Connection;
@@ -14,6 +31,12 @@ use duckdb_loadable_macros::duckdb_entrypoint_c_api;
 #[duckdb_entrypoint_c_api(ext_name = "protobuf", min_duckdb_version = "v0.0.1")]
 fn protobuf_init(conn: Connection) -> Result<(), Box<dyn Error>> {
     conn.register_table_function_local_init::<ProtobufVTab>("protobuf")?;
+    conn.register_table_function::<ProtobufErrorsVTab>("protobuf_errors")?;
+    conn.register_table_function::<ProtobufValidateVTab>("protobuf_validate")?;
+    conn.register_table_function::<ProtobufMessagesVTab>("protobuf_messages")?;
+    conn.register_table_function::<ProtobufSchemaVTab>("protobuf_schema")?;
+    conn.register_table_function::<ProtobufBenchmarkVTab>("protobuf_benchmark")?;
+    conn.register_table_function::<ProtobufLastScanStatsVTab>("protobuf_last_scan_stats")?;
 
     Ok(())
 }
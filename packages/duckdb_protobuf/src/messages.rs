@@ -0,0 +1,133 @@
+use crate::read::{MyFlatVector, VectorAccessor};
+use crate::vtab::{descriptor_bytes, format_error_with_causes, Handle};
+use duckdb::vtab::{BindInfo, DataChunk, FunctionInfo, InitInfo, LogicalType, LogicalTypeId, VTab};
+use prost_reflect::DescriptorPool;
+use std::error::Error;
+use std::ffi::CString;
+
+/// A message type of the descriptors.
+pub struct MessageInfo {
+    full_name: String,
+    field_count: i32,
+    /// Name of the `.proto` file declaring the message.
+    file: String,
+}
+
+pub struct MessagesScanState {
+    offset: usize,
+}
+
+/// `protobuf_messages(descriptors = ...)`, listing the message types of the
+/// descriptors to help pick the `message_type` of `protobuf(...)`.
+pub struct ProtobufMessagesVTab;
+
+impl VTab for ProtobufMessagesVTab {
+    type InitData = Handle<MessagesScanState>;
+    type BindData = Handle<Vec<MessageInfo>>;
+
+    unsafe fn bind(
+        bind: &BindInfo,
+        data: *mut Self::BindData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::bind(bind, data).map_err(format_error_with_causes)?)
+    }
+
+    unsafe fn init(
+        init_info: &InitInfo,
+        data: *mut Self::InitData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        let data = &mut *data;
+        data.init();
+
+        init_info.set_max_threads(1);
+        data.assign(MessagesScanState { offset: 0 });
+
+        Ok(())
+    }
+
+    unsafe fn func(
+        func: &FunctionInfo,
+        output: &mut DataChunk,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::func(func, output).map_err(format_error_with_causes)?)
+    }
+
+    fn named_parameters() -> Option<Vec<(String, LogicalType)>> {
+        Some(
+            ["descriptors", "proto_files", "import_paths"]
+                .into_iter()
+                .map(|name| (name.to_string(), LogicalType::new(LogicalTypeId::Varchar)))
+                .collect(),
+        )
+    }
+}
+
+impl ProtobufMessagesVTab {
+    fn bind(bind: &BindInfo, data: *mut <Self as VTab>::BindData) -> Result<(), anyhow::Error> {
+        let data = unsafe { &mut *data };
+        data.init();
+
+        let descriptor_pool = DescriptorPool::decode(descriptor_bytes(bind)?.as_slice())?;
+
+        // map entry messages are generated for map fields, they can't be
+        // read on their own
+        let mut messages = descriptor_pool
+            .all_messages()
+            .filter(|it| !it.is_map_entry())
+            .map(|it| MessageInfo {
+                full_name: it.full_name().to_string(),
+                field_count: it.fields().len() as i32,
+                file: it.parent_file().name().to_string(),
+            })
+            .collect::<Vec<_>>();
+        messages.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+
+        bind.add_result_column("full_name", LogicalType::new(LogicalTypeId::Varchar));
+        bind.add_result_column("field_count", LogicalType::new(LogicalTypeId::Integer));
+        bind.add_result_column("file", LogicalType::new(LogicalTypeId::Varchar));
+
+        data.assign(messages);
+
+        Ok(())
+    }
+
+    fn func(func: &FunctionInfo, output: &mut DataChunk) -> Result<(), anyhow::Error> {
+        let bind_data = unsafe { &*func.get_bind_data::<<Self as VTab>::BindData>() };
+        let init_data = unsafe { &mut *func.get_init_data::<<Self as VTab>::InitData>() };
+
+        let available_chunk_size = output.flat_vector(0).capacity();
+        let remaining = &bind_data[init_data.offset..];
+        let items = remaining.len().min(available_chunk_size);
+
+        for (row_idx, message) in remaining[..items].iter().enumerate() {
+            let full_name = CString::new(message.full_name.as_str())?;
+            let file = CString::new(message.file.as_str())?;
+
+            unsafe {
+                duckdb::ffi::duckdb_vector_assign_string_element(
+                    output.get_vector(0),
+                    row_idx as _,
+                    full_name.as_ptr(),
+                );
+            }
+
+            let mut field_counts = unsafe {
+                MyFlatVector::<i32>::with_capacity(output.get_vector(1), available_chunk_size)
+            };
+            field_counts.as_mut_slice()[row_idx] = message.field_count;
+
+            unsafe {
+                duckdb::ffi::duckdb_vector_assign_string_element(
+                    output.get_vector(2),
+                    row_idx as _,
+                    file.as_ptr(),
+                );
+            }
+        }
+
+        init_data.offset += items;
+        output.set_len(items);
+
+        Ok(())
+    }
+}
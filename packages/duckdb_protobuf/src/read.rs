@@ -1,75 +1,8 @@
-use std::collections::HashMap;
-use std::ffi::CString;
 use std::marker::PhantomData;
 use std::slice;
 
-use anyhow::{bail, format_err};
-use duckdb::vtab::{DataChunk, LogicalType, LogicalTypeId};
-use prost_reflect::{Cardinality, DynamicMessage, FieldDescriptor, Kind, ReflectMessage, Value};
-
-pub fn write_to_output(
-    mappings: &[u64],
-    columns_state: &mut HashMap<ColumnKey, u64>,
-    value: &DynamicMessage,
-    output: &DataChunk,
-    max_rows: usize,
-    row_idx: usize,
-) -> Result<(), anyhow::Error> {
-    let column_key = &ColumnKey::empty();
-    let fields = value.descriptor().fields().collect::<Vec<_>>();
-    for (output_field_idx, field_idx) in mappings.iter().enumerate() {
-        let field_idx = *field_idx as usize;
-        if field_idx >= fields.len() {
-            continue;
-        }
-
-        let field_descriptor = &fields[field_idx];
-        let column_vector = output.get_vector(output_field_idx);
-        let value = value.get_field(&field_descriptor);
-
-        let column_key = column_key.field(&field_descriptor);
-
-        write_column(
-            columns_state,
-            &column_key,
-            &value,
-            &field_descriptor,
-            column_vector,
-            max_rows,
-            row_idx,
-        )?;
-    }
-
-    Ok(())
-}
-
-pub fn write_message(
-    columns_state: &mut HashMap<ColumnKey, u64>,
-    column_key: &ColumnKey,
-    value: &DynamicMessage,
-    output: &impl VectorAccessor,
-    max_rows: usize,
-    row_idx: usize,
-) -> Result<(), anyhow::Error> {
-    for (field_idx, field_descriptor) in value.descriptor().fields().enumerate() {
-        let column_vector = output.get_vector(field_idx);
-        let value = value.get_field(&field_descriptor);
-
-        let column_key = column_key.field(&field_descriptor);
-
-        write_column(
-            columns_state,
-            &column_key,
-            &value,
-            &field_descriptor,
-            column_vector,
-            max_rows,
-            row_idx,
-        )?;
-    }
-
-    Ok(())
-}
+use duckdb::vtab::DataChunk;
+use prost_reflect::FieldDescriptor;
 
 pub struct MyFlatVector<T> {
     _phantom_data: PhantomData<T>,
@@ -95,251 +28,19 @@ impl<T> MyFlatVector<T> {
     }
 }
 
-pub fn write_column(
-    columns_state: &mut HashMap<ColumnKey, u64>,
-    column_key: &ColumnKey,
-    value: &Value,
-    field_descriptor: &FieldDescriptor,
-    column: duckdb::ffi::duckdb_vector,
-    max_rows: usize,
-    row_idx: usize,
-) -> Result<(), anyhow::Error> {
-    match field_descriptor.cardinality() {
-        Cardinality::Repeated => {
-            let column_key = column_key.extending(ColumnKeyElement::List);
-
-            let mut list_entries_vector = unsafe {
-                MyFlatVector::<duckdb::ffi::duckdb_list_entry>::with_capacity(column, max_rows)
-            };
-            let list_entry = &mut list_entries_vector.as_mut_slice()[row_idx];
-
-            let values = value
-                .as_list()
-                .ok_or_else(|| format_err!("expected list"))?;
-
-            let next_offset_ref = columns_state.get_mut(&column_key);
-            let next_offset = if let Some(it) = &next_offset_ref {
-                **it
-            } else {
-                0
-            };
-
-            let len_u64 = u64::try_from(values.len())?;
-
-            list_entry.offset = next_offset;
-            list_entry.length = len_u64;
-
-            let new_next_offset = next_offset + len_u64;
-
-            if let Some(it) = next_offset_ref {
-                *it = new_next_offset;
-            } else {
-                columns_state.insert(column_key.clone(), new_next_offset);
-            }
-
-            let new_length = new_next_offset;
-
-            unsafe { duckdb::ffi::duckdb_list_vector_reserve(column, new_length) };
-            unsafe { duckdb::ffi::duckdb_list_vector_set_size(column, new_length) };
-
-            let child_vector = unsafe { duckdb::ffi::duckdb_list_vector_get_child(column) };
-
-            for (idx, value) in values.iter().enumerate() {
-                let row_idx = next_offset as usize + idx;
-
-                write_single_column(
-                    columns_state,
-                    &column_key,
-                    value,
-                    field_descriptor,
-                    child_vector,
-                    new_length as usize,
-                    row_idx,
-                )?;
-            }
-        }
-        Cardinality::Optional | Cardinality::Required => {
-            write_single_column(
-                columns_state,
-                column_key,
-                value,
-                field_descriptor,
-                column,
-                max_rows,
-                row_idx,
-            )?;
-        }
-    }
-
-    Ok(())
-}
-
-pub fn write_single_column(
-    columns_state: &mut HashMap<ColumnKey, u64>,
-    column_key: &ColumnKey,
-    value: &Value,
-    field_descriptor: &FieldDescriptor,
-    column: duckdb::ffi::duckdb_vector,
-    max_rows: usize,
-    row_idx: usize,
-) -> Result<(), anyhow::Error> {
-    match field_descriptor.kind() {
-        Kind::Message(message_descriptor)
-            if message_descriptor.full_name() == "google.protobuf.Timestamp" =>
-        {
-            let message = value
-                .as_message()
-                .ok_or_else(|| format_err!("expected message"))?;
-            let seconds =
-                message
-                    .get_field(&message_descriptor.get_field(1).ok_or_else(|| {
-                        format_err!("expected field 1 for google.protobuf.Timestamp")
-                    })?)
-                    .as_i64()
-                    .ok_or_else(|| format_err!("expected i64"))?;
-
-            let nanos =
-                message
-                    .get_field(&message_descriptor.get_field(2).ok_or_else(|| {
-                        format_err!("expected field 2 for google.protobuf.Timestamp")
-                    })?)
-                    .as_i32()
-                    .ok_or_else(|| format_err!("expected i32"))?;
-
-            let mut vector = unsafe { MyFlatVector::<i64>::with_capacity(column, max_rows) };
-            vector.as_mut_slice()[row_idx] = seconds * 1000000 + (nanos as i64 / 1000);
-        }
-        Kind::Message(..) => {
-            let message = value
-                .as_message()
-                .ok_or_else(|| format_err!("expected message"))?;
-
-            let source = unsafe { StructVector::new(column) };
-
-            write_message(
-                columns_state,
-                column_key,
-                message,
-                &source,
-                max_rows,
-                row_idx,
-            )?;
-        }
-        Kind::Enum(enum_descriptor) => {
-            let enum_value = value
-                .as_enum_number()
-                .ok_or_else(|| format_err!("expected enum value"))?;
-
-            let enum_value_descriptor = enum_descriptor
-                .get_value(enum_value)
-                .unwrap_or_else(|| enum_descriptor.default_value());
-
-            let (idx, _) = enum_descriptor
-                .values()
-                .enumerate()
-                .find(|(_, it)| it.number() == enum_value_descriptor.number())
-                .unwrap();
-
-            let column_type =
-                unsafe { duckdb::ffi::duckdb_vector_get_column_type(column) };
-
-            let logical_type = LogicalTypeId::from(unsafe { duckdb::ffi::duckdb_enum_internal_type(column_type) });
-
-            match logical_type {
-                LogicalTypeId::UTinyint => {
-                    let mut vector = unsafe { MyFlatVector::<u8>::with_capacity(column, max_rows) };
-                    vector.as_mut_slice()[row_idx] = idx as _;
-                }
-                LogicalTypeId::USmallint => {
-                    let mut vector =
-                        unsafe { MyFlatVector::<u16>::with_capacity(column, max_rows) };
-                    vector.as_mut_slice()[row_idx] = idx as _;
-                }
-                LogicalTypeId::UInteger => {
-                    let mut vector =
-                        unsafe { MyFlatVector::<u32>::with_capacity(column, max_rows) };
-                    vector.as_mut_slice()[row_idx] = idx as _;
-                }
-                _ => bail!("unknown enum column type {:?}", logical_type),
-            }
-        }
-        Kind::String => {
-            let value = value
-                .as_str()
-                .ok_or_else(|| format_err!("expected string"))?;
-            let value = CString::new(value)?;
-
-            unsafe {
-                duckdb::ffi::duckdb_vector_assign_string_element(
-                    column,
-                    row_idx as u64,
-                    value.as_ptr(),
-                )
-            };
-        }
-        Kind::Double => {
-            let value = value
-                .as_f64()
-                .ok_or_else(|| format_err!("expected double"))?;
-            let mut vector = unsafe { MyFlatVector::<f64>::with_capacity(column, max_rows) };
-            vector.as_mut_slice()[row_idx] = value;
-        }
-        Kind::Float => {
-            let value = value
-                .as_f32()
-                .ok_or_else(|| format_err!("expected float"))?;
-            let mut vector = unsafe { MyFlatVector::<f32>::with_capacity(column, max_rows) };
-            vector.as_mut_slice()[row_idx] = value;
-        }
-        Kind::Int32 => {
-            let value = value
-                .as_i32()
-                .ok_or_else(|| format_err!("expected int32"))?;
-            let mut vector = unsafe { MyFlatVector::<i32>::with_capacity(column, max_rows) };
-            vector.as_mut_slice()[row_idx] = value;
-        }
-        Kind::Int64 => {
-            let value = value
-                .as_i64()
-                .ok_or_else(|| format_err!("expected int64"))?;
-            let mut vector = unsafe { MyFlatVector::<i64>::with_capacity(column, max_rows) };
-            vector.as_mut_slice()[row_idx] = value;
-        }
-        Kind::Uint32 => {
-            let value = value
-                .as_u32()
-                .ok_or_else(|| format_err!("expected uint32"))?;
-            let mut vector = unsafe { MyFlatVector::<u32>::with_capacity(column, max_rows) };
-            vector.as_mut_slice()[row_idx] = value;
-        }
-        Kind::Uint64 => {
-            let value = value
-                .as_u64()
-                .ok_or_else(|| format_err!("expected uint64"))?;
-            let mut vector = unsafe { MyFlatVector::<u64>::with_capacity(column, max_rows) };
-            vector.as_mut_slice()[row_idx] = value;
-        }
-        Kind::Bool => {
-            let value = value
-                .as_bool()
-                .ok_or_else(|| format_err!("expected bool"))?;
-            let mut vector = unsafe { MyFlatVector::<bool>::with_capacity(column, max_rows) };
-            vector.as_mut_slice()[row_idx] = value;
-        }
-        _ => {
-            bail!("unhandled field type");
-        }
-    };
-
-    Ok(())
-}
-
 #[derive(Hash, Eq, PartialEq, Clone)]
 pub enum ColumnKeyElement {
     Field { field_tag: u32 },
     List,
+    UnionMember { tag: u8 },
 }
 
+/// Identifies a (possibly nested) output vector by the path of fields and list
+/// elements leading to it from the top-level column. Every list vector reached
+/// through the same path shares a single child vector across all rows of a
+/// chunk, so the next free offset in that child vector is tracked per key.
+/// Keys of nested lists include the elements of every enclosing list, keeping
+/// the offsets of each nesting level independent.
 #[derive(Hash, Eq, PartialEq, Clone)]
 pub struct ColumnKey {
     pub elements: Vec<ColumnKeyElement>,
@@ -375,10 +76,10 @@ impl VectorAccessor for DataChunk {
     }
 }
 
-struct StructVector(duckdb::ffi::duckdb_vector);
+pub struct StructVector(duckdb::ffi::duckdb_vector);
 
 impl StructVector {
-    unsafe fn new(value: duckdb::ffi::duckdb_vector) -> Self {
+    pub unsafe fn new(value: duckdb::ffi::duckdb_vector) -> Self {
         Self(value)
     }
 }
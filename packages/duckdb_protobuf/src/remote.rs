@@ -0,0 +1,57 @@
+//! Files addressed by URL, like `s3://bucket/dumps/*.bin`, read through
+//! `object_store` instead of the local filesystem. URLs travel through the
+//! rest of the extension as `PathBuf`s holding the URL text, so they show up
+//! as is in `filename`.
+//!
+//! Reading them pulls in `object_store` and a `tokio` runtime, so it's behind
+//! the off-by-default `remote` feature. Without it, URLs are still told apart
+//! from local paths but fail to open.
+
+#[cfg(feature = "remote")]
+mod store;
+
+#[cfg(feature = "remote")]
+pub use store::{glob, len, open};
+
+#[cfg(not(feature = "remote"))]
+use std::io;
+use std::path::Path;
+#[cfg(not(feature = "remote"))]
+use std::path::PathBuf;
+
+/// Whether `files`, or a path taken from it, is a URL rather than a local
+/// path.
+pub fn is_url(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .to_str()
+        .map_or(false, |it| it.contains("://"))
+}
+
+#[cfg(not(feature = "remote"))]
+fn unsupported(url: impl AsRef<Path>) -> String {
+    format!(
+        "{}: reading URLs requires building the extension with the `remote` feature",
+        url.as_ref().display()
+    )
+}
+
+#[cfg(not(feature = "remote"))]
+pub fn glob(pattern: &str) -> Result<Vec<PathBuf>, anyhow::Error> {
+    Err(anyhow::Error::msg(unsupported(pattern)))
+}
+
+#[cfg(not(feature = "remote"))]
+pub fn open(path: &Path) -> Result<Box<dyn io::Read>, io::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        unsupported(path),
+    ))
+}
+
+#[cfg(not(feature = "remote"))]
+pub fn len(path: &Path) -> Result<u64, io::Error> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        unsupported(path),
+    ))
+}
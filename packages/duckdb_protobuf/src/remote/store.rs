@@ -0,0 +1,143 @@
+//! The `object_store` backed half of [`super`], built with the `remote`
+//! feature.
+
+use anyhow::format_err;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use glob::{MatchOptions, Pattern};
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::runtime::Runtime;
+use url::Url;
+
+/// Characters starting a wildcard in a `glob` pattern.
+const WILDCARDS: [char; 3] = ['*', '?', '['];
+
+/// Lists the objects matching a URL holding `glob` wildcards, in alphabetical
+/// order like local globs. Only the part of the URL following the last `/`
+/// before the first wildcard is matched, the rest is the prefix objects are
+/// listed under.
+pub fn glob(pattern: &str) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let Some(wildcard) = pattern.find(WILDCARDS) else {
+        return Ok(vec![PathBuf::from(pattern)]);
+    };
+
+    let split = pattern[..wildcard].rfind('/').map_or(0, |it| it + 1);
+    let (base, rest) = pattern.split_at(split);
+    let rest = Pattern::new(rest)?;
+
+    let (store, prefix) = store(base)?;
+
+    let objects = runtime().block_on(async {
+        store
+            .list(Some(&prefix))
+            .map(|meta| meta.map(|it| it.location))
+            .collect::<Vec<_>>()
+            .await
+    });
+
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+
+    let mut paths = vec![];
+    for location in objects {
+        let location = location?;
+        let relative = location
+            .as_ref()
+            .strip_prefix(prefix.as_ref())
+            .unwrap_or(location.as_ref())
+            .trim_start_matches('/');
+
+        if rest.matches_with(relative, options) {
+            paths.push(PathBuf::from(format!("{}{}", base, relative)));
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Streams an object's bytes.
+pub fn open(path: &Path) -> Result<Box<dyn Read>, io::Error> {
+    let (store, location) = store(url_text(path)?).map_err(io::Error::other)?;
+
+    let stream = runtime()
+        .block_on(store.get(&location))
+        .map_err(io::Error::other)?
+        .into_stream();
+
+    Ok(Box::new(ObjectReader {
+        stream,
+        chunk: Default::default(),
+    }))
+}
+
+/// Size of an object in bytes.
+pub fn len(path: &Path) -> Result<u64, io::Error> {
+    let (store, location) = store(url_text(path)?).map_err(io::Error::other)?;
+
+    let meta = runtime()
+        .block_on(store.head(&location))
+        .map_err(io::Error::other)?;
+
+    Ok(meta.size as u64)
+}
+
+fn url_text(path: &Path) -> Result<&str, io::Error> {
+    path.to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "URL isn't valid UTF-8"))
+}
+
+/// The store a URL points into and the object path within it. Credentials
+/// and regions come from the usual `AWS_*` environment variables.
+fn store(url: &str) -> Result<(Box<dyn ObjectStore>, ObjectPath), anyhow::Error> {
+    let url = Url::parse(url).map_err(|err| format_err!("{}: {}", url, err))?;
+
+    let options = std::env::vars()
+        .filter(|(key, _)| key.starts_with("AWS_"))
+        .map(|(key, value)| (key.to_lowercase(), value));
+
+    Ok(object_store::parse_url_opts(&url, options)?)
+}
+
+/// Runs the stores' requests. Created on first use and shared by every thread
+/// of every scan.
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start the runtime for remote files")
+    })
+}
+
+/// Reads an object sequentially, fetching the next chunk once the current one
+/// is consumed.
+struct ObjectReader {
+    stream: BoxStream<'static, object_store::Result<bytes::Bytes>>,
+    chunk: bytes::Bytes,
+}
+
+impl Read for ObjectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            match runtime().block_on(self.stream.next()) {
+                None => return Ok(0),
+                Some(chunk) => self.chunk = chunk.map_err(io::Error::other)?,
+            }
+        }
+
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+
+        Ok(len)
+    }
+}
@@ -0,0 +1,119 @@
+use crate::wire::field_payloads;
+use anyhow::{bail, format_err};
+use prost_reflect::{Kind, MessageDescriptor};
+use std::borrow::Cow;
+
+/// A dotted path of message fields, resolved against the descriptor, which
+/// points at the repeated message field whose elements become rows, or with
+/// `root_message_path` at the singular message field which becomes the row.
+pub struct RootPath {
+    field_numbers: Vec<u32>,
+    message_name: String,
+    /// Whether the field at the end of the path is repeated.
+    repeated: bool,
+}
+
+impl RootPath {
+    /// Resolves a `root_path`, ending in a repeated message field.
+    pub fn resolve(descriptor: &MessageDescriptor, path: &str) -> Result<RootPath, anyhow::Error> {
+        RootPath::resolve_path(descriptor, path, true)
+    }
+
+    /// Resolves a `root_message_path`, made of singular message fields only.
+    pub fn resolve_message(
+        descriptor: &MessageDescriptor,
+        path: &str,
+    ) -> Result<RootPath, anyhow::Error> {
+        RootPath::resolve_path(descriptor, path, false)
+    }
+
+    fn resolve_path(
+        descriptor: &MessageDescriptor,
+        path: &str,
+        repeated: bool,
+    ) -> Result<RootPath, anyhow::Error> {
+        let segments = path.split('.').collect::<Vec<_>>();
+
+        let mut current = descriptor.clone();
+        let mut field_numbers = vec![];
+
+        for (idx, segment) in segments.iter().enumerate() {
+            let field = current.get_field_by_name(segment).ok_or_else(|| {
+                format_err!(
+                    "field `{}` not found in message `{}`",
+                    segment,
+                    current.full_name()
+                )
+            })?;
+
+            let Kind::Message(message_descriptor) = field.kind() else {
+                bail!("field `{}` is not a message", segment);
+            };
+
+            if field.is_map() {
+                bail!("field `{}` is a map", segment);
+            }
+
+            if !repeated && field.is_list() {
+                bail!(
+                    "field `{}` is repeated, use `root_path` to read its elements as rows",
+                    segment
+                );
+            }
+
+            let is_leaf = idx == segments.len() - 1;
+            if repeated && is_leaf && !field.is_list() {
+                bail!(
+                    "field `{}` at the end of the path must be repeated",
+                    segment
+                );
+            }
+
+            if repeated && !is_leaf && field.is_list() {
+                bail!(
+                    "field `{}` is repeated, only the last field in the path may be repeated",
+                    segment
+                );
+            }
+
+            field_numbers.push(field.number());
+            current = message_descriptor;
+        }
+
+        Ok(RootPath {
+            field_numbers,
+            message_name: current.full_name().to_string(),
+            repeated,
+        })
+    }
+
+    /// The fully qualified name of the message each row is decoded as.
+    pub fn message_name(&self) -> &str {
+        &self.message_name
+    }
+
+    /// Splits an encoded top-level message into the encoded elements of the
+    /// repeated field at the end of the path. Intermediate messages which
+    /// appear more than once are concatenated, matching protobuf's merge
+    /// semantics. A singular field at the end of the path gives a single
+    /// row, holding its default values when it's absent.
+    pub fn extract(&self, bytes: &[u8]) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+        let Some((leaf, intermediate)) = self.field_numbers.split_last() else {
+            return Ok(vec![bytes.to_vec()]);
+        };
+
+        let mut message = Cow::Borrowed(bytes);
+        for field_number in intermediate {
+            message = Cow::Owned(field_payloads(&message, *field_number)?.concat());
+        }
+
+        if !self.repeated {
+            return Ok(vec![field_payloads(&message, *leaf)?.concat()]);
+        }
+
+        Ok(field_payloads(&message, *leaf)?
+            .into_iter()
+            .map(|it| it.to_vec())
+            .collect())
+    }
+}
@@ -0,0 +1,56 @@
+//! Ids of `protobuf()` scans, and what's kept of the most recent ones so it
+//! can be queried after they finish, like their errors and stats.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Identifies a `protobuf()` scan. Ids are handed out in the order scans
+/// start, from 1.
+pub type ScanId = u64;
+
+pub fn next_scan_id() -> ScanId {
+    static NEXT_SCAN_ID: AtomicU64 = AtomicU64::new(1);
+
+    NEXT_SCAN_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Number of scans kept around, the oldest being dropped once more scans
+/// start.
+const KEPT_SCANS: usize = 16;
+
+/// A value kept for each of the most recently started scans.
+pub struct ScanHistory<T> {
+    scans: Mutex<VecDeque<(ScanId, Arc<T>)>>,
+}
+
+impl<T> ScanHistory<T> {
+    pub const fn new() -> ScanHistory<T> {
+        ScanHistory {
+            scans: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn start_scan(&self, scan_id: ScanId, value: T) -> Arc<T> {
+        let value = Arc::new(value);
+
+        let mut scans = self.scans.lock().unwrap();
+        scans.push_back((scan_id, value.clone()));
+        if scans.len() > KEPT_SCANS {
+            scans.pop_front();
+        }
+
+        value
+    }
+
+    /// The value of scan `scan_id`, or of the most recently started scan.
+    pub fn get(&self, scan_id: Option<ScanId>) -> Option<(ScanId, Arc<T>)> {
+        let scans = self.scans.lock().unwrap();
+        let found = match scan_id {
+            None => scans.back(),
+            Some(scan_id) => scans.iter().find(|(id, _)| *id == scan_id),
+        };
+
+        found.cloned()
+    }
+}
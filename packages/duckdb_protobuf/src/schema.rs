@@ -0,0 +1,220 @@
+use crate::gen::set_null;
+use crate::read::{MyFlatVector, VectorAccessor};
+use crate::types::{row_columns, Column};
+use crate::vtab::{
+    decode_options, descriptor_bytes, format_error_with_causes, message_descriptor, Handle,
+    Parameters,
+};
+use duckdb::vtab::{BindInfo, DataChunk, FunctionInfo, InitInfo, LogicalType, LogicalTypeId, VTab};
+use prost_reflect::{DescriptorPool, Kind};
+use std::error::Error;
+use std::ffi::CString;
+
+/// A column `protobuf(...)` reads a message into.
+pub struct ColumnInfo {
+    name: String,
+    /// `None` for a oneof read as a single column.
+    number: Option<i32>,
+    proto_type: String,
+    duckdb_type: String,
+    repeated: bool,
+}
+
+pub struct SchemaScanState {
+    offset: usize,
+}
+
+/// `protobuf_schema(descriptors = ..., message_type = ...)`, describing the
+/// columns a message is read into, taking the same parameters as
+/// `protobuf(...)`.
+pub struct ProtobufSchemaVTab;
+
+impl VTab for ProtobufSchemaVTab {
+    type InitData = Handle<SchemaScanState>;
+    type BindData = Handle<Vec<ColumnInfo>>;
+
+    unsafe fn bind(
+        bind: &BindInfo,
+        data: *mut Self::BindData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::bind(bind, data).map_err(format_error_with_causes)?)
+    }
+
+    unsafe fn init(
+        init_info: &InitInfo,
+        data: *mut Self::InitData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        let data = &mut *data;
+        data.init();
+
+        init_info.set_max_threads(1);
+        data.assign(SchemaScanState { offset: 0 });
+
+        Ok(())
+    }
+
+    unsafe fn func(
+        func: &FunctionInfo,
+        output: &mut DataChunk,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::func(func, output).map_err(format_error_with_causes)?)
+    }
+
+    fn named_parameters() -> Option<Vec<(String, LogicalType)>> {
+        Some(Parameters::values())
+    }
+}
+
+impl ProtobufSchemaVTab {
+    fn bind(bind: &BindInfo, data: *mut <Self as VTab>::BindData) -> Result<(), anyhow::Error> {
+        let data = unsafe { &mut *data };
+        data.init();
+
+        let descriptor_pool = DescriptorPool::decode(descriptor_bytes(bind)?.as_slice())?;
+        let descriptor = message_descriptor(bind, &descriptor_pool)?;
+        let options = decode_options(bind)?;
+
+        let mut columns = vec![];
+        for column in row_columns(&descriptor, &options)? {
+            let (number, proto_type, repeated) = match &column {
+                Column::Field(field) => (
+                    Some(field.number() as i32),
+                    kind_name(&field.kind()),
+                    field.is_list() || field.is_map(),
+                ),
+                Column::Oneof(..) => (None, "oneof".to_string(), false),
+            };
+
+            columns.push(ColumnInfo {
+                name: column.name(&options).into_owned(),
+                number,
+                proto_type,
+                duckdb_type: type_name(&column.logical_type(&options)?),
+                repeated,
+            });
+        }
+
+        bind.add_result_column("name", LogicalType::new(LogicalTypeId::Varchar));
+        bind.add_result_column("number", LogicalType::new(LogicalTypeId::Integer));
+        bind.add_result_column("proto_type", LogicalType::new(LogicalTypeId::Varchar));
+        bind.add_result_column("duckdb_type", LogicalType::new(LogicalTypeId::Varchar));
+        bind.add_result_column("repeated", LogicalType::new(LogicalTypeId::Boolean));
+
+        data.assign(columns);
+
+        Ok(())
+    }
+
+    fn func(func: &FunctionInfo, output: &mut DataChunk) -> Result<(), anyhow::Error> {
+        let bind_data = unsafe { &*func.get_bind_data::<<Self as VTab>::BindData>() };
+        let init_data = unsafe { &mut *func.get_init_data::<<Self as VTab>::InitData>() };
+
+        let available_chunk_size = output.flat_vector(0).capacity();
+        let remaining = &bind_data[init_data.offset..];
+        let items = remaining.len().min(available_chunk_size);
+
+        for (row_idx, column) in remaining[..items].iter().enumerate() {
+            for (column_idx, value) in [
+                (0, &column.name),
+                (2, &column.proto_type),
+                (3, &column.duckdb_type),
+            ] {
+                let value = CString::new(value.as_str())?;
+                unsafe {
+                    duckdb::ffi::duckdb_vector_assign_string_element(
+                        output.get_vector(column_idx),
+                        row_idx as _,
+                        value.as_ptr(),
+                    );
+                }
+            }
+
+            match column.number {
+                Some(number) => {
+                    let mut numbers = unsafe {
+                        MyFlatVector::<i32>::with_capacity(
+                            output.get_vector(1),
+                            available_chunk_size,
+                        )
+                    };
+                    numbers.as_mut_slice()[row_idx] = number;
+                }
+                None => set_null(output.get_vector(1), row_idx),
+            }
+
+            let mut repeated = unsafe {
+                MyFlatVector::<bool>::with_capacity(output.get_vector(4), available_chunk_size)
+            };
+            repeated.as_mut_slice()[row_idx] = column.repeated;
+        }
+
+        init_data.offset += items;
+        output.set_len(items);
+
+        Ok(())
+    }
+}
+
+/// Name of a field's type in a `.proto` file, the full name for messages and
+/// enums.
+fn kind_name(kind: &Kind) -> String {
+    match kind {
+        Kind::Message(message) => message.full_name().to_string(),
+        Kind::Enum(enum_descriptor) => enum_descriptor.full_name().to_string(),
+        Kind::Double => "double".to_string(),
+        Kind::Float => "float".to_string(),
+        Kind::Int32 => "int32".to_string(),
+        Kind::Int64 => "int64".to_string(),
+        Kind::Uint32 => "uint32".to_string(),
+        Kind::Uint64 => "uint64".to_string(),
+        Kind::Sint32 => "sint32".to_string(),
+        Kind::Sint64 => "sint64".to_string(),
+        Kind::Fixed32 => "fixed32".to_string(),
+        Kind::Fixed64 => "fixed64".to_string(),
+        Kind::Sfixed32 => "sfixed32".to_string(),
+        Kind::Sfixed64 => "sfixed64".to_string(),
+        Kind::Bool => "bool".to_string(),
+        Kind::String => "string".to_string(),
+        Kind::Bytes => "bytes".to_string(),
+    }
+}
+
+/// Renders a type the way duckdb spells it. The C API only exposes the
+/// children of structs and lists, so the parameters of other types, like the
+/// values of an `ENUM` or the members of a `UNION`, are left out.
+fn type_name(logical_type: &LogicalType) -> String {
+    match logical_type.id() {
+        LogicalTypeId::Struct => {
+            let children = (0..logical_type.num_children())
+                .map(|idx| {
+                    format!(
+                        "{} {}",
+                        logical_type.child_name(idx),
+                        type_name(&logical_type.child(idx))
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            format!("STRUCT({})", children.join(", "))
+        }
+        LogicalTypeId::List => format!("{}[]", type_name(&logical_type.child(0))),
+        LogicalTypeId::Boolean => "BOOLEAN".to_string(),
+        LogicalTypeId::Integer => "INTEGER".to_string(),
+        LogicalTypeId::Bigint => "BIGINT".to_string(),
+        LogicalTypeId::UInteger => "UINTEGER".to_string(),
+        LogicalTypeId::UBigint => "UBIGINT".to_string(),
+        LogicalTypeId::Float => "FLOAT".to_string(),
+        LogicalTypeId::Double => "DOUBLE".to_string(),
+        LogicalTypeId::Decimal => "DECIMAL".to_string(),
+        LogicalTypeId::Varchar => "VARCHAR".to_string(),
+        LogicalTypeId::Blob => "BLOB".to_string(),
+        LogicalTypeId::Timestamp => "TIMESTAMP".to_string(),
+        LogicalTypeId::TimestampS => "TIMESTAMP_S".to_string(),
+        LogicalTypeId::TimestampMs => "TIMESTAMP_MS".to_string(),
+        LogicalTypeId::TimestampNs => "TIMESTAMP_NS".to_string(),
+        LogicalTypeId::Interval => "INTERVAL".to_string(),
+        LogicalTypeId::Enum => "ENUM".to_string(),
+        LogicalTypeId::Union => "UNION".to_string(),
+        id => format!("{:?}", id).to_uppercase(),
+    }
+}
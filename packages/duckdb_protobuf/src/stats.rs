@@ -0,0 +1,152 @@
+use crate::read::MyFlatVector;
+use crate::scans::{ScanHistory, ScanId};
+use crate::vtab::{format_error_with_causes, Handle};
+use duckdb::vtab::{BindInfo, DataChunk, FunctionInfo, InitInfo, LogicalType, LogicalTypeId, VTab};
+use std::error::Error;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Counts the records read by a single scan, across all of its threads. Like
+/// [`crate::errors::ErrorCollector`], the counters of the most recent scans
+/// are kept around by scan id so they can be queried after they finish.
+#[derive(Default)]
+pub struct ScanStats {
+    /// The `files` of the scan.
+    files: String,
+    records: AtomicU64,
+    bytes: AtomicU64,
+}
+
+static SCAN_STATS: ScanHistory<ScanStats> = ScanHistory::new();
+
+impl ScanStats {
+    pub fn start_scan(scan_id: ScanId, files: &str) -> Arc<ScanStats> {
+        SCAN_STATS.start_scan(
+            scan_id,
+            ScanStats {
+                files: files.to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Counts a record read from a file, whether or not it decodes. `bytes`
+    /// is the length of its message.
+    pub fn push_record(&self, bytes: usize) {
+        self.records.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// The counters of scan `scan_id`, or of the most recently started scan.
+    fn scan(scan_id: Option<ScanId>) -> Option<(String, u64, u64, ScanId)> {
+        let (scan_id, stats) = SCAN_STATS.get(scan_id)?;
+
+        Some((
+            stats.files.clone(),
+            stats.records.load(Ordering::Relaxed),
+            stats.bytes.load(Ordering::Relaxed),
+            scan_id,
+        ))
+    }
+}
+
+pub struct StatsScanState {
+    stats: Option<(String, u64, u64, ScanId)>,
+}
+
+/// `protobuf_last_scan_stats()`, giving the `files` of the most recent
+/// `protobuf()` scan, or of the scan given as `scan_id`, and the number of
+/// records and bytes it read, or no rows when there wasn't one.
+pub struct ProtobufLastScanStatsVTab;
+
+impl VTab for ProtobufLastScanStatsVTab {
+    type InitData = Handle<StatsScanState>;
+    type BindData = Handle<Option<ScanId>>;
+
+    unsafe fn bind(
+        bind: &BindInfo,
+        data: *mut Self::BindData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        let data = &mut *data;
+        data.init();
+
+        bind.add_result_column("files", LogicalType::new(LogicalTypeId::Varchar));
+        bind.add_result_column("records", LogicalType::new(LogicalTypeId::UBigint));
+        bind.add_result_column("bytes", LogicalType::new(LogicalTypeId::UBigint));
+        bind.add_result_column("scan_id", LogicalType::new(LogicalTypeId::UBigint));
+
+        let scan_id = bind
+            .get_named_parameter("scan_id")
+            .map(|value| value.to_int64() as ScanId);
+        data.assign(scan_id);
+
+        Ok(())
+    }
+
+    unsafe fn init(
+        init_info: &InitInfo,
+        data: *mut Self::InitData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        let data = &mut *data;
+        data.init();
+
+        let bind_data = &*init_info.get_bind_data::<Self::BindData>();
+
+        init_info.set_max_threads(1);
+        data.assign(StatsScanState {
+            stats: ScanStats::scan(**bind_data),
+        });
+
+        Ok(())
+    }
+
+    unsafe fn func(
+        func: &FunctionInfo,
+        output: &mut DataChunk,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::func(func, output).map_err(format_error_with_causes)?)
+    }
+
+    fn named_parameters() -> Option<Vec<(String, LogicalType)>> {
+        Some(vec![(
+            "scan_id".to_string(),
+            LogicalType::new(LogicalTypeId::UBigint),
+        )])
+    }
+}
+
+impl ProtobufLastScanStatsVTab {
+    fn func(func: &FunctionInfo, output: &mut DataChunk) -> Result<(), anyhow::Error> {
+        let init_data = unsafe { &mut *func.get_init_data::<<Self as VTab>::InitData>() };
+
+        let Some((files, records, bytes, scan_id)) = init_data.stats.take() else {
+            output.set_len(0);
+            return Ok(());
+        };
+
+        let files = CString::new(files)?;
+        unsafe {
+            duckdb::ffi::duckdb_vector_assign_string_element(
+                output.get_vector(0),
+                0,
+                files.as_ptr(),
+            );
+        }
+
+        let available_chunk_size = output.flat_vector(0).capacity();
+        for (column_idx, value) in [(1, records), (2, bytes), (3, scan_id)] {
+            let mut vector = unsafe {
+                MyFlatVector::<u64>::with_capacity(
+                    output.get_vector(column_idx),
+                    available_chunk_size,
+                )
+            };
+            vector.as_mut_slice()[0] = value;
+        }
+
+        output.set_len(1);
+
+        Ok(())
+    }
+}
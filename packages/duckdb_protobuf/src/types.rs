@@ -1,32 +1,784 @@
-use anyhow::format_err;
+use anyhow::{bail, format_err, Context};
 use duckdb::vtab::{LogicalType, LogicalTypeId};
-use prost_reflect::{Cardinality, FieldDescriptor, Kind};
+use prost_reflect::{Cardinality, FieldDescriptor, Kind, MessageDescriptor, OneofDescriptor};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::CString;
+use strum::{AsRefStr, EnumIter, EnumString};
 
-pub fn into_logical_type(field: &FieldDescriptor) -> Result<LogicalType, anyhow::Error> {
+/// Parameters changing how fields are represented, shared by the column types
+/// and the decoder so the two agree.
+#[derive(Clone)]
+pub struct DecodeOptions {
+    pub unknown_enum: UnknownEnum,
+    pub struct_field_order: StructFieldOrder,
+    /// Message types `google.protobuf.Any` fields are decoded as. When empty,
+    /// `Any` fields are read as their `type_url` and encoded `value`.
+    pub any_types: Vec<String>,
+    pub invalid_utf8: InvalidUtf8,
+    pub unsupported_fields: UnsupportedFields,
+    /// Scale of `int64` fields holding fixed point decimals, by field name or
+    /// fully qualified field name.
+    pub decimal_fields: HashMap<String, u8>,
+    /// Number of a custom field option holding the scale of fixed point
+    /// `int64` fields, for descriptors annotating them. `decimal_fields`
+    /// takes precedence.
+    pub decimal_scale_option: Option<u32>,
+    /// `int32` fields holding days since 1970-01-01, read as `DATE`s, by
+    /// field name or fully qualified field name.
+    pub date_fields: Vec<String>,
+    /// `int64` fields holding microseconds since midnight, read as `TIME`s,
+    /// by field name or fully qualified field name.
+    pub time_fields: Vec<String>,
+    pub column_naming: ColumnNaming,
+    pub normalize_names: NormalizeNames,
+    /// Suffix the names of fields with their number, like `name#1`, to tell
+    /// fields apart across versions of a schema.
+    pub field_numbers: bool,
+    /// Precision `google.protobuf.Timestamp` messages are read with.
+    pub timestamp_unit: TimestampUnit,
+    /// Repeated message fields read as JSON, by field name or fully qualified
+    /// field name, or [`ALL_FIELDS`] for all of them.
+    pub repeated_messages_as_json: Vec<String>,
+    /// Enum types `int32` fields are read as, by field name or fully
+    /// qualified field name.
+    pub enum_mappings: HashMap<String, String>,
+    /// Message types `bytes` fields holding encoded messages are read as, by
+    /// field name or fully qualified field name.
+    pub decode_bytes_as: HashMap<String, String>,
+    /// Signedness `fixed32` and `fixed64` fields are read with, by field name
+    /// or fully qualified field name.
+    pub fixed_signedness: HashMap<String, FixedSignedness>,
+    /// Read enum fields as their number.
+    pub enum_as_int: bool,
+    /// Names of the columns of the rows' message which are read, all of them
+    /// when empty. Unlike a projection, this changes the columns of the table.
+    pub columns: Vec<String>,
+    /// Read 64 bit integer fields as `VARCHAR`s holding their decimal text,
+    /// for consumers which can't represent them exactly. Fields of
+    /// `decimal_fields` are left alone.
+    pub int64_as_string: bool,
+    /// Read the fields of each oneof into a single struct naming the field
+    /// which is set, see [`Column::Oneof`].
+    pub oneof_as_struct: bool,
+    /// Levels of nested messages read as structs, deeper messages are read as
+    /// their encoded bytes. Bounds the types of self-referential messages.
+    pub max_depth: usize,
+    /// Read singular fields absent from a record as their default value
+    /// rather than `NULL`.
+    pub use_defaults: bool,
+    /// Read fields encoded with a wire type their type can't have, like a
+    /// field whose type changed, as `NULL` rather than failing the record.
+    pub lenient: bool,
+}
+
+impl DecodeOptions {
+    /// Name of the column or struct child holding a field.
+    pub fn column_name<'a>(&self, field: &'a FieldDescriptor) -> Cow<'a, str> {
+        let name = match self.column_naming {
+            ColumnNaming::Proto => field.name(),
+            ColumnNaming::Json => field.json_name(),
+        };
+
+        let name = match self.normalize_names {
+            NormalizeNames::None => Cow::Borrowed(name),
+            NormalizeNames::Lower => Cow::Owned(name.to_lowercase()),
+            NormalizeNames::Snake => Cow::Owned(to_snake_case(name)),
+        };
+
+        if self.field_numbers {
+            return Cow::Owned(format!("{}#{}", name, field.number()));
+        }
+
+        name
+    }
+
+    /// Whether the elements of a repeated message field are read as JSON
+    /// strings instead of structs. Doesn't apply to map fields.
+    pub fn as_json(&self, field: &FieldDescriptor) -> bool {
+        field.is_list()
+            && matches!(field.kind(), Kind::Message(..))
+            && self
+                .repeated_messages_as_json
+                .iter()
+                .any(|name| name == ALL_FIELDS || name == field.name() || name == field.full_name())
+    }
+
+    /// Whether a field is one of [`DecodeOptions::date_fields`].
+    pub fn is_date(&self, field: &FieldDescriptor) -> bool {
+        is_listed(&self.date_fields, field)
+    }
+
+    /// Whether a field is one of [`DecodeOptions::time_fields`].
+    pub fn is_time(&self, field: &FieldDescriptor) -> bool {
+        is_listed(&self.time_fields, field)
+    }
+
+    /// The kind a field is read as, its declared kind unless it's overridden
+    /// by [`DecodeOptions::fixed_signedness`],
+    /// [`DecodeOptions::decode_bytes_as`], [`DecodeOptions::enum_mappings`] or
+    /// [`DecodeOptions::enum_as_int`].
+    pub fn field_kind(&self, field: &FieldDescriptor) -> Result<Kind, anyhow::Error> {
+        if let Some(message_name) = field_entry(&self.decode_bytes_as, field) {
+            if !matches!(field.kind(), Kind::Bytes) {
+                bail!(
+                    "field `{}`: `decode_bytes_as` only applies to bytes fields",
+                    field.name()
+                );
+            }
+
+            // both are length delimited, the bytes are the encoded message
+            let message_descriptor = field
+                .parent_pool()
+                .get_message_by_name(message_name)
+                .ok_or_else(|| {
+                    format_err!(
+                        "`decode_bytes_as`: message type `{}` not found",
+                        message_name
+                    )
+                })?;
+
+            return Ok(Kind::Message(message_descriptor));
+        }
+
+        if let Some(signedness) = field_entry(&self.fixed_signedness, field) {
+            // both are encoded as the same little endian bytes, only the
+            // interpretation differs
+            return Ok(match (field.kind(), signedness) {
+                (Kind::Fixed32 | Kind::Sfixed32, FixedSignedness::Unsigned) => Kind::Fixed32,
+                (Kind::Fixed32 | Kind::Sfixed32, FixedSignedness::Signed) => Kind::Sfixed32,
+                (Kind::Fixed64 | Kind::Sfixed64, FixedSignedness::Unsigned) => Kind::Fixed64,
+                (Kind::Fixed64 | Kind::Sfixed64, FixedSignedness::Signed) => Kind::Sfixed64,
+                _ => bail!(
+                    "field `{}`: `fixed_signedness` only applies to fixed32 and fixed64 fields",
+                    field.name()
+                ),
+            });
+        }
+
+        let Some(enum_name) = field_entry(&self.enum_mappings, field) else {
+            return Ok(match field.kind() {
+                // enums are encoded as int32s
+                Kind::Enum(..) if self.enum_as_int => Kind::Int32,
+                kind => kind,
+            });
+        };
+
+        if !matches!(field.kind(), Kind::Int32) {
+            bail!(
+                "field `{}`: `enum_mappings` only applies to int32 fields",
+                field.name()
+            );
+        }
+
+        let enum_descriptor = field
+            .parent_pool()
+            .get_enum_by_name(enum_name)
+            .ok_or_else(|| format_err!("`enum_mappings`: enum type `{}` not found", enum_name))?;
+
+        if self.enum_as_int {
+            return Ok(Kind::Int32);
+        }
+
+        Ok(Kind::Enum(enum_descriptor))
+    }
+
+    pub fn decimal_scale(&self, field: &FieldDescriptor) -> Result<Option<u8>, anyhow::Error> {
+        if let Some(scale) = field_entry(&self.decimal_fields, field) {
+            return Ok(Some(*scale));
+        }
+
+        let Some(number) = self.decimal_scale_option else {
+            return Ok(None);
+        };
+
+        // custom options declared in the descriptors are decoded as
+        // extensions of `google.protobuf.FieldOptions`
+        let options = field.options();
+        let Some((_, value)) = options
+            .extensions()
+            .find(|(extension, _)| extension.number() == number)
+        else {
+            return Ok(None);
+        };
+
+        let scale = value
+            .as_i32()
+            .map(i64::from)
+            .or_else(|| value.as_u32().map(i64::from))
+            .or_else(|| value.as_i64())
+            .or_else(|| value.as_u64().and_then(|it| i64::try_from(it).ok()));
+
+        match scale {
+            Some(scale @ 0..=18) => Ok(Some(scale as u8)),
+            _ => bail!(
+                "field `{}`: scale of option {} must be between 0 and 18, got: {:?}",
+                field.name(),
+                number,
+                value
+            ),
+        }
+    }
+}
+
+/// Looks up a per field option, by fully qualified field name first and then
+/// by field name.
+fn field_entry<'a, T>(entries: &'a HashMap<String, T>, field: &FieldDescriptor) -> Option<&'a T> {
+    entries
+        .get(field.full_name())
+        .or_else(|| entries.get(field.name()))
+}
+
+/// Whether a field is in a list of field names or fully qualified field
+/// names.
+fn is_listed(names: &[String], field: &FieldDescriptor) -> bool {
+    names
+        .iter()
+        .any(|name| name == field.name() || name == field.full_name())
+}
+
+/// Matches every field in [`DecodeOptions::repeated_messages_as_json`].
+pub const ALL_FIELDS: &str = "*";
+
+/// Name of the union member holding `Any` values whose type isn't one of
+/// [`DecodeOptions::any_types`].
+pub const ANY_RAW_MEMBER: &str = "raw";
+
+/// How the bits of a `fixed32` or `fixed64` field are interpreted. Producers
+/// sometimes store signed values in fields declared unsigned.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum FixedSignedness {
+    Unsigned,
+    Signed,
+}
+
+/// How enum values not declared in the descriptor are read.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum UnknownEnum {
+    /// Read as the enum's default value. Enums are duckdb `ENUM`s.
+    Default,
+    /// Read as the number, in text. Enums are `VARCHAR`s holding the value's
+    /// name.
+    Preserve,
+    /// Read as `NULL`. Enums are duckdb `ENUM`s.
+    Null,
+}
+
+/// How strings which aren't valid UTF-8 are read.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum InvalidUtf8 {
+    /// Fail to decode the record.
+    Error,
+    /// Replace invalid sequences with U+FFFD.
+    Replace,
+}
+
+/// What to do with fields of types which can't be read.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum UnsupportedFields {
+    /// Fail to bind.
+    Error,
+    /// Leave them out of the columns and structs.
+    Skip,
+}
+
+/// Presets for the options dealing with data written by a different version
+/// of the schema, each can still be set individually.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum SchemaEvolution {
+    Strict,
+    Lenient,
+}
+
+impl SchemaEvolution {
+    pub fn unknown_enum(self) -> UnknownEnum {
+        match self {
+            SchemaEvolution::Strict => UnknownEnum::Default,
+            SchemaEvolution::Lenient => UnknownEnum::Preserve,
+        }
+    }
+
+    pub fn invalid_utf8(self) -> InvalidUtf8 {
+        match self {
+            SchemaEvolution::Strict => InvalidUtf8::Error,
+            SchemaEvolution::Lenient => InvalidUtf8::Replace,
+        }
+    }
+
+    pub fn unsupported_fields(self) -> UnsupportedFields {
+        match self {
+            SchemaEvolution::Strict => UnsupportedFields::Error,
+            SchemaEvolution::Lenient => UnsupportedFields::Skip,
+        }
+    }
+
+    pub fn lenient(self) -> bool {
+        matches!(self, SchemaEvolution::Lenient)
+    }
+}
+
+/// Which name of a field its column and struct child names start from,
+/// before [`NormalizeNames`] is applied.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ColumnNaming {
+    /// The name declared in the `.proto`, like `user_id`.
+    Proto,
+    /// The name used by the JSON mapping, like `userId`, or the field's
+    /// `json_name` option.
+    Json,
+}
+
+/// Precision `google.protobuf.Timestamp` messages are read with, finer parts
+/// of their `nanos` are truncated.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum TimestampUnit {
+    /// `TIMESTAMP_S`
+    S,
+    /// `TIMESTAMP_MS`
+    Ms,
+    /// `TIMESTAMP`, holding microseconds.
+    Us,
+    /// `TIMESTAMP_NS`
+    Ns,
+}
+
+impl TimestampUnit {
+    pub fn logical_type_id(self) -> LogicalTypeId {
+        match self {
+            TimestampUnit::S => LogicalTypeId::TimestampS,
+            TimestampUnit::Ms => LogicalTypeId::TimestampMs,
+            TimestampUnit::Us => LogicalTypeId::Timestamp,
+            TimestampUnit::Ns => LogicalTypeId::TimestampNs,
+        }
+    }
+
+    /// How many of the unit make up a second.
+    pub fn per_second(self) -> i64 {
+        match self {
+            TimestampUnit::S => 1,
+            TimestampUnit::Ms => 1_000,
+            TimestampUnit::Us => 1_000_000,
+            TimestampUnit::Ns => 1_000_000_000,
+        }
+    }
+}
+
+/// How field names are transformed into column and struct child names.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum NormalizeNames {
+    None,
+    /// `userId` becomes `userid`.
+    Lower,
+    /// `userId` becomes `user_id`.
+    Snake,
+}
+
+/// Converts a camel or pascal case name to snake case, keeping acronyms
+/// together, so `HTTPServerId` becomes `http_server_id`.
+fn to_snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut result = String::with_capacity(name.len() + 4);
+
+    for (idx, char) in chars.iter().enumerate() {
+        if char.is_uppercase() && idx > 0 {
+            let previous = chars[idx - 1];
+            let next_is_lower = chars.get(idx + 1).map_or(false, |it| it.is_lowercase());
+
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_is_lower)
+            {
+                result.push('_');
+            }
+        }
+
+        result.extend(char.to_lowercase());
+    }
+
+    result
+}
+
+/// Order of the fields of a `STRUCT` read from a message.
+#[derive(Copy, Clone, EnumString, EnumIter, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum StructFieldOrder {
+    /// The order the fields are declared in.
+    Declaration,
+    /// Ascending field number, giving the same type for descriptors declaring
+    /// the same fields in a different order.
+    Number,
+}
+
+/// A column, or a child of a struct read from a message.
+#[derive(Clone)]
+pub enum Column {
+    Field(FieldDescriptor),
+    /// The fields of a oneof, read as a `STRUCT(tag VARCHAR, value UNION)`
+    /// where `tag` names the field which is set and `value` holds it. Both are
+    /// `NULL` when none is set.
+    Oneof(OneofDescriptor),
+}
+
+impl Column {
+    pub fn name<'a>(&'a self, options: &DecodeOptions) -> Cow<'a, str> {
+        match self {
+            Column::Field(field) => options.column_name(field),
+            Column::Oneof(oneof) => match options.normalize_names {
+                NormalizeNames::None => Cow::Borrowed(oneof.name()),
+                NormalizeNames::Lower => Cow::Owned(oneof.name().to_lowercase()),
+                NormalizeNames::Snake => Cow::Owned(to_snake_case(oneof.name())),
+            },
+        }
+    }
+
+    pub fn logical_type(&self, options: &DecodeOptions) -> Result<LogicalType, anyhow::Error> {
+        self.nested_logical_type(options, 0, &mut 0)
+    }
+
+    /// The type of a column of a message nested `depth` levels deep.
+    /// `nested_fields` counts the fields of the structs built so far for the
+    /// top-level column, see [`MAX_NESTED_FIELDS`].
+    fn nested_logical_type(
+        &self,
+        options: &DecodeOptions,
+        depth: usize,
+        nested_fields: &mut usize,
+    ) -> Result<LogicalType, anyhow::Error> {
+        match self {
+            Column::Field(field) => into_logical_type(field, options, depth, nested_fields),
+            Column::Oneof(oneof) => {
+                let fields = oneof_fields(oneof, options);
+                let names = fields
+                    .iter()
+                    .map(|field| options.column_name(field))
+                    .collect::<Vec<_>>();
+
+                check_names(names.iter().map(|name| name.as_ref()))
+                    .with_context(|| format_err!("oneof `{}`", oneof.full_name()))?;
+
+                let members = fields
+                    .iter()
+                    .zip(&names)
+                    .map(|(field, name)| {
+                        Ok((
+                            name.as_ref(),
+                            into_logical_type(field, options, depth, nested_fields)?,
+                        ))
+                    })
+                    .collect::<Result<Vec<(&str, LogicalType)>, anyhow::Error>>()?;
+
+                Ok(LogicalType::struct_type(&[
+                    ("tag", LogicalType::new(LogicalTypeId::Varchar)),
+                    ("value", LogicalType::union_type(members.as_slice())),
+                ]))
+            }
+        }
+    }
+
+    /// The number structs are ordered by with [`StructFieldOrder::Number`],
+    /// the lowest field number of a oneof.
+    fn number(&self, options: &DecodeOptions) -> u32 {
+        match self {
+            Column::Field(field) => field.number(),
+            Column::Oneof(oneof) => oneof_fields(oneof, options)
+                .iter()
+                .map(|field| field.number())
+                .min()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The columns of a message which are read, in declaration order. These are
+/// the columns when it's the message of the rows.
+pub fn field_columns(
+    message_descriptor: &MessageDescriptor,
+    options: &DecodeOptions,
+) -> Vec<Column> {
+    let mut columns = vec![];
+
+    for field in message_descriptor.fields() {
+        if !is_read(&field, options) {
+            continue;
+        }
+
+        match read_oneof(&field, options) {
+            // a oneof takes the place of its first field which is read
+            Some(oneof) => {
+                if oneof_fields(&oneof, options)[0] == field {
+                    columns.push(Column::Oneof(oneof));
+                }
+            }
+            None => columns.push(Column::Field(field)),
+        }
+    }
+
+    columns
+}
+
+/// The columns of the rows' message, the [`field_columns`] named by
+/// [`DecodeOptions::columns`] when it's given.
+pub fn row_columns(
+    message_descriptor: &MessageDescriptor,
+    options: &DecodeOptions,
+) -> Result<Vec<Column>, anyhow::Error> {
+    let columns = field_columns(message_descriptor, options);
+    if options.columns.is_empty() {
+        return Ok(columns);
+    }
+
+    for name in &options.columns {
+        if !columns.iter().any(|column| column.name(options) == *name) {
+            bail!(
+                "`columns`: `{}` isn't a column of `{}`",
+                name,
+                message_descriptor.full_name()
+            );
+        }
+    }
+
+    Ok(columns
+        .into_iter()
+        .filter(|column| options.columns.iter().any(|it| column.name(options) == *it))
+        .collect())
+}
+
+/// The columns of a message read as a `STRUCT`, in the order of the struct's
+/// children.
+pub fn struct_fields(
+    message_descriptor: &MessageDescriptor,
+    options: &DecodeOptions,
+) -> Vec<Column> {
+    let mut columns = field_columns(message_descriptor, options);
+    if let StructFieldOrder::Number = options.struct_field_order {
+        columns.sort_by_key(|column| column.number(options));
+    }
+
+    columns
+}
+
+/// The fields of a oneof which are read, in declaration order, the members of
+/// its `value` union.
+pub fn oneof_fields(oneof: &OneofDescriptor, options: &DecodeOptions) -> Vec<FieldDescriptor> {
+    oneof
+        .fields()
+        .filter(|field| is_read(field, options))
+        .collect()
+}
+
+/// The oneof a field is read as part of, see [`DecodeOptions::oneof_as_struct`].
+/// The synthetic oneofs of proto3 `optional` fields are left alone.
+fn read_oneof(field: &FieldDescriptor, options: &DecodeOptions) -> Option<OneofDescriptor> {
+    if !options.oneof_as_struct {
+        return None;
+    }
+
+    field
+        .containing_oneof()
+        .filter(|oneof| !oneof.is_synthetic())
+}
+
+fn is_read(field: &FieldDescriptor, options: &DecodeOptions) -> bool {
+    match options.unsupported_fields {
+        UnsupportedFields::Error => true,
+        UnsupportedFields::Skip => is_supported(field, options),
+    }
+}
+
+/// The message types an `Any` field is decoded as, in union member order.
+pub fn any_members(
+    any_descriptor: &MessageDescriptor,
+    options: &DecodeOptions,
+) -> Result<Vec<MessageDescriptor>, anyhow::Error> {
+    options
+        .any_types
+        .iter()
+        .map(|name| {
+            any_descriptor
+                .parent_pool()
+                .get_message_by_name(name)
+                .ok_or_else(|| format_err!("`any_types`: message type `{}` not found", name))
+        })
+        .collect()
+}
+
+/// Whether a message is one of the well-known types holding arbitrary JSON,
+/// `google.protobuf.Struct`, `Value` or `ListValue`. These are read as JSON
+/// text wherever they appear, like [`is_timestamp`].
+pub fn is_json_value(message_descriptor: &MessageDescriptor) -> bool {
+    matches!(
+        message_descriptor.full_name(),
+        "google.protobuf.Struct" | "google.protobuf.Value" | "google.protobuf.ListValue"
+    )
+}
+
+/// duckdb's `JSON` type, a `VARCHAR` with the alias the json extension
+/// recognizes.
+fn json_logical_type() -> LogicalType {
+    unsafe {
+        let ptr =
+            duckdb::ffi::duckdb_create_logical_type(duckdb::ffi::DUCKDB_TYPE_DUCKDB_TYPE_VARCHAR);
+        let alias = CString::new("JSON").unwrap();
+        duckdb::ffi::duckdb_logical_type_set_alias(ptr, alias.as_ptr());
+
+        LogicalType::from(ptr)
+    }
+}
+
+/// The `type_url` and encoded `value` of `google.protobuf.Any` values whose
+/// type isn't one of [`DecodeOptions::any_types`], or of all of them without
+/// `any_types`.
+fn any_raw_logical_type() -> LogicalType {
+    LogicalType::struct_type(&[
+        ("type_url", LogicalType::new(LogicalTypeId::Varchar)),
+        ("value", LogicalType::new(LogicalTypeId::Blob)),
+    ])
+}
+
+/// Whether a message is a `google.protobuf.Timestamp`. These are read as a
+/// duckdb `TIMESTAMP` wherever they appear, as a column, a struct child, a
+/// list element or a map value.
+pub fn is_timestamp(message_descriptor: &MessageDescriptor) -> bool {
+    message_descriptor.full_name() == "google.protobuf.Timestamp"
+}
+
+/// The `value` field of the `google.protobuf.*Value` wrapper messages, which
+/// are read as their value, `NULL` when the wrapper is absent. `BytesValue`
+/// isn't unwrapped as `bytes` fields aren't supported.
+pub fn wrapped_field(message_descriptor: &MessageDescriptor) -> Option<FieldDescriptor> {
+    match message_descriptor.full_name() {
+        "google.protobuf.DoubleValue"
+        | "google.protobuf.FloatValue"
+        | "google.protobuf.Int64Value"
+        | "google.protobuf.UInt64Value"
+        | "google.protobuf.Int32Value"
+        | "google.protobuf.UInt32Value"
+        | "google.protobuf.BoolValue"
+        | "google.protobuf.StringValue" => message_descriptor.get_field(1),
+        _ => None,
+    }
+}
+
+/// Whether a message is a `google.protobuf.Duration`, read as a duckdb
+/// `INTERVAL` wherever it appears, like [`is_timestamp`].
+pub fn is_duration(message_descriptor: &MessageDescriptor) -> bool {
+    message_descriptor.full_name() == "google.protobuf.Duration"
+}
+
+/// The type of a field of a message nested `depth` levels deep, 0 for the
+/// message of the rows.
+fn into_logical_type(
+    field: &FieldDescriptor,
+    options: &DecodeOptions,
+    depth: usize,
+    nested_fields: &mut usize,
+) -> Result<LogicalType, anyhow::Error> {
     Ok(match field.cardinality() {
-        Cardinality::Optional | Cardinality::Required => into_logical_type_single(field)?,
-        Cardinality::Repeated => LogicalType::list(&into_logical_type_single(field)?),
+        Cardinality::Optional | Cardinality::Required => {
+            into_logical_type_single(field, options, depth, nested_fields)?
+        }
+        Cardinality::Repeated if options.as_json(field) => {
+            LogicalType::list(&LogicalType::new(LogicalTypeId::Varchar))
+        }
+        Cardinality::Repeated => LogicalType::list(&into_logical_type_single(
+            field,
+            options,
+            depth,
+            nested_fields,
+        )?),
     })
 }
 
-fn into_logical_type_single(field: &FieldDescriptor) -> Result<LogicalType, anyhow::Error> {
-    let value = match field.kind() {
+fn into_logical_type_single(
+    field: &FieldDescriptor,
+    options: &DecodeOptions,
+    depth: usize,
+    nested_fields: &mut usize,
+) -> Result<LogicalType, anyhow::Error> {
+    if options.decimal_scale(field)?.is_some() && !matches!(field.kind(), Kind::Int64) {
+        bail!(
+            "field `{}`: `decimal_fields` and `decimal_scale_option` only apply to int64 fields",
+            field.name()
+        );
+    }
+
+    if options.is_date(field) && !matches!(field.kind(), Kind::Int32) {
+        bail!(
+            "field `{}`: `date_fields` only applies to int32 fields",
+            field.name()
+        );
+    }
+
+    if options.is_time(field) && !matches!(field.kind(), Kind::Int64) {
+        bail!(
+            "field `{}`: `time_fields` only applies to int64 fields",
+            field.name()
+        );
+    }
+
+    let value = match options.field_kind(field)? {
+        Kind::Message(message_descriptor) if is_timestamp(&message_descriptor) => {
+            LogicalType::new(options.timestamp_unit.logical_type_id())
+        }
+        Kind::Message(message_descriptor) if is_duration(&message_descriptor) => {
+            LogicalType::new(LogicalTypeId::Interval)
+        }
+        Kind::Message(message_descriptor) if is_json_value(&message_descriptor) => {
+            json_logical_type()
+        }
+        Kind::Message(message_descriptor) if wrapped_field(&message_descriptor).is_some() => {
+            into_logical_type_single(
+                &wrapped_field(&message_descriptor).unwrap(),
+                options,
+                depth,
+                nested_fields,
+            )?
+        }
+        Kind::Message(..) if depth >= options.max_depth => LogicalType::new(LogicalTypeId::Blob),
         Kind::Message(message_descriptor)
-            if message_descriptor.full_name() == "google.protobuf.Timestamp" =>
+            if message_descriptor.full_name() == "google.protobuf.Any"
+                && !options.any_types.is_empty() =>
         {
-            LogicalType::new(LogicalTypeId::Timestamp)
-        }
-        Kind::Message(message_descriptor) => {
-            let fields = message_descriptor
-                .fields()
-                .collect::<Vec<FieldDescriptor>>();
+            let members = any_members(&message_descriptor, options)?;
 
-            let fields = fields
-                .iter()
-                .map(|field| Ok((field.name(), into_logical_type(&field)?)))
-                .collect::<Result<Vec<(&str, LogicalType)>, anyhow::Error>>()?;
+            check_names(
+                members
+                    .iter()
+                    .map(|member| member.full_name())
+                    .chain([ANY_RAW_MEMBER]),
+            )
+            .with_context(|| format_err!("field `{}`: `any_types`", field.name()))?;
+
+            let mut types = vec![];
+            for member in &members {
+                types.push((
+                    member.full_name(),
+                    message_logical_type(member, options, depth + 1, nested_fields)?,
+                ));
+            }
 
-            LogicalType::struct_type(fields.as_slice())
+            types.push((ANY_RAW_MEMBER, any_raw_logical_type()));
+
+            LogicalType::union_type(types.as_slice())
+        }
+        Kind::Message(message_descriptor)
+            if message_descriptor.full_name() == "google.protobuf.Any" =>
+        {
+            any_raw_logical_type()
+        }
+        Kind::Message(message_descriptor) => {
+            message_logical_type(&message_descriptor, options, depth + 1, nested_fields)?
+        }
+        Kind::Enum(..) if matches!(options.unknown_enum, UnknownEnum::Preserve) => {
+            LogicalType::new(LogicalTypeId::Varchar)
         }
         Kind::Enum(descriptor) => {
             let names = descriptor.values().collect::<Vec<_>>();
@@ -35,10 +787,29 @@ fn into_logical_type_single(field: &FieldDescriptor) -> Result<LogicalType, anyh
         }
         Kind::Double => LogicalType::new(LogicalTypeId::Double),
         Kind::Float => LogicalType::new(LogicalTypeId::Float),
+        // dates and times are stored as the same integers, so the decoder
+        // writes them as is
+        Kind::Int32 if options.is_date(field) => LogicalType::new(LogicalTypeId::Date),
         Kind::Int32 => LogicalType::new(LogicalTypeId::Integer),
-        Kind::Int64 => LogicalType::new(LogicalTypeId::Bigint),
+        Kind::Int64 if options.is_time(field) => LogicalType::new(LogicalTypeId::Time),
+        // decimals of this width are stored as an int64, so the decoder writes
+        // the scaled integer as is
+        Kind::Int64 => match options.decimal_scale(field)? {
+            Some(scale) => LogicalType::decimal(18, scale),
+            None if options.int64_as_string => LogicalType::new(LogicalTypeId::Varchar),
+            None => LogicalType::new(LogicalTypeId::Bigint),
+        },
+        Kind::Uint64 | Kind::Sint64 | Kind::Fixed64 | Kind::Sfixed64 if options.int64_as_string => {
+            LogicalType::new(LogicalTypeId::Varchar)
+        }
         Kind::Uint32 => LogicalType::new(LogicalTypeId::UInteger),
         Kind::Uint64 => LogicalType::new(LogicalTypeId::UBigint),
+        Kind::Sint32 => LogicalType::new(LogicalTypeId::Integer),
+        Kind::Sint64 => LogicalType::new(LogicalTypeId::Bigint),
+        Kind::Fixed32 => LogicalType::new(LogicalTypeId::UInteger),
+        Kind::Fixed64 => LogicalType::new(LogicalTypeId::UBigint),
+        Kind::Sfixed32 => LogicalType::new(LogicalTypeId::Integer),
+        Kind::Sfixed64 => LogicalType::new(LogicalTypeId::Bigint),
         Kind::Bool => LogicalType::new(LogicalTypeId::Boolean),
         Kind::String => LogicalType::new(LogicalTypeId::Varchar),
         logical_type => {
@@ -53,3 +824,100 @@ fn into_logical_type_single(field: &FieldDescriptor) -> Result<LogicalType, anyh
 
     Ok(value)
 }
+
+/// Whether [`into_logical_type`] can handle a field's type, without looking
+/// into messages.
+fn is_supported(field: &FieldDescriptor, options: &DecodeOptions) -> bool {
+    if field_entry(&options.decode_bytes_as, field).is_some() {
+        return true;
+    }
+
+    match field.kind() {
+        Kind::Message(..)
+        | Kind::Enum(..)
+        | Kind::Double
+        | Kind::Float
+        | Kind::Int32
+        | Kind::Int64
+        | Kind::Uint32
+        | Kind::Uint64
+        | Kind::Sint32
+        | Kind::Sint64
+        | Kind::Fixed32
+        | Kind::Fixed64
+        | Kind::Sfixed32
+        | Kind::Sfixed64
+        | Kind::Bool
+        | Kind::String => true,
+        _ => false,
+    }
+}
+
+/// Number of struct fields the type of a single column may hold across all
+/// of its levels. Messages with several fields of their own type double in
+/// size at every level, so `max_depth` alone doesn't keep their types small.
+const MAX_NESTED_FIELDS: usize = 10_000;
+
+fn message_logical_type(
+    message_descriptor: &MessageDescriptor,
+    options: &DecodeOptions,
+    depth: usize,
+    nested_fields: &mut usize,
+) -> Result<LogicalType, anyhow::Error> {
+    let columns = struct_fields(message_descriptor, options);
+
+    *nested_fields += columns.len();
+    if *nested_fields > MAX_NESTED_FIELDS {
+        bail!(
+            "message `{}` nested {} levels deep makes a type of more than {} fields, lower `max_depth` to read deeper messages as their encoded bytes",
+            message_descriptor.full_name(),
+            options.max_depth,
+            MAX_NESTED_FIELDS
+        );
+    }
+
+    let names = columns
+        .iter()
+        .map(|column| column.name(options))
+        .collect::<Vec<_>>();
+
+    check_names(names.iter().map(|name| name.as_ref()))
+        .with_context(|| format_err!("message `{}`", message_descriptor.full_name()))?;
+
+    let fields = columns
+        .iter()
+        .zip(&names)
+        .map(|(column, name)| {
+            Ok((
+                name.as_ref(),
+                column.nested_logical_type(options, depth, nested_fields)?,
+            ))
+        })
+        .collect::<Result<Vec<(&str, LogicalType)>, anyhow::Error>>()?;
+
+    Ok(LogicalType::struct_type(fields.as_slice()))
+}
+
+/// Ensures a set of column or struct field names can be handed to duckdb.
+/// duckdb compares names case-insensitively, so `userId` and `userid` refer to
+/// the same column and would otherwise surface as a confusing ambiguity error
+/// once the query runs.
+pub fn check_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<(), anyhow::Error> {
+    let mut seen = HashMap::<String, &str>::new();
+
+    for name in names {
+        if name.contains('\0') {
+            bail!("name {:?} contains a NUL byte", name);
+        }
+
+        if let Some(existing) = seen.insert(name.to_lowercase(), name) {
+            bail!(
+                "names `{}` and `{}` collide, duckdb treats names case-insensitively",
+                existing,
+                name
+            );
+        }
+    }
+
+    Ok(())
+}
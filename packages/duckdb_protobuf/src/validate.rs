@@ -0,0 +1,276 @@
+use crate::gen::{parse_message, MessageLayout, ParserState};
+use crate::io::{Format, OpenFilesLimit};
+use crate::read::MyFlatVector;
+use crate::types::row_columns;
+use crate::vtab::{format_error_with_causes, Handle, Parameters};
+use duckdb::vtab::{BindInfo, DataChunk, FunctionInfo, InitInfo, LogicalType, LogicalTypeId, VTab};
+use prost_reflect::MessageDescriptor;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::ffi::CString;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub struct ValidateState {
+    tasks: VecDeque<Vec<PathBuf>>,
+    open_files: Arc<OpenFilesLimit>,
+    decoder: ScratchDecoder,
+    text_descriptor: Option<MessageDescriptor>,
+}
+
+/// Decodes records the way a scan with the same parameters would, into a
+/// chunk which is thrown away.
+pub struct ScratchDecoder {
+    layout: MessageLayout,
+    scratch: DataChunk,
+}
+
+/// Outcome of decoding every record of a stream.
+struct Validation {
+    records: u64,
+    first_error: Option<String>,
+}
+
+/// `protobuf_validate(...)`, decoding every record of the files like
+/// `protobuf(...)` would without returning them, and listing for each file
+/// the number of records read and the first error hit.
+pub struct ProtobufValidateVTab;
+
+impl VTab for ProtobufValidateVTab {
+    type InitData = Handle<ValidateState>;
+    type BindData = Handle<Parameters>;
+
+    unsafe fn bind(
+        bind: &BindInfo,
+        data: *mut Self::BindData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::bind(bind, data).map_err(format_error_with_causes)?)
+    }
+
+    unsafe fn init(
+        init_info: &InitInfo,
+        data: *mut Self::InitData,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::init(init_info, data).map_err(format_error_with_causes)?)
+    }
+
+    unsafe fn func(
+        func: &FunctionInfo,
+        output: &mut DataChunk,
+    ) -> duckdb::Result<(), Box<dyn Error>> {
+        Ok(Self::func(func, output).map_err(format_error_with_causes)?)
+    }
+
+    fn named_parameters() -> Option<Vec<(String, LogicalType)>> {
+        Some(Parameters::values())
+    }
+}
+
+impl ProtobufValidateVTab {
+    fn bind(bind: &BindInfo, data: *mut <Self as VTab>::BindData) -> Result<(), anyhow::Error> {
+        let data = unsafe { &mut *data };
+        data.init();
+
+        let params = Parameters::from_bind_info(bind)?;
+
+        bind.add_result_column("filename", LogicalType::new(LogicalTypeId::Varchar));
+        bind.add_result_column("records", LogicalType::new(LogicalTypeId::UBigint));
+        bind.add_result_column("ok", LogicalType::new(LogicalTypeId::Boolean));
+        bind.add_result_column("first_error", LogicalType::new(LogicalTypeId::Varchar));
+
+        data.assign(params);
+
+        Ok(())
+    }
+
+    fn init(
+        init_info: &InitInfo,
+        data: *mut <Self as VTab>::InitData,
+    ) -> Result<(), anyhow::Error> {
+        let data = unsafe { &mut *data };
+        data.init();
+
+        let bind_data = unsafe { &*init_info.get_bind_data::<<Self as VTab>::BindData>() };
+
+        let text_descriptor = match bind_data.format {
+            Format::Binary => None,
+            Format::Text | Format::Jsonl => Some(bind_data.record_descriptor()),
+        };
+
+        init_info.set_max_threads(1);
+        data.assign(ValidateState {
+            tasks: bind_data.tasks()?.into(),
+            open_files: OpenFilesLimit::new(bind_data.max_open_files),
+            decoder: ScratchDecoder::new(bind_data)?,
+            text_descriptor,
+        });
+
+        Ok(())
+    }
+
+    fn func(func: &FunctionInfo, output: &mut DataChunk) -> Result<(), anyhow::Error> {
+        let bind_data = unsafe { &*func.get_bind_data::<<Self as VTab>::BindData>() };
+        let init_data = unsafe { &mut *func.get_init_data::<<Self as VTab>::InitData>() };
+
+        let available_chunk_size = output.flat_vector(0).capacity();
+        let mut items = 0;
+
+        while items < available_chunk_size {
+            let Some(paths) = init_data.tasks.pop_front() else {
+                break;
+            };
+
+            let filename = CString::new(paths[0].to_string_lossy().as_ref())?;
+            let validation = init_data.validate(bind_data, paths);
+
+            unsafe {
+                duckdb::ffi::duckdb_vector_assign_string_element(
+                    output.get_vector(0),
+                    items as _,
+                    filename.as_ptr(),
+                );
+            }
+
+            let mut records = unsafe {
+                MyFlatVector::<u64>::with_capacity(output.get_vector(1), available_chunk_size)
+            };
+            records.as_mut_slice()[items] = validation.records;
+
+            let mut ok = unsafe {
+                MyFlatVector::<bool>::with_capacity(output.get_vector(2), available_chunk_size)
+            };
+            ok.as_mut_slice()[items] = validation.first_error.is_none();
+
+            let first_error = output.get_vector(3);
+            match validation.first_error {
+                None => unsafe {
+                    duckdb::ffi::duckdb_vector_ensure_validity_writable(first_error);
+                    let validity = duckdb::ffi::duckdb_vector_get_validity(first_error);
+                    duckdb::ffi::duckdb_validity_set_row_invalid(validity, items as _);
+                },
+                Some(message) => {
+                    let message = CString::new(message.replace('\0', ""))?;
+                    unsafe {
+                        duckdb::ffi::duckdb_vector_assign_string_element(
+                            first_error,
+                            items as _,
+                            message.as_ptr(),
+                        );
+                    }
+                }
+            }
+
+            items += 1;
+        }
+
+        output.set_len(items);
+
+        Ok(())
+    }
+}
+
+impl ValidateState {
+    /// Reads every record of a stream, stopping early only when the stream
+    /// itself can't be read further.
+    fn validate(&mut self, parameters: &Parameters, paths: Vec<PathBuf>) -> Validation {
+        let mut validation = Validation {
+            records: 0,
+            first_error: None,
+        };
+
+        let mut reader = match parameters.open_records(
+            paths,
+            None,
+            &self.open_files,
+            self.text_descriptor.as_ref(),
+        ) {
+            Ok(reader) => reader,
+            Err(err) => {
+                validation.first_error = Some(format_error_with_causes(err).to_string());
+                return validation;
+            }
+        };
+
+        loop {
+            let record = match reader.try_get_next() {
+                Ok(Some(record)) => record,
+                Ok(None) => break,
+                Err(err) => {
+                    validation
+                        .first_error
+                        .get_or_insert_with(|| err.to_string());
+                    break;
+                }
+            };
+
+            validation.records += 1;
+
+            if let Some(message) = record.trailer_error {
+                validation
+                    .first_error
+                    .get_or_insert_with(|| format!("position {}: {}", record.position, message));
+                continue;
+            }
+
+            if let Err(err) = self.decoder.decode(parameters, &record.bytes) {
+                validation.first_error.get_or_insert_with(|| {
+                    format!(
+                        "position {}: {}",
+                        record.position,
+                        format_error_with_causes(err)
+                    )
+                });
+            }
+        }
+
+        validation
+    }
+}
+
+impl ScratchDecoder {
+    pub fn new(parameters: &Parameters) -> Result<ScratchDecoder, anyhow::Error> {
+        let descriptor = parameters.message_descriptor();
+        let columns = row_columns(&descriptor, &parameters.decode_options)?;
+
+        // the types are checked first, bounding the size of the layout
+        let logical_types = columns
+            .iter()
+            .map(|column| column.logical_type(&parameters.decode_options))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let column_indices = (0..columns.len() as u64).collect::<Vec<_>>();
+        let layout =
+            MessageLayout::for_columns(&descriptor, &column_indices, &parameters.decode_options)?;
+
+        Ok(ScratchDecoder {
+            layout,
+            scratch: DataChunk::new(&logical_types),
+        })
+    }
+
+    /// Decodes a record, or each of the messages `root_path` extracts from
+    /// it.
+    pub fn decode(&mut self, parameters: &Parameters, bytes: &[u8]) -> Result<(), anyhow::Error> {
+        let rows = match &parameters.root_path {
+            None => vec![bytes.to_vec()],
+            Some(root_path) => root_path.extract(bytes)?,
+        };
+
+        for row in rows {
+            // start from an empty chunk every time so the strings and list
+            // elements of previous records don't pile up
+            unsafe { duckdb::ffi::duckdb_data_chunk_reset(self.scratch.get_ptr()) };
+
+            parse_message(
+                &mut ParserState::default(),
+                &self.layout,
+                &row,
+                &self.scratch,
+                1,
+                0,
+            )?;
+        }
+
+        Ok(())
+    }
+}
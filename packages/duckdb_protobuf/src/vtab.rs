@@ -1,7 +1,23 @@
-use crate::filtered_dynamic_message::FilteredDynamicMessage;
-use crate::io::{parse, DelimitedLengthKind, LengthDelimitedRecordsReader, LengthKind, Record};
-use crate::read::{write_to_output, MyFlatVector, VectorAccessor};
-use crate::types::into_logical_type;
+use crate::errors::{ErrorCollector, OnError, RecordError};
+use crate::gen::{parse_message, MessageLayout, ParserState};
+use crate::index_cache::{Chunk, IndexBuilder, RecordOffset};
+use crate::io::{
+    default_max_open_files, expand_braces, is_stdin, parse, sniff_fixed_length_kind,
+    sniff_length_kind, Compression, ConcatReader, FileOrder, Format, JsonRecordsReader,
+    LengthDelimitedRecordsReader, LengthKind, OpenFilesLimit, Record, RecordTrailer, RecordsReader,
+    SingleRecordReader, TextRecordsReader, TrailingBytes,
+};
+use crate::read::{MyFlatVector, VectorAccessor};
+use crate::root_path::RootPath;
+use crate::scans::next_scan_id;
+use crate::stats::ScanStats;
+use crate::types::{
+    check_names, row_columns, ColumnNaming, DecodeOptions, FixedSignedness, InvalidUtf8,
+    NormalizeNames, SchemaEvolution, StructFieldOrder, TimestampUnit, UnknownEnum,
+    UnsupportedFields,
+};
+use crate::wire::{field_numbers, recognized_bytes};
+use crate::{index_cache, remote};
 use anyhow::{format_err, Context};
 use crossbeam::queue::ArrayQueue;
 use duckdb::vtab::{
@@ -9,7 +25,9 @@ use duckdb::vtab::{
     VTabLocalData,
 };
 use prost::Message;
-use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, ReflectMessage};
+use prost_reflect::{DescriptorPool, MessageDescriptor};
+use prost_types::{FileDescriptorProto, FileDescriptorSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::ffi::CString;
 use std::fs::File;
@@ -17,18 +35,395 @@ use std::io::Read;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::ptr::null_mut;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use strum::IntoEnumIterator;
+
+/// Added to errors about descriptor files which can't be read, which are
+/// usually not descriptor sets at all.
+const DESCRIPTOR_SET_HINT: &str = "ensure it was produced by `protoc --descriptor_set_out`";
+
+/// Reads descriptor files into a single encoded `FileDescriptorSet`, so a
+/// message can be declared in one file and its dependencies in another.
+/// Files found in several descriptor files are kept once, as long as they're
+/// the same everywhere.
+fn read_descriptors(paths: &[String]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut files: Vec<(FileDescriptorProto, &String)> = vec![];
+
+    for path in paths {
+        let mut buffer = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut buffer))
+            .with_context(|| format_err!("{}", path))?;
+
+        // an empty buffer decodes as a set without files, which would only
+        // fail later as a missing `message_type`
+        if buffer.is_empty() {
+            return Err(format_err!(
+                "{}: the file is empty, {}",
+                path,
+                DESCRIPTOR_SET_HINT
+            ));
+        }
+
+        let set = FileDescriptorSet::decode(buffer.as_slice()).map_err(|err| {
+            format_err!(
+                "{}: failed to parse `descriptors` as a FileDescriptorSet: {}; {}",
+                path,
+                err,
+                DESCRIPTOR_SET_HINT
+            )
+        })?;
+
+        for file in set.file {
+            match files.iter().find(|(it, _)| it.name == file.name) {
+                Some((existing, _)) if *existing == file => {}
+                Some((_, existing_path)) => {
+                    return Err(format_err!(
+                        "`{}` is declared differently in {} and {}",
+                        file.name(),
+                        existing_path,
+                        path
+                    ))
+                }
+                None => files.push((file, path)),
+            }
+        }
+    }
+
+    let set = FileDescriptorSet {
+        file: files.into_iter().map(|(file, _)| file).collect(),
+    };
+
+    // surfaces conflicting declarations across files, like a message
+    // declared twice
+    DescriptorPool::from_file_descriptor_set(set.clone())?;
+
+    Ok(set.encode_to_vec())
+}
+
+/// Compiles `.proto` files into an encoded `FileDescriptorSet`, like the
+/// one `descriptors` points to. Imports are looked up in `import_paths`, or
+/// the directories of the files when there are none, and the well known
+/// types are built in.
+fn compile_proto_files(
+    proto_files: &[String],
+    import_paths: &[String],
+) -> Result<Vec<u8>, anyhow::Error> {
+    let import_paths = if import_paths.is_empty() {
+        proto_files
+            .iter()
+            .map(|it| match Path::new(it).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            })
+            .collect::<Vec<_>>()
+    } else {
+        import_paths.iter().map(PathBuf::from).collect()
+    };
+
+    let mut compiler = protox::Compiler::new(import_paths)?;
+    compiler.include_imports(true);
+    compiler.open_files(proto_files)?;
+
+    Ok(compiler.encode_file_descriptor_set())
+}
+
+/// Parses a `delimiter`, either a single kind or `ext:` followed by
+/// `extension=kind` pairs and a `*=kind` pair for other files.
+fn parse_delimiter(
+    value: &str,
+) -> Result<(LengthKind, HashMap<String, LengthKind>), anyhow::Error> {
+    let Some(pairs) = value.strip_prefix("ext:") else {
+        return Ok((parse::<LengthKind>(value)?, HashMap::new()));
+    };
+
+    let mut default = None;
+    let mut kinds = HashMap::new();
+    for pair in pairs
+        .split(',')
+        .map(|it| it.trim())
+        .filter(|it| !it.is_empty())
+    {
+        let (extension, kind) = pair
+            .split_once('=')
+            .ok_or_else(|| format_err!("expected `extension=kind`, got: {}", pair))?;
+        let extension = extension.trim().trim_start_matches('.');
+        let kind = parse::<LengthKind>(kind.trim())
+            .with_context(|| format_err!("extension `{}`", extension))?;
+
+        match extension {
+            "*" => default = Some(kind),
+            extension => {
+                kinds.insert(extension.to_string(), kind);
+            }
+        }
+    }
+
+    let default =
+        default.ok_or_else(|| format_err!("expected a `*=kind` pair for the other extensions"))?;
+
+    Ok((default, kinds))
+}
+
+/// The message type read when `message_type` is left out, which is only
+/// allowed when the descriptors hold a single message.
+fn only_message_name(descriptor_pool: &DescriptorPool) -> Result<String, anyhow::Error> {
+    match message_names(descriptor_pool).as_slice() {
+        [name] => Ok(name.clone()),
+        [] => Err(format_err!(
+            "missing parameter `message_type`, the descriptors hold no messages"
+        )),
+        names => Err(format_err!(
+            "missing parameter `message_type`, expected one of: {}",
+            names.join(", ")
+        )),
+    }
+}
+
+/// Descriptor of the `message_type` parameter.
+pub fn message_descriptor(
+    bind: &BindInfo,
+    descriptor_pool: &DescriptorPool,
+) -> Result<MessageDescriptor, anyhow::Error> {
+    let message_name = match bind.get_named_parameter("message_type") {
+        Some(value) => value.to_string(),
+        None => only_message_name(descriptor_pool)?,
+    };
+
+    descriptor_pool
+        .get_message_by_name(&message_name)
+        .ok_or_else(|| {
+            format_err!(
+                "message type `{}` not found in `descriptors`, expected one of: {}",
+                message_name,
+                message_names(descriptor_pool).join(", ")
+            )
+        })
+}
+
+/// The sorted full names of the messages in the descriptors, leaving out the
+/// generated map entry messages.
+fn message_names(descriptor_pool: &DescriptorPool) -> Vec<String> {
+    let mut names = descriptor_pool
+        .all_messages()
+        .filter(|it| !it.is_map_entry())
+        .map(|it| it.full_name().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+
+    names
+}
+
+/// Reads the descriptors given by either the `descriptors` or `proto_files`
+/// parameter into a single encoded `FileDescriptorSet`.
+pub fn descriptor_bytes(bind: &BindInfo) -> Result<Vec<u8>, anyhow::Error> {
+    let descriptors = get_list_parameter(bind, "descriptors");
+    let proto_files = get_list_parameter(bind, "proto_files");
+
+    match (descriptors.is_empty(), proto_files.is_empty()) {
+        (false, false) => Err(format_err!(
+            "only one of `descriptors` and `proto_files` can be given"
+        )),
+        (false, true) => {
+            read_descriptors(&descriptors).with_context(|| format_err!("field `descriptors`"))
+        }
+        (true, false) => {
+            compile_proto_files(&proto_files, &get_list_parameter(bind, "import_paths"))
+                .with_context(|| format_err!("field `proto_files`"))
+        }
+        (true, true) => Err(format_err!("missing parameter `descriptors`")),
+    }
+}
+
+/// Reads the parameters affecting how fields are read into columns.
+pub fn decode_options(bind: &BindInfo) -> Result<DecodeOptions, anyhow::Error> {
+    let schema_evolution = get_enum_parameter::<SchemaEvolution>(bind, "schema_evolution")?
+        .unwrap_or(SchemaEvolution::Strict);
+
+    let unknown_enum = get_enum_parameter::<UnknownEnum>(bind, "unknown_enum")?
+        .unwrap_or(schema_evolution.unknown_enum());
+
+    let invalid_utf8 = get_enum_parameter::<InvalidUtf8>(bind, "invalid_utf8")?
+        .unwrap_or(schema_evolution.invalid_utf8());
+
+    let unsupported_fields = get_enum_parameter::<UnsupportedFields>(bind, "unsupported_fields")?
+        .unwrap_or(schema_evolution.unsupported_fields());
+
+    let decimal_fields = get_map_parameter(bind, "decimal_fields")?
+        .into_iter()
+        .map(|(name, scale)| {
+            let scale = scale
+                .parse::<u8>()
+                .ok()
+                .filter(|it| *it <= 18)
+                .ok_or_else(|| {
+                    format_err!(
+                        "when parsing parameter decimal_fields: scale of `{}` must be between 0 and 18, got: {}",
+                        name,
+                        scale
+                    )
+                })?;
+
+            Ok((name, scale))
+        })
+        .collect::<Result<_, anyhow::Error>>()?;
+
+    let decimal_scale_option = bind
+        .get_named_parameter("decimal_scale_option")
+        .map(|value| {
+            u32::try_from(value.to_int64())
+                .ok()
+                .filter(|it| (1..=MAX_FIELD_NUMBER).contains(it))
+                .ok_or_else(|| format_err!("`decimal_scale_option` must be a field number"))
+        })
+        .transpose()?;
+
+    let any_types = get_list_parameter(bind, "any_types");
+
+    let repeated_messages_as_json = get_list_parameter(bind, "repeated_messages_as_json");
+    let date_fields = get_list_parameter(bind, "date_fields");
+    let time_fields = get_list_parameter(bind, "time_fields");
+    let columns = get_list_parameter(bind, "columns");
+
+    let enum_mappings = get_map_parameter(bind, "enum_mappings")?;
+    let decode_bytes_as = get_map_parameter(bind, "decode_bytes_as")?;
+
+    let fixed_signedness = get_map_parameter(bind, "fixed_signedness")?
+        .into_iter()
+        .map(|(name, signedness)| {
+            let signedness = parse::<FixedSignedness>(&signedness).map_err(|err| {
+                format_err!(
+                    "when parsing parameter fixed_signedness: `{}`: {}",
+                    name,
+                    err
+                )
+            })?;
+
+            Ok((name, signedness))
+        })
+        .collect::<Result<_, anyhow::Error>>()?;
+
+    let enum_as_int = get_bool_parameter(bind, "enum_as_int");
+    let int64_as_string = get_bool_parameter(bind, "int64_as_string");
+    let oneof_as_struct = get_bool_parameter(bind, "oneof_as_struct");
+    let field_numbers = get_bool_parameter(bind, "field_numbers");
+    let use_defaults = bind
+        .get_named_parameter("use_defaults")
+        .map_or(true, |it| it.to_int64() != 0);
+    let lenient = bind
+        .get_named_parameter("lenient")
+        .map_or(schema_evolution.lenient(), |it| it.to_int64() != 0);
+
+    let max_depth = match bind.get_named_parameter("max_depth") {
+        Some(value) => usize::try_from(value.to_int64())
+            .map_err(|_| format_err!("`max_depth` can't be negative"))?,
+        None => DEFAULT_MAX_DEPTH,
+    };
+
+    let column_naming =
+        get_enum_parameter::<ColumnNaming>(bind, "column_naming")?.unwrap_or(ColumnNaming::Proto);
+
+    let normalize_names = get_enum_parameter::<NormalizeNames>(bind, "normalize_names")?
+        .unwrap_or(NormalizeNames::None);
+
+    let timestamp_unit =
+        get_enum_parameter::<TimestampUnit>(bind, "timestamp_unit")?.unwrap_or(TimestampUnit::Us);
+
+    let struct_field_order = get_enum_parameter::<StructFieldOrder>(bind, "struct_field_order")?
+        .unwrap_or(StructFieldOrder::Declaration);
+
+    Ok(DecodeOptions {
+        unknown_enum,
+        struct_field_order,
+        any_types,
+        invalid_utf8,
+        unsupported_fields,
+        decimal_fields,
+        decimal_scale_option,
+        date_fields,
+        time_fields,
+        column_naming,
+        normalize_names,
+        field_numbers,
+        timestamp_unit,
+        repeated_messages_as_json,
+        enum_mappings,
+        decode_bytes_as,
+        fixed_signedness,
+        columns,
+        enum_as_int,
+        int64_as_string,
+        oneof_as_struct,
+        max_depth,
+        use_defaults,
+        lenient,
+    })
+}
+
+/// Number of records looked at by [`Parameters::check_schema`].
+const SCHEMA_SAMPLE_RECORDS: usize = 16;
 
 pub struct Parameters {
     pub files: String,
-    pub descriptor_bytes: Vec<u8>,
+    /// Decoded once when binding and shared by every thread, clones share
+    /// the same descriptors.
+    pub descriptor_pool: DescriptorPool,
     pub message_name: String,
+    pub root_path: Option<RootPath>,
+    /// Descriptor of the message each row is decoded as. This is the
+    /// `message_type` unless a `root_path` is given.
     pub shared_message_descriptor: MessageDescriptor,
+    pub format: Format,
+    /// Ignored when reading text format messages. With `extension_length_kinds`,
+    /// the kind of files whose extension isn't in it.
     pub length_kind: LengthKind,
+    /// Kind of files with each extension, from a `delimiter` of the form
+    /// `ext:bin=Varint,*=BigEndianFixed`.
+    pub extension_length_kinds: HashMap<String, LengthKind>,
+    /// New names of field columns, by the name they'd have otherwise. Fields
+    /// are still read by number.
+    pub renames: HashMap<String, String>,
     pub include_filename: bool,
     pub include_position: bool,
     pub include_size: bool,
+    pub include_presence: bool,
+    pub include_row_number: bool,
+    pub include_file_index: bool,
+    pub include_record_index: bool,
+    pub include_file_mtime: bool,
+    pub include_raw: bool,
+    pub validate_schema: bool,
+    /// Fail to bind when the first record of the first file has fields the
+    /// message type doesn't declare, see [`Parameters::check_strict_schema`].
+    pub strict_schema: bool,
+    pub size_includes_prefix: bool,
+    pub concat_files: bool,
+    /// Give no rows rather than failing when no files match `files`.
+    pub allow_empty: bool,
+    pub order: FileOrder,
+    /// Most rows given to duckdb by a single call of `func`, capped by the
+    /// capacity of the output chunk.
+    pub batch_size: Option<usize>,
+    pub compression: Compression,
+    pub mmap: bool,
+    /// Directory holding the record offsets of files, see
+    /// [`index_cache`].
+    pub index_cache: Option<PathBuf>,
+    pub max_open_files: usize,
+    pub on_error: OnError,
+    pub trailing_bytes: TrailingBytes,
+    pub record_trailer: RecordTrailer,
+    pub decode_options: DecodeOptions,
 }
 
+/// Levels of nested messages read as structs unless `max_depth` is given.
+const DEFAULT_MAX_DEPTH: usize = 32;
+
+/// Largest field number the protobuf encoding allows.
+const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+
 impl Parameters {
     pub fn from_bind_info(bind: &BindInfo) -> Result<Self, anyhow::Error> {
         let files = bind
@@ -36,73 +431,500 @@ impl Parameters {
             .ok_or_else(|| format_err!("missing argument `files`"))?
             .to_string();
 
-        let descriptor = bind
-            .get_named_parameter("descriptors")
-            .ok_or_else(|| format_err!("missing parameter `descriptor`"))?
-            .to_string();
+        let descriptor_pool = DescriptorPool::decode(descriptor_bytes(bind)?.as_slice())?;
 
-        let descriptor_bytes = (|| -> Result<Vec<u8>, anyhow::Error> {
-            let mut file = File::open(descriptor)?;
-            let mut buffer = Vec::new();
-            file.read_to_end(&mut buffer)?;
+        let message_descriptor = message_descriptor(bind, &descriptor_pool)?;
+        let message_name = message_descriptor.full_name().to_string();
 
-            Ok(buffer)
-        })()
-        .with_context(|| format_err!("field `descriptors`"))?;
+        let root_path = match (
+            bind.get_named_parameter("root_path"),
+            bind.get_named_parameter("root_message_path"),
+        ) {
+            (None, None) => None,
+            (Some(path), None) => Some(
+                RootPath::resolve(&message_descriptor, &path.to_string())
+                    .with_context(|| format_err!("field `root_path`"))?,
+            ),
+            (None, Some(path)) => Some(
+                RootPath::resolve_message(&message_descriptor, &path.to_string())
+                    .with_context(|| format_err!("field `root_message_path`"))?,
+            ),
+            (Some(..), Some(..)) => {
+                return Err(format_err!(
+                    "`root_path` and `root_message_path` can't be used together"
+                ))
+            }
+        };
 
-        let shared_descriptor_pool = DescriptorPool::decode(descriptor_bytes.as_slice())?;
+        let message_descriptor = match &root_path {
+            None => message_descriptor,
+            Some(root_path) => descriptor_pool
+                .get_message_by_name(root_path.message_name())
+                .unwrap(),
+        };
 
-        let message_name = bind
-            .get_named_parameter("message_type")
-            .ok_or_else(|| format_err!("missing parameter `message_type`"))?
-            .to_string();
+        let format = get_enum_parameter::<Format>(bind, "format")?.unwrap_or(Format::Binary);
+
+        let (length_kind, extension_length_kinds) =
+            match (bind.get_named_parameter("delimiter"), format) {
+                (Some(value), _) => parse_delimiter(&value.to_string())
+                    .map_err(|err| format_err!("when parsing parameter delimiter: {}", err))?,
+                // text format and JSON messages are found by their separators
+                // instead
+                (None, Format::Text | Format::Jsonl) => {
+                    (LengthKind::SingleMessagePerFile, HashMap::new())
+                }
+                (None, Format::Binary) => return Err(format_err!("missing parameter `delimiter`")),
+            };
+        let length_kinds = extension_length_kinds
+            .values()
+            .copied()
+            .chain([length_kind])
+            .collect::<Vec<_>>();
+
+        let include_filename = get_bool_parameter(bind, "filename");
+        let include_position = get_bool_parameter(bind, "position");
+        let include_size = get_bool_parameter(bind, "size");
+        let include_presence = get_bool_parameter(bind, "field_presence");
+        let include_row_number = get_bool_parameter(bind, "global_row_number");
+        let include_file_index = get_bool_parameter(bind, "file_index");
+        let include_record_index = get_bool_parameter(bind, "record_index");
+        let include_file_mtime = get_bool_parameter(bind, "file_mtime");
+        let renames = get_map_parameter(bind, "rename")?;
+        let include_raw = get_bool_parameter(bind, "raw");
+        let validate_schema = get_bool_parameter(bind, "validate_schema");
+        let strict_schema = get_bool_parameter(bind, "strict_schema");
+        let size_includes_prefix = get_bool_parameter(bind, "size_includes_prefix");
+        let concat_files = get_bool_parameter(bind, "concat_files");
+        let allow_empty = get_bool_parameter(bind, "allow_empty");
+
+        let order = get_enum_parameter::<FileOrder>(bind, "order")?.unwrap_or(FileOrder::Name);
+
+        let batch_size = bind
+            .get_named_parameter("batch_size")
+            .map(|value| {
+                usize::try_from(value.to_int64())
+                    .ok()
+                    .filter(|it| *it > 0)
+                    .ok_or_else(|| format_err!("`batch_size` must be positive"))
+            })
+            .transpose()?;
+
+        if concat_files
+            && format == Format::Binary
+            && matches!(length_kind, LengthKind::SingleMessagePerFile)
+        {
+            return Err(format_err!(
+                "`concat_files` can't be used with `SingleMessagePerFile` as records need a delimiter to be found across files"
+            ));
+        }
+
+        if concat_files && !extension_length_kinds.is_empty() {
+            return Err(format_err!(
+                "`concat_files` can't be used with a `delimiter` by extension as the files are read as a single stream"
+            ));
+        }
+
+        // `skip_malformed = true` is another way of writing `on_error = 'skip'`
+        let on_error = match (
+            get_enum_parameter::<OnError>(bind, "on_error")?,
+            get_bool_parameter(bind, "skip_malformed"),
+        ) {
+            (Some(OnError::Fail), true) => {
+                return Err(format_err!(
+                    "`skip_malformed` can't be used with `on_error = 'fail'`"
+                ))
+            }
+            (_, true) => OnError::Skip,
+            (on_error, false) => on_error.unwrap_or(OnError::Fail),
+        };
 
-        let message_descriptor = shared_descriptor_pool
-            .get_message_by_name(&message_name.as_str())
-            .ok_or_else(|| format_err!("message type not found in `descriptor`"))?;
+        let compression =
+            get_enum_parameter::<Compression>(bind, "compression")?.unwrap_or(Compression::None);
+
+        let mmap = get_bool_parameter(bind, "mmap");
+
+        let index_cache = bind
+            .get_named_parameter("index_cache")
+            .map(|it| PathBuf::from(it.to_string()));
+
+        if index_cache.is_some() {
+            // records are found at their offsets in the file as stored
+            if format != Format::Binary
+                || concat_files
+                || remote::is_url(&files)
+                || !matches!(compression, Compression::None)
+                || length_kinds.iter().any(|it| {
+                    matches!(
+                        it,
+                        LengthKind::SingleMessagePerFile
+                            | LengthKind::AutoEndianFixed
+                            | LengthKind::Auto
+                    )
+                })
+            {
+                return Err(format_err!(
+                    "`index_cache` only applies to uncompressed local files of length delimited records, with an explicit `delimiter` and without `concat_files`"
+                ));
+            }
+        }
 
-        let length_kind = bind
-            .get_named_parameter("delimiter")
-            .ok_or_else(|| format_err!("missing parameter `delimiter`"))?;
+        // standard input can only be read once, while guessing the delimiter
+        // reads the start of the stream twice
+        if is_stdin(&files)
+            && (index_cache.is_some()
+                || length_kinds
+                    .iter()
+                    .any(|it| matches!(it, LengthKind::AutoEndianFixed | LengthKind::Auto)))
+        {
+            return Err(format_err!(
+                "standard input can't be read with `index_cache` or a guessed `delimiter`"
+            ));
+        }
 
-        let length_kind = parse::<LengthKind>(&length_kind.to_string())
-            .map_err(|err| format_err!("when parsing parameter delimiter: {}", err))?;
+        if strict_schema && (format != Format::Binary || is_stdin(&files)) {
+            return Err(format_err!(
+                "`strict_schema` only applies to binary records read from files"
+            ));
+        }
 
-        let include_filename = bind
-            .get_named_parameter("filename")
-            .map(|value| value.to_int64() != 0)
-            .unwrap_or(false);
+        let max_open_files = match bind.get_named_parameter("max_open_files") {
+            Some(value) => usize::try_from(value.to_int64())
+                .ok()
+                .filter(|it| *it > 0)
+                .ok_or_else(|| format_err!("`max_open_files` must be positive"))?,
+            None => default_max_open_files(),
+        };
 
-        let include_position = bind
-            .get_named_parameter("position")
-            .map(|value| value.to_int64() != 0)
-            .unwrap_or(false);
+        let trailing_bytes = get_enum_parameter::<TrailingBytes>(bind, "trailing_bytes")?
+            .unwrap_or(TrailingBytes::Ignore);
 
-        let include_size = bind
-            .get_named_parameter("size")
-            .map(|value| value.to_int64() != 0)
-            .unwrap_or(false);
+        let record_trailer = get_enum_parameter::<RecordTrailer>(bind, "record_trailer")?
+            .unwrap_or(RecordTrailer::None);
+
+        if record_trailer != RecordTrailer::None
+            && (format != Format::Binary
+                || length_kinds
+                    .iter()
+                    .any(|it| matches!(it, LengthKind::SingleMessagePerFile)))
+        {
+            return Err(format_err!(
+                "`record_trailer` only applies to length delimited records"
+            ));
+        }
 
         Ok(Self {
             files,
-            descriptor_bytes,
+            descriptor_pool,
             message_name,
+            root_path,
             shared_message_descriptor: message_descriptor,
+            format,
             length_kind,
+            extension_length_kinds,
+            renames,
             include_filename,
             include_position,
             include_size,
+            include_presence,
+            include_row_number,
+            include_file_index,
+            include_record_index,
+            include_file_mtime,
+            include_raw,
+            validate_schema,
+            strict_schema,
+            size_includes_prefix,
+            concat_files,
+            allow_empty,
+            order,
+            batch_size,
+            compression,
+            mmap,
+            index_cache,
+            max_open_files,
+            on_error,
+            trailing_bytes,
+            record_trailer,
+            decode_options: decode_options(bind)?,
         })
     }
 
-    pub fn message_descriptor(&self) -> Result<MessageDescriptor, anyhow::Error> {
-        let descriptor_pool = DescriptorPool::decode(self.descriptor_bytes.as_slice())?;
+    pub fn message_descriptor(&self) -> MessageDescriptor {
+        self.shared_message_descriptor.clone()
+    }
 
-        let message_descriptor = descriptor_pool
+    /// Descriptor of the `message_type` each record is encoded as, before any
+    /// `root_path` is applied.
+    pub fn record_descriptor(&self) -> MessageDescriptor {
+        self.descriptor_pool
             .get_message_by_name(&self.message_name)
-            .unwrap();
+            .unwrap()
+    }
+
+    /// Files to read, each task is a list of files read as a single stream.
+    pub fn tasks(&self) -> Result<Vec<Vec<PathBuf>>, anyhow::Error> {
+        let mut tasks = vec![];
+        if is_stdin(&self.files) {
+            tasks.push(PathBuf::from(&self.files));
+        } else {
+            // a file matched by more than one alternative is read once
+            let mut seen = HashSet::new();
+            for pattern in expand_braces(&self.files) {
+                let paths = if remote::is_url(&pattern) {
+                    remote::glob(&pattern)?
+                } else {
+                    glob::glob(&pattern)?.collect::<Result<Vec<_>, _>>()?
+                };
+
+                tasks.extend(paths.into_iter().filter(|it| seen.insert(it.clone())));
+            }
+        }
+
+        if tasks.is_empty() {
+            if self.allow_empty {
+                return Ok(vec![]);
+            }
 
-        Ok(message_descriptor)
+            return Err(format_err!("no files matching glob found {}", self.files));
+        }
+
+        // sorted before numbering and concatenating files, so `file_index`
+        // doesn't depend on how the filesystem lists them
+        match self.order {
+            FileOrder::Name => tasks.sort(),
+            FileOrder::Mtime => tasks.sort_by_cached_key(|it| (file_mtime(it), it.clone())),
+            FileOrder::None => {}
+        }
+
+        Ok(if self.concat_files {
+            vec![tasks]
+        } else {
+            tasks.into_iter().map(|it| vec![it]).collect()
+        })
+    }
+
+    /// The kind of length prefix of the records of a file, picked by its
+    /// extension when `delimiter` maps extensions to kinds.
+    pub fn length_kind_of(&self, path: &Path) -> LengthKind {
+        path.extension()
+            .and_then(|it| it.to_str())
+            .and_then(|it| self.extension_length_kinds.get(it))
+            .copied()
+            .unwrap_or(self.length_kind)
+    }
+
+    /// Identifies how the records of a file are framed in the sidecar files
+    /// of `index_cache`, as indices can't be shared between framings.
+    pub fn framing(&self, path: &Path) -> String {
+        format!(
+            "{}/{}",
+            self.length_kind_of(path).as_ref(),
+            self.record_trailer.as_ref()
+        )
+    }
+
+    /// Opens the records of a task. `text_descriptor` is the descriptor of
+    /// [`Parameters::record_descriptor`] when reading text format or JSON
+    /// messages. Only the records of `chunk` are read when it's given.
+    pub fn open_records(
+        &self,
+        paths: Vec<PathBuf>,
+        chunk: Option<Chunk>,
+        open_files: &Arc<OpenFilesLimit>,
+        text_descriptor: Option<&MessageDescriptor>,
+    ) -> Result<RecordsReader, anyhow::Error> {
+        // a stream is reported by its first file
+        let path = paths[0].clone();
+
+        if let Some(chunk) = chunk {
+            let Some(length_kind) = self.length_kind_of(&path).delimited() else {
+                // rejected along with `index_cache`
+                unreachable!()
+            };
+            let stream = ConcatReader::range(
+                path.clone(),
+                chunk.position,
+                chunk.end,
+                self.mmap,
+                open_files,
+            )
+            .with_context(|| format_err!("{}", path.display()))?;
+
+            return Ok(RecordsReader::LengthDelimited(
+                LengthDelimitedRecordsReader::create(
+                    stream,
+                    length_kind,
+                    self.trailing_bytes,
+                    self.record_trailer,
+                    path,
+                )
+                .starting_at(chunk.position, chunk.index),
+            ));
+        }
+        let mut stream = ConcatReader::new(paths.clone(), self.compression, self.mmap, open_files);
+
+        if let Some(descriptor) = text_descriptor {
+            return Ok(match self.format {
+                Format::Jsonl => {
+                    RecordsReader::Json(JsonRecordsReader::create(stream, descriptor, path))
+                }
+                _ => RecordsReader::Text(TextRecordsReader::create(stream, descriptor, path)?),
+            });
+        }
+
+        let length_kind = match self.length_kind_of(&path) {
+            LengthKind::AutoEndianFixed => {
+                let file_len = ConcatReader::total_len(&paths, self.compression, open_files)?;
+                let length_kind = sniff_fixed_length_kind(stream, file_len)?;
+                stream = ConcatReader::new(paths, self.compression, self.mmap, open_files);
+
+                length_kind
+            }
+            LengthKind::Auto => {
+                let file_len = ConcatReader::total_len(&paths, self.compression, open_files)?;
+                let length_kind = sniff_length_kind(stream, file_len)?;
+                stream = ConcatReader::new(paths.clone(), self.compression, self.mmap, open_files);
+
+                match length_kind {
+                    Some(length_kind) => length_kind,
+                    // a single message can't span concatenated files
+                    None if paths.len() > 1 => {
+                        return Err(format_err!(
+                            "{}: couldn't detect the `delimiter` of the concatenated files",
+                            path.display()
+                        ))
+                    }
+                    None => {
+                        let mut bytes = Vec::new();
+                        stream.read_to_end(&mut bytes)?;
+
+                        return Ok(RecordsReader::Single(SingleRecordReader::create(
+                            bytes, path,
+                        )));
+                    }
+                }
+            }
+            LengthKind::SingleMessagePerFile => {
+                let mut bytes = Vec::new();
+                stream.read_to_end(&mut bytes)?;
+
+                return Ok(RecordsReader::Single(SingleRecordReader::create(
+                    bytes, path,
+                )));
+            }
+            length_kind => length_kind
+                .delimited()
+                .expect("only guessed delimiters and `SingleMessagePerFile` aren't delimited"),
+        };
+
+        Ok(RecordsReader::LengthDelimited(
+            LengthDelimitedRecordsReader::create(
+                stream,
+                length_kind,
+                self.trailing_bytes,
+                self.record_trailer,
+                path,
+            ),
+        ))
+    }
+
+    /// Gives the mismatch, as an error to list in `protobuf_errors()`, when
+    /// most of the bytes of the first records of the first file aren't
+    /// fields of the message type, see `validate_schema`. Unknown fields are
+    /// skipped when decoding, so reading with the wrong `message_type`
+    /// otherwise silently gives default values.
+    pub fn check_schema(&self) -> Result<Option<RecordError>, anyhow::Error> {
+        let descriptor = self.record_descriptor();
+        let Some(paths) = self.tasks()?.into_iter().next() else {
+            return Ok(None);
+        };
+        let path = paths[0].clone();
+
+        let open_files = OpenFilesLimit::new(self.max_open_files);
+        let mut records = self.open_records(paths, None, &open_files, None)?;
+
+        let mut recognized = 0;
+        let mut total = 0;
+        for _ in 0..SCHEMA_SAMPLE_RECORDS {
+            let Some(record) = records.try_get_next()? else {
+                break;
+            };
+
+            recognized += recognized_bytes(&descriptor, &record.bytes);
+            total += record.bytes.len();
+        }
+
+        if recognized * 2 >= total {
+            return Ok(None);
+        }
+
+        Ok(Some(RecordError {
+            filename: path.to_string_lossy().into_owned(),
+            position: 0,
+            message: format!(
+                "only {} of the first {} bytes of records are fields of `{}`, `message_type` may not match the data",
+                recognized,
+                total,
+                descriptor.full_name()
+            ),
+        }))
+    }
+
+    /// Fails when the first record of the first file has fields the message
+    /// type doesn't declare, which usually means the descriptors don't match
+    /// the data. Unlike `check_schema`, a single unknown field is enough.
+    pub fn check_strict_schema(&self) -> Result<(), anyhow::Error> {
+        let descriptor = self.record_descriptor();
+        let Some(paths) = self.tasks()?.into_iter().next() else {
+            return Ok(());
+        };
+        let path = paths[0].clone();
+
+        let open_files = OpenFilesLimit::new(self.max_open_files);
+        let mut records = self.open_records(paths, None, &open_files, None)?;
+        let Some(record) = records.try_get_next()? else {
+            return Ok(());
+        };
+
+        let mut unknown = field_numbers(&record.bytes)
+            .with_context(|| {
+                format_err!("{}: record at position {}", path.display(), record.position)
+            })?
+            .into_iter()
+            .filter(|number| descriptor.get_field(*number).is_none())
+            .collect::<Vec<_>>();
+        unknown.sort();
+
+        if !unknown.is_empty() {
+            return Err(format_err!(
+                "`strict_schema`: {}: the first record has fields {:?} which `{}` doesn't declare, the descriptors may not match the data",
+                path.display(),
+                unknown,
+                descriptor.full_name()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Columns following the message's fields, in order.
+    pub fn metadata_columns(&self) -> Vec<MetadataColumn> {
+        [
+            (self.include_filename, MetadataColumn::Filename),
+            (self.include_position, MetadataColumn::Position),
+            (self.include_size, MetadataColumn::Size),
+            (self.include_presence, MetadataColumn::Presence),
+            (self.include_row_number, MetadataColumn::RowNumber),
+            (self.include_file_index, MetadataColumn::FileIndex),
+            (self.include_record_index, MetadataColumn::RecordIndex),
+            (self.include_file_mtime, MetadataColumn::FileMtime),
+            (self.include_raw, MetadataColumn::Raw),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, column)| column)
+        .collect()
     }
 
     pub fn values() -> Vec<(String, LogicalType)> {
@@ -119,6 +941,14 @@ impl Parameters {
                 "descriptors".to_string(),
                 LogicalType::new(LogicalTypeId::Varchar),
             ),
+            (
+                "proto_files".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "import_paths".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
             (
                 "delimiter".to_string(),
                 LogicalType::new(LogicalTypeId::Varchar),
@@ -132,12 +962,330 @@ impl Parameters {
                 LogicalType::new(LogicalTypeId::Boolean),
             ),
             ("size".to_string(), LogicalType::new(LogicalTypeId::Boolean)),
+            (
+                "root_path".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "root_message_path".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "on_error".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "skip_malformed".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "field_presence".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "size_includes_prefix".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "trailing_bytes".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "record_trailer".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "unknown_enum".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "concat_files".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "allow_empty".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "order".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "batch_size".to_string(),
+                LogicalType::new(LogicalTypeId::Bigint),
+            ),
+            (
+                "struct_field_order".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "compression".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "any_types".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "schema_evolution".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "invalid_utf8".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "unsupported_fields".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "decimal_fields".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "column_naming".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "normalize_names".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "timestamp_unit".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "format".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "date_fields".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "time_fields".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "columns".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "rename".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "repeated_messages_as_json".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "max_open_files".to_string(),
+                LogicalType::new(LogicalTypeId::Bigint),
+            ),
+            ("mmap".to_string(), LogicalType::new(LogicalTypeId::Boolean)),
+            (
+                "index_cache".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "max_depth".to_string(),
+                LogicalType::new(LogicalTypeId::Bigint),
+            ),
+            (
+                "decimal_scale_option".to_string(),
+                LogicalType::new(LogicalTypeId::Bigint),
+            ),
+            (
+                "enum_mappings".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "decode_bytes_as".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "fixed_signedness".to_string(),
+                LogicalType::new(LogicalTypeId::Varchar),
+            ),
+            (
+                "validate_schema".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "strict_schema".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "enum_as_int".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "oneof_as_struct".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "field_numbers".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "use_defaults".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "lenient".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "int64_as_string".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "global_row_number".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "file_index".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "record_index".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            (
+                "file_mtime".to_string(),
+                LogicalType::new(LogicalTypeId::Boolean),
+            ),
+            ("raw".to_string(), LogicalType::new(LogicalTypeId::Boolean)),
         ]
     }
 }
+
+fn get_bool_parameter(bind: &BindInfo, name: &str) -> bool {
+    bind.get_named_parameter(name)
+        .map(|value| value.to_int64() != 0)
+        .unwrap_or(false)
+}
+
+/// Parses a parameter holding a comma separated list.
+fn get_list_parameter(bind: &BindInfo, name: &str) -> Vec<String> {
+    let Some(value) = bind.get_named_parameter(name) else {
+        return vec![];
+    };
+
+    value
+        .to_string()
+        .split(',')
+        .map(|it| it.trim().to_string())
+        .filter(|it| !it.is_empty())
+        .collect()
+}
+
+/// Parses a parameter holding comma separated `key=value` pairs.
+fn get_map_parameter(
+    bind: &BindInfo,
+    name: &str,
+) -> Result<HashMap<String, String>, anyhow::Error> {
+    let Some(value) = bind.get_named_parameter(name) else {
+        return Ok(HashMap::new());
+    };
+
+    value
+        .to_string()
+        .split(',')
+        .map(|it| it.trim())
+        .filter(|it| !it.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').ok_or_else(|| {
+                format_err!(
+                    "when parsing parameter {}: expected `key=value`, got: {}",
+                    name,
+                    pair
+                )
+            })?;
+
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+fn get_enum_parameter<T: std::str::FromStr<Err = impl Error> + IntoEnumIterator + AsRef<str>>(
+    bind: &BindInfo,
+    name: &str,
+) -> Result<Option<T>, anyhow::Error> {
+    bind.get_named_parameter(name)
+        .map(|value| parse::<T>(&value.to_string()))
+        .transpose()
+        .map_err(|err| format_err!("when parsing parameter {}: {}", name, err))
+}
+
+/// Columns describing a row rather than holding one of its fields.
+#[derive(Copy, Clone)]
+pub enum MetadataColumn {
+    Filename,
+    Position,
+    Size,
+    /// Names of the fields present in the record, in descriptor order.
+    Presence,
+    /// Position of the row among all the rows of the scan.
+    RowNumber,
+    /// Position of the file among the files matching `files`.
+    FileIndex,
+    /// Position of the record among the records of its stream.
+    RecordIndex,
+    /// Modification time of the file.
+    FileMtime,
+    /// Encoded message the row was decoded from.
+    Raw,
+}
+
+impl MetadataColumn {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetadataColumn::Filename => "filename",
+            MetadataColumn::Position => "position",
+            MetadataColumn::Size => "size",
+            MetadataColumn::Presence => "_presence",
+            MetadataColumn::RowNumber => "_row_number",
+            MetadataColumn::FileIndex => "file_index",
+            MetadataColumn::RecordIndex => "record_index",
+            MetadataColumn::FileMtime => "file_mtime",
+            MetadataColumn::Raw => "_raw",
+        }
+    }
+
+    pub fn logical_type(&self) -> LogicalType {
+        match self {
+            MetadataColumn::Filename => LogicalType::new(LogicalTypeId::Varchar),
+            MetadataColumn::Position
+            | MetadataColumn::Size
+            | MetadataColumn::RowNumber
+            | MetadataColumn::FileIndex
+            | MetadataColumn::RecordIndex => LogicalType::new(LogicalTypeId::UBigint),
+            MetadataColumn::FileMtime => LogicalType::new(LogicalTypeId::Timestamp),
+            MetadataColumn::Raw => LogicalType::new(LogicalTypeId::Blob),
+            MetadataColumn::Presence => {
+                LogicalType::list(&LogicalType::new(LogicalTypeId::Varchar))
+            }
+        }
+    }
+}
 pub struct GlobalState {
-    queue: ArrayQueue<PathBuf>,
+    queue: ArrayQueue<Task>,
     column_indices: Vec<duckdb::ffi::idx_t>,
+    errors: Arc<ErrorCollector>,
+    stats: Arc<ScanStats>,
+    open_files: Arc<OpenFilesLimit>,
+    /// The next `_row_number`, shared by all threads.
+    next_row_number: AtomicU64,
+}
+
+/// Files read as a single stream.
+struct Task {
+    /// Index of the first file among the files matching `files`.
+    file_index: u64,
+    paths: Vec<PathBuf>,
+    /// Part of the file read, when the file was split using its index.
+    chunk: Option<Chunk>,
 }
 
 impl GlobalState {
@@ -145,34 +1293,67 @@ impl GlobalState {
         params: &Parameters,
         column_indices: Vec<duckdb::ffi::idx_t>,
     ) -> Result<GlobalState, anyhow::Error> {
-        let tasks = {
-            let mut tasks = vec![];
-            let items = glob::glob(params.files.as_str())?;
-            for item in items {
-                let item = item?;
-                tasks.push(item);
+        let mut tasks = vec![];
+
+        // tasks hold a single file each unless `concat_files` is set, in
+        // which case there's a single task starting at the first file
+        for (file_index, paths) in params.tasks()?.into_iter().enumerate() {
+            let file_index = file_index as u64;
+
+            let offsets = params
+                .index_cache
+                .as_ref()
+                .and_then(|dir| index_cache::load(dir, &paths[0], &params.framing(&paths[0])));
+
+            match offsets {
+                // files which were indexed are split between threads
+                Some(offsets) => {
+                    for chunk in index_cache::chunks(&offsets) {
+                        tasks.push(Task {
+                            file_index,
+                            paths: paths.clone(),
+                            chunk: Some(chunk),
+                        });
+                    }
+                }
+                None => tasks.push(Task {
+                    file_index,
+                    paths,
+                    chunk: None,
+                }),
             }
-
-            tasks
-        };
-
-        if tasks.is_empty() {
-            return Err(format_err!("no files matching glob found {}", params.files));
         }
 
         let queue = {
-            let queue = ArrayQueue::new(tasks.len());
-
-            for item in tasks {
-                queue.push(item).unwrap();
+            // a queue can't be empty, see `allow_empty`
+            let queue = ArrayQueue::new(tasks.len().max(1));
+            for task in tasks {
+                queue.push(task).unwrap();
             }
 
             queue
         };
 
+        // every scan starts a collector, so a scan failing on errors rather
+        // than skipping them doesn't leave the previous scan's errors behind
+        let scan_id = next_scan_id();
+        let errors = ErrorCollector::start_scan(scan_id);
+
+        // the records looked at on standard input wouldn't be read again
+        if params.validate_schema && params.format == Format::Binary && !is_stdin(&params.files) {
+            // the scan reports files which can't be read
+            if let Ok(Some(mismatch)) = params.check_schema() {
+                errors.push(mismatch);
+            }
+        }
+
         Ok(GlobalState {
             queue,
             column_indices,
+            errors,
+            stats: ScanStats::start_scan(scan_id, &params.files),
+            open_files: OpenFilesLimit::new(params.max_open_files),
+            next_row_number: AtomicU64::new(0),
         })
     }
 }
@@ -220,23 +1401,50 @@ impl ProtobufVTab {
 
         let params = Parameters::from_bind_info(bind)?;
 
-        for field_descriptor in params.shared_message_descriptor.fields() {
-            bind.add_result_column(
-                field_descriptor.name().as_ref(),
-                into_logical_type(&field_descriptor)?,
-            );
+        if params.strict_schema {
+            params.check_strict_schema()?;
         }
 
-        if params.include_filename {
-            bind.add_result_column("filename", LogicalType::new(LogicalTypeId::Varchar));
+        let mut columns = vec![];
+
+        let fields = row_columns(&params.shared_message_descriptor, &params.decode_options)?;
+        for name in params.renames.keys() {
+            if !fields
+                .iter()
+                .any(|column| column.name(&params.decode_options) == *name)
+            {
+                return Err(format_err!(
+                    "`rename`: `{}` isn't a column of `{}`",
+                    name,
+                    params.shared_message_descriptor.full_name()
+                ));
+            }
+        }
+
+        for column in fields {
+            let name = column.name(&params.decode_options);
+            columns.push((
+                params
+                    .renames
+                    .get(name.as_ref())
+                    .cloned()
+                    .unwrap_or_else(|| name.into_owned()),
+                column.logical_type(&params.decode_options)?,
+            ));
         }
 
-        if params.include_position {
-            bind.add_result_column("position", LogicalType::new(LogicalTypeId::UBigint));
+        for metadata_column in params.metadata_columns() {
+            columns.push((
+                metadata_column.name().to_string(),
+                metadata_column.logical_type(),
+            ));
         }
 
-        if params.include_size {
-            bind.add_result_column("size", LogicalType::new(LogicalTypeId::UBigint));
+        check_names(columns.iter().map(|(name, _)| name.as_str()))
+            .with_context(|| format_err!("result columns"))?;
+
+        for (name, logical_type) in columns {
+            bind.add_result_column(&name, logical_type);
         }
 
         data.assign(params);
@@ -255,7 +1463,7 @@ impl ProtobufVTab {
         let column_indices = init_info.get_column_indices();
 
         let new_global_state = GlobalState::new(bind_data, column_indices)?;
-        init_info.set_max_threads(new_global_state.queue.len() as _);
+        init_info.set_max_threads(new_global_state.queue.len().max(1) as _);
         data.assign(new_global_state);
 
         Ok(())
@@ -269,136 +1477,232 @@ impl ProtobufVTab {
 
         let parameters: &Parameters = bind_data.deref();
 
-        let local_descriptor = local_init_data.local_descriptor.clone();
+        let layout = local_init_data.layout.clone();
 
         let mut state_container = StateContainer {
             local_state: local_init_data,
             global_state: init_data,
             parameters,
+            open_next_stream: true,
         };
 
         let available_chunk_size = output.flat_vector(0).capacity();
+        // rows past the batch are read by the next call, from the same stream
+        let batch_size = parameters
+            .batch_size
+            .map_or(available_chunk_size, |it| it.min(available_chunk_size));
         let mut items = 0;
 
-        let mut column_information = Default::default();
-
-        let message = {
-            let message = DynamicMessage::new(local_descriptor.clone());
-            let fields: Vec<_> = local_descriptor.fields().collect();
-
-            let message = FilteredDynamicMessage::new(
-                message,
-                init_data
-                    .column_indices
-                    .iter()
-                    .filter_map(|it| {
-                        let it = *it as usize;
-                        if it >= fields.len() {
-                            return None;
-                        }
-
-                        Some(fields[it].number())
-                    })
-                    .collect(),
-            );
+        // list offsets index this chunk's child vectors, see `ParserState`
+        let mut parser_state = ParserState::default();
+        let metadata_columns = parameters.metadata_columns();
+        let mut presence_offset = 0;
+        let fields_len = row_columns(layout.descriptor(), &parameters.decode_options)?.len();
+        let include_presence = init_data.column_indices.iter().any(|it| {
+            (*it as usize)
+                .checked_sub(fields_len)
+                .and_then(|it| metadata_columns.get(it))
+                .map_or(false, |it| matches!(it, MetadataColumn::Presence))
+        });
 
-            message
-        };
+        while items < batch_size {
+            // a chunk ends along with its stream instead of opening the next
+            // file, so once a LIMIT is reached and duckdb stops asking for
+            // chunks, no file is opened only to be dropped
+            state_container.open_next_stream = items == 0;
 
-        for output_row_idx in 0..available_chunk_size {
+            let output_row_idx = items;
             let StateContainerValue {
-                path_reference,
+                path,
                 size,
                 bytes,
                 position,
-            } = match state_container.next_message()? {
+                file_index,
+                record_index,
+                file_mtime,
+            } = match state_container.next_row()? {
                 None => break,
                 Some(message_info) => message_info,
             };
 
-            let mut message = message.clone();
-            message.merge(bytes.as_slice())?;
-            let message = message.into();
+            // a skipped record may fail after pushing list elements, which
+            // are dropped by rolling back to before it
+            let checkpoint = match parameters.on_error {
+                OnError::Fail => None,
+                OnError::Skip => Some(parser_state.checkpoint()),
+            };
 
-            write_to_output(
-                &init_data.column_indices,
-                &mut column_information,
-                &message,
+            let decoded = parse_message(
+                &mut parser_state,
+                &layout,
+                bytes.as_slice(),
                 output,
                 available_chunk_size,
                 output_row_idx,
-            )?;
-
-            let mut field_offset = message.descriptor().fields().len();
-
-            if parameters.include_filename {
-                if let Some((field_offset, _)) = init_data
-                    .column_indices
-                    .iter()
-                    .enumerate()
-                    .find(|(_, it)| (**it as usize) == (field_offset))
-                {
-                    let it = (|| -> Option<CString> {
-                        let value = CString::new(path_reference.path().to_str()?).ok()?;
-                        Some(value)
-                    })();
+            )
+            .and_then(|()| {
+                include_presence
+                    .then(|| field_numbers(bytes.as_slice()))
+                    .transpose()
+            });
+
+            let present = match decoded {
+                Ok(present) => present.unwrap_or_default(),
+                Err(err) => match checkpoint {
+                    None => {
+                        return Err(err.context(format_err!(
+                            "{}: record at position {}",
+                            path.display(),
+                            position
+                        )))
+                    }
+                    Some(checkpoint) => {
+                        parser_state.rollback(checkpoint);
+                        init_data.errors.push(RecordError {
+                            filename: path.to_string_lossy().into_owned(),
+                            position,
+                            message: format_error_with_causes(err).to_string(),
+                        });
+
+                        continue;
+                    }
+                },
+            };
 
-                    let column = output.get_vector(field_offset);
+            for (output_idx, column_idx) in init_data.column_indices.iter().enumerate() {
+                let Some(metadata_column) = (*column_idx as usize)
+                    .checked_sub(fields_len)
+                    .and_then(|it| metadata_columns.get(it))
+                else {
+                    continue;
+                };
 
-                    match it {
+                let column = output.get_vector(output_idx);
+
+                match metadata_column {
+                    MetadataColumn::Filename => {
+                        let it = (|| -> Option<CString> {
+                            let value = CString::new(path.to_str()?).ok()?;
+                            Some(value)
+                        })();
+
+                        match it {
+                            None => unsafe {
+                                let validity = duckdb::ffi::duckdb_vector_get_validity(column);
+                                duckdb::ffi::duckdb_validity_set_row_invalid(
+                                    validity,
+                                    output_row_idx as _,
+                                );
+                            },
+                            Some(value) => unsafe {
+                                duckdb::ffi::duckdb_vector_assign_string_element(
+                                    column,
+                                    output_row_idx as _,
+                                    value.as_ptr(),
+                                )
+                            },
+                        }
+                    }
+                    MetadataColumn::Position => {
+                        let mut vector = unsafe {
+                            MyFlatVector::<u64>::with_capacity(column, available_chunk_size)
+                        };
+                        vector.as_mut_slice()[output_row_idx] = position as _;
+                    }
+                    MetadataColumn::RowNumber => {
+                        let mut vector = unsafe {
+                            MyFlatVector::<u64>::with_capacity(column, available_chunk_size)
+                        };
+                        vector.as_mut_slice()[output_row_idx] =
+                            init_data.next_row_number.fetch_add(1, Ordering::Relaxed);
+                    }
+                    MetadataColumn::Size => {
+                        let mut vector = unsafe {
+                            MyFlatVector::<u64>::with_capacity(column, available_chunk_size)
+                        };
+                        vector.as_mut_slice()[output_row_idx] = size as _;
+                    }
+                    MetadataColumn::FileIndex => {
+                        let mut vector = unsafe {
+                            MyFlatVector::<u64>::with_capacity(column, available_chunk_size)
+                        };
+                        vector.as_mut_slice()[output_row_idx] = file_index;
+                    }
+                    MetadataColumn::RecordIndex => {
+                        let mut vector = unsafe {
+                            MyFlatVector::<u64>::with_capacity(column, available_chunk_size)
+                        };
+                        vector.as_mut_slice()[output_row_idx] = record_index;
+                    }
+                    MetadataColumn::FileMtime => match file_mtime {
                         None => unsafe {
+                            duckdb::ffi::duckdb_vector_ensure_validity_writable(column);
                             let validity = duckdb::ffi::duckdb_vector_get_validity(column);
                             duckdb::ffi::duckdb_validity_set_row_invalid(
                                 validity,
                                 output_row_idx as _,
                             );
                         },
-                        Some(value) => unsafe {
-                            duckdb::ffi::duckdb_vector_assign_string_element(
+                        Some(micros) => {
+                            let mut vector = unsafe {
+                                MyFlatVector::<i64>::with_capacity(column, available_chunk_size)
+                            };
+                            vector.as_mut_slice()[output_row_idx] = micros;
+                        }
+                    },
+                    MetadataColumn::Raw => unsafe {
+                        duckdb::ffi::duckdb_vector_assign_string_element_len(
+                            column,
+                            output_row_idx as _,
+                            bytes.as_ptr().cast(),
+                            bytes.len() as _,
+                        )
+                    },
+                    MetadataColumn::Presence => {
+                        let names = layout
+                            .descriptor()
+                            .fields()
+                            .filter(|field| present.contains(&field.number()))
+                            .map(|field| {
+                                CString::new(parameters.decode_options.column_name(&field).as_ref())
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+
+                        let mut list_entries = unsafe {
+                            MyFlatVector::<duckdb::ffi::duckdb_list_entry>::with_capacity(
                                 column,
-                                output_row_idx as _,
-                                value.as_ptr(),
+                                available_chunk_size,
                             )
-                        },
-                    }
-                }
+                        };
+                        let list_entry = &mut list_entries.as_mut_slice()[output_row_idx];
+                        list_entry.offset = presence_offset;
+                        list_entry.length = names.len() as u64;
 
-                field_offset += 1;
-            }
+                        presence_offset += names.len() as u64;
 
-            if parameters.include_position {
-                if let Some((field_offset, _)) = init_data
-                    .column_indices
-                    .iter()
-                    .enumerate()
-                    .find(|(_, it)| (**it as usize) == (field_offset))
-                {
-                    let column = output.get_vector(field_offset);
-                    let mut vector =
-                        unsafe { MyFlatVector::<u64>::with_capacity(column, available_chunk_size) };
-                    vector.as_mut_slice()[output_row_idx] = position as _;
-                }
-
-                field_offset += 1;
-            }
+                        unsafe {
+                            duckdb::ffi::duckdb_list_vector_reserve(column, presence_offset);
+                            duckdb::ffi::duckdb_list_vector_set_size(column, presence_offset);
+                        }
 
-            if parameters.include_size {
-                if let Some((field_offset, _)) = init_data
-                    .column_indices
-                    .iter()
-                    .enumerate()
-                    .find(|(_, it)| (**it as usize) == (field_offset))
-                {
-                    let column = output.get_vector(field_offset);
-                    let mut vector =
-                        unsafe { MyFlatVector::<u64>::with_capacity(column, available_chunk_size) };
-                    vector.as_mut_slice()[output_row_idx] = size as _;
+                        let child_vector =
+                            unsafe { duckdb::ffi::duckdb_list_vector_get_child(column) };
+
+                        for (idx, name) in names.iter().enumerate() {
+                            unsafe {
+                                duckdb::ffi::duckdb_vector_assign_string_element(
+                                    child_vector,
+                                    list_entry.offset + idx as u64,
+                                    name.as_ptr(),
+                                )
+                            };
+                        }
+                    }
                 }
-
-                field_offset += 1;
             }
 
             items += 1;
+            state_container.recycle(bytes);
         }
 
         output.set_len(items);
@@ -411,90 +1715,267 @@ struct StateContainer<'a> {
     local_state: &'a mut LocalState,
     global_state: &'a GlobalState,
     parameters: &'a Parameters,
-}
-
-enum PathReference<'a> {
-    Borrowed(&'a Path),
-    Owned(PathBuf),
-}
-
-impl<'a> PathReference<'a> {
-    pub fn path(&self) -> &Path {
-        match self {
-            PathReference::Borrowed(it) => *it,
-            PathReference::Owned(it) => it.as_path(),
-        }
-    }
+    /// Whether another stream is opened once the current one is exhausted,
+    /// rather than ending the chunk.
+    open_next_stream: bool,
 }
 
 struct StateContainerValue<'a> {
-    path_reference: PathReference<'a>,
+    path: &'a Path,
     bytes: Vec<u8>,
     size: usize,
     position: u64,
+    file_index: u64,
+    record_index: u64,
+    /// Microseconds since the epoch, `None` when it isn't known.
+    file_mtime: Option<i64>,
 }
 
 impl StateContainer<'_> {
+    /// Gives the bytes of a row back once it's written, see
+    /// [`RecordsReader::recycle`].
+    fn recycle(&mut self, bytes: Vec<u8>) {
+        if let Some(current) = &mut self.local_state.current {
+            current.recycle(bytes);
+        }
+    }
+
+    fn next_row(&mut self) -> Result<Option<StateContainerValue>, anyhow::Error> {
+        let parameters = self.parameters;
+        let Some(root_path) = &parameters.root_path else {
+            return self.next_message();
+        };
+
+        while self
+            .local_state
+            .pending
+            .as_ref()
+            .map_or(true, |it| it.rows.is_empty())
+        {
+            let Some(value) = self.next_message()? else {
+                return Ok(None);
+            };
+
+            let rows = match root_path.extract(&value.bytes) {
+                Ok(rows) => rows,
+                Err(err) => match parameters.on_error {
+                    OnError::Fail => {
+                        return Err(err.context(format_err!(
+                            "{}: record at position {}",
+                            value.path.display(),
+                            value.position
+                        )))
+                    }
+                    OnError::Skip => {
+                        let error = RecordError {
+                            filename: value.path.to_string_lossy().into_owned(),
+                            position: value.position,
+                            message: format_error_with_causes(err).to_string(),
+                        };
+                        self.global_state.errors.push(error);
+
+                        continue;
+                    }
+                },
+            };
+
+            self.local_state.pending = Some(PendingRows {
+                path: value.path.to_path_buf(),
+                position: value.position,
+                size: value.size,
+                file_index: value.file_index,
+                record_index: value.record_index,
+                file_mtime: value.file_mtime,
+                rows: rows.into(),
+            });
+        }
+
+        let pending = self.local_state.pending.as_mut().unwrap();
+        let bytes = pending.rows.pop_front().unwrap();
+
+        Ok(Some(StateContainerValue {
+            path: pending.path.as_path(),
+            bytes,
+            size: pending.size,
+            position: pending.position,
+            file_index: pending.file_index,
+            record_index: pending.record_index,
+            file_mtime: pending.file_mtime,
+        }))
+    }
+
     fn next_message(&mut self) -> Result<Option<StateContainerValue>, anyhow::Error> {
-        let mut value = match self.local_state.current.take() {
-            Some(it) => it,
-            None => {
-                let Some(next_file_path) = self.global_state.queue.pop() else {
-                    return Ok(None);
-                };
+        loop {
+            let mut value = match self.local_state.current.take() {
+                Some(it) => it,
+                None if !self.open_next_stream => return Ok(None),
+                None => {
+                    let Some(Task {
+                        file_index,
+                        paths: next_file_paths,
+                        chunk,
+                    }) = self.global_state.queue.pop()
+                    else {
+                        return Ok(None);
+                    };
+
+                    self.local_state.file_index = file_index;
+                    // looked up once per stream rather than for every record
+                    if self.parameters.include_file_mtime {
+                        self.local_state.file_mtime = file_mtime(&next_file_paths[0]);
+                    }
 
-                let mut next_file = File::open(&next_file_path)?;
-                match self.parameters.length_kind {
-                    LengthKind::BigEndianFixed => LengthDelimitedRecordsReader::create(
-                        next_file,
-                        DelimitedLengthKind::BigEndianFixed,
-                        next_file_path,
-                    ),
-                    LengthKind::Varint => LengthDelimitedRecordsReader::create(
-                        next_file,
-                        DelimitedLengthKind::Varint,
-                        next_file_path,
-                    ),
-                    LengthKind::SingleMessagePerFile => {
-                        let mut bytes = Vec::new();
-                        next_file.read_to_end(&mut bytes)?;
-                        let size = bytes.len();
-                        return Ok(Some(StateContainerValue {
-                            bytes,
-                            path_reference: PathReference::Owned(next_file_path),
+                    // files which weren't split using an index are indexed
+                    // as they're read
+                    self.local_state.index_builder = match (&self.parameters.index_cache, chunk) {
+                        (Some(dir), None) => IndexBuilder::start(
+                            dir,
+                            &next_file_paths[0],
+                            &self.parameters.framing(&next_file_paths[0]),
+                        )
+                        .ok(),
+                        _ => None,
+                    };
+
+                    self.parameters.open_records(
+                        next_file_paths,
+                        chunk,
+                        &self.global_state.open_files,
+                        self.local_state.text_descriptor.as_ref(),
+                    )?
+                }
+            };
+
+            // the current stream is exhausted, move on to the next one
+            let Some(Record {
+                position,
+                index,
+                size,
+                bytes: next_message,
+                prefix_size,
+                trailer_error,
+            }) = value.try_get_next()?
+            else {
+                if let Some((position, message)) = value.take_warning() {
+                    self.global_state.errors.push(RecordError {
+                        filename: value.path().to_string_lossy().into_owned(),
+                        position,
+                        message,
+                    });
+                }
+
+                if let Some(builder) = self.local_state.index_builder.take() {
+                    let sidecar = builder.sidecar().to_path_buf();
+                    if let Err(err) = builder.finish() {
+                        self.global_state.errors.push(RecordError {
+                            filename: value.path().to_string_lossy().into_owned(),
                             position: 0,
-                            size,
-                        }));
+                            message: format!(
+                                "failed to write index {}: {}",
+                                sidecar.display(),
+                                err
+                            ),
+                        });
+                    }
+                }
+
+                continue;
+            };
+
+            if let Some(builder) = &mut self.local_state.index_builder {
+                builder.push(RecordOffset { position, size });
+            }
+
+            self.global_state.stats.push_record(next_message.len());
+
+            if let Some(message) = trailer_error {
+                self.local_state.current = Some(value);
+                let path = self.local_state.current.as_ref().unwrap().path();
+
+                match self.parameters.on_error {
+                    OnError::Fail => {
+                        return Err(format_err!(
+                            "{}: record at position {}: {}",
+                            path.display(),
+                            position,
+                            message
+                        ))
+                    }
+                    OnError::Skip => {
+                        self.global_state.errors.push(RecordError {
+                            filename: path.to_string_lossy().into_owned(),
+                            position,
+                            message,
+                        });
+
+                        continue;
                     }
                 }
             }
-        };
 
-        let Some(Record {
-            position,
-            size,
-            bytes: next_message,
-        }) = value.try_get_next()?
-        else {
-            return Ok(None);
-        };
+            let size = if self.parameters.size_includes_prefix {
+                size + prefix_size as u64
+            } else {
+                size
+            };
 
-        self.local_state.current = Some(value);
-        Ok(Some(StateContainerValue {
-            path_reference: PathReference::Borrowed(
-                self.local_state.current.as_ref().unwrap().path(),
-            ),
-            bytes: next_message,
-            size: size as _,
-            position,
-        }))
+            self.local_state.current = Some(value);
+            return Ok(Some(StateContainerValue {
+                path: self.local_state.current.as_ref().unwrap().path(),
+                bytes: next_message,
+                size: size as _,
+                position,
+                file_index: self.local_state.file_index,
+                record_index: index,
+                file_mtime: self.local_state.file_mtime,
+            }));
+        }
     }
 }
 
+/// Modification time of a file in microseconds since the epoch, `None` when
+/// the platform or filesystem doesn't record it.
+fn file_mtime(path: &Path) -> Option<i64> {
+    if is_stdin(path) {
+        return None;
+    }
+
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+
+    Some(match modified.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_micros() as i64,
+        Err(err) => -(err.duration().as_micros() as i64),
+    })
+}
+
+/// Rows produced by a record when reading with a `root_path` which didn't fit
+/// into the current output chunk.
+struct PendingRows {
+    path: PathBuf,
+    position: u64,
+    size: usize,
+    file_index: u64,
+    record_index: u64,
+    file_mtime: Option<i64>,
+    rows: VecDeque<Vec<u8>>,
+}
+
 #[repr(C)]
 pub struct LocalState {
-    current: Option<LengthDelimitedRecordsReader>,
-    local_descriptor: MessageDescriptor,
+    current: Option<RecordsReader>,
+    /// Index of the first file of `current` among the files matching `files`.
+    file_index: u64,
+    /// Modification time of the first file of `current`, when `file_mtime`
+    /// is set.
+    file_mtime: Option<i64>,
+    /// Collects the record offsets of `current` when it's a whole file and
+    /// `index_cache` is set.
+    index_builder: Option<IndexBuilder>,
+    pending: Option<PendingRows>,
+    /// Set when reading text format or JSON messages, which are parsed
+    /// against it.
+    text_descriptor: Option<MessageDescriptor>,
+    layout: Arc<MessageLayout>,
 }
 
 impl VTabLocalData for ProtobufVTab {
@@ -505,14 +1986,28 @@ impl VTabLocalData for ProtobufVTab {
         data: *mut Self::LocalInitData,
     ) -> duckdb::Result<(), Box<dyn Error>> {
         let bind_data = unsafe { &*init_info.get_bind_data::<<Self as VTab>::BindData>() };
-        let local_descriptor = bind_data.message_descriptor()?;
+        let local_descriptor = bind_data.message_descriptor();
+        let layout = MessageLayout::for_columns(
+            &local_descriptor,
+            &init_info.get_column_indices(),
+            &bind_data.decode_options,
+        )?;
+        let text_descriptor = match bind_data.format {
+            Format::Binary => None,
+            Format::Text | Format::Jsonl => Some(bind_data.record_descriptor()),
+        };
 
         let data = unsafe { &mut *data };
         data.init();
 
         data.assign(LocalState {
             current: None,
-            local_descriptor,
+            file_index: 0,
+            file_mtime: None,
+            index_builder: None,
+            pending: None,
+            text_descriptor,
+            layout: Arc::new(layout),
         });
 
         Ok(())
@@ -563,7 +2058,7 @@ impl<T> Free for Handle<T> {
     }
 }
 
-fn format_error_with_causes(error: anyhow::Error) -> anyhow::Error {
+pub fn format_error_with_causes(error: anyhow::Error) -> anyhow::Error {
     format_err!(
         "{}",
         error
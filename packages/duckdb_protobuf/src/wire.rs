@@ -0,0 +1,107 @@
+use anyhow::bail;
+use prost::bytes::Buf;
+use prost::encoding::{decode_key, decode_varint, skip_field, DecodeContext, WireType};
+use prost_reflect::MessageDescriptor;
+use std::collections::HashSet;
+
+/// Returns the payloads of every length delimited or group occurrence of a
+/// field in an encoded message, in the order they appear.
+pub fn field_payloads(bytes: &[u8], field_number: u32) -> Result<Vec<&[u8]>, anyhow::Error> {
+    let mut buf = bytes;
+    let mut payloads = vec![];
+
+    while buf.has_remaining() {
+        let (tag, wire_type) = decode_key(&mut buf)?;
+        if tag == field_number && wire_type == WireType::StartGroup {
+            payloads.push(read_group(&mut buf, field_number)?);
+            continue;
+        }
+
+        if tag != field_number || wire_type != WireType::LengthDelimited {
+            skip_field(wire_type, tag, &mut buf, DecodeContext::default())?;
+            continue;
+        }
+
+        let len = decode_varint(&mut buf)? as usize;
+        if len > buf.remaining() {
+            bail!("field {} extends past the end of the message", field_number);
+        }
+
+        payloads.push(&buf[..len]);
+        buf.advance(len);
+    }
+
+    Ok(payloads)
+}
+
+/// Reads the fields of a group up to its end group tag, which are encoded
+/// like the fields of a message. `buf` starts after the start group tag.
+pub fn read_group<'a>(buf: &mut &'a [u8], field_number: u32) -> Result<&'a [u8], anyhow::Error> {
+    let group = *buf;
+
+    loop {
+        if !buf.has_remaining() {
+            bail!("group extends past the end of the message");
+        }
+
+        let end = group.len() - buf.remaining();
+        let (number, wire_type) = decode_key(buf)?;
+        if wire_type == WireType::EndGroup {
+            if number != field_number {
+                bail!("group ended by the end group tag of field {}", number);
+            }
+
+            return Ok(&group[..end]);
+        }
+
+        // nested groups are skipped up to their own end group tag
+        skip_field(wire_type, number, buf, DecodeContext::default())?;
+    }
+}
+
+/// Returns the numbers of the fields present in an encoded message without
+/// decoding their values.
+pub fn field_numbers(bytes: &[u8]) -> Result<HashSet<u32>, anyhow::Error> {
+    let mut buf = bytes;
+    let mut numbers = HashSet::new();
+
+    while buf.has_remaining() {
+        let (tag, wire_type) = decode_key(&mut buf)?;
+        skip_field(wire_type, tag, &mut buf, DecodeContext::default())?;
+        numbers.insert(tag);
+    }
+
+    Ok(numbers)
+}
+
+/// Returns the number of bytes of an encoded message taken up by fields the
+/// message type declares with a matching wire type, counting keys. Stops at
+/// the first malformed field, leaving the rest unrecognized.
+pub fn recognized_bytes(descriptor: &MessageDescriptor, bytes: &[u8]) -> usize {
+    let mut buf = bytes;
+    let mut recognized = 0;
+
+    while buf.has_remaining() {
+        let start = buf.remaining();
+        let Ok((tag, wire_type)) = decode_key(&mut buf) else {
+            break;
+        };
+
+        // packed repeated fields are length delimited
+        let declared = descriptor.get_field(tag).is_some_and(|field| {
+            field.kind().wire_type() == wire_type
+                || (field.is_packable() && wire_type == WireType::LengthDelimited)
+                || (field.is_group() && wire_type == WireType::StartGroup)
+        });
+
+        if skip_field(wire_type, tag, &mut buf, DecodeContext::default()).is_err() {
+            break;
+        }
+
+        if declared {
+            recognized += start - buf.remaining();
+        }
+    }
+
+    recognized
+}
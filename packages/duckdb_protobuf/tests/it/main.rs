@@ -1,12 +1,31 @@
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
+use std::process::{Command, Stdio};
 use std::sync::Once;
 
 use anyhow::Result;
+use duckdb::types::{TimeUnit, Value};
 use duckdb::{Config, Connection};
 use prost::Message;
 
+mod user {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/src/user.rs"));
+}
+
+#[allow(dead_code)]
+mod legacy {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/src/legacy.rs"));
+}
+
+#[allow(dead_code)]
+mod features {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/src/features.rs"
+    ));
+}
+
 static INIT: Once = Once::new();
 
 fn setup() {
@@ -17,7 +36,11 @@ fn setup() {
 }
 
 fn compile_protos() -> Result<(), Box<dyn std::error::Error>> {
-    let proto_path = "tests/protos/user.proto";
+    let proto_paths = [
+        "tests/protos/user.proto",
+        "tests/protos/features.proto",
+        "tests/protos/legacy.proto",
+    ];
     let descriptor_dir = "tests/generated";
     let out_dir = "tests/src";
 
@@ -27,17 +50,12 @@ fn compile_protos() -> Result<(), Box<dyn std::error::Error>> {
     prost_build::Config::new()
         .out_dir(out_dir)
         .file_descriptor_set_path("tests/generated/descriptor.pb")
-        .compile_protos(&[proto_path], &[Path::new("tests/protos")])?;
+        .compile_protos(&proto_paths, &[Path::new("tests/protos")])?;
 
     Ok(())
 }
 
 fn generate_test_data() -> Result<(), Box<dyn std::error::Error>> {
-    // Include the generated Rust code for the protobuf messages
-    mod user {
-        include!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/src/user.rs"));
-    }
-
     // Create some example User messages
     let users = [
         user::User {
@@ -122,3 +140,5374 @@ fn test_query_protobuf_data() -> Result<()> {
     assert_eq!(results[0].1, 1, "Expected first id to be 1");
     Ok(())
 }
+
+fn write_delimited(
+    path: &str,
+    messages: &[impl Message],
+    write_prefix: impl Fn(usize, &mut Vec<u8>),
+) -> Result<()> {
+    let path = Path::new(path);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+
+    let mut buf = Vec::new();
+    for message in messages {
+        write_prefix(message.encoded_len(), &mut buf);
+        message.encode(&mut buf)?;
+    }
+
+    File::create(path)?.write_all(&buf)?;
+
+    Ok(())
+}
+
+fn write_varint_delimited(path: &str, messages: &[impl Message]) -> Result<()> {
+    write_delimited(path, messages, |len, buf| {
+        prost::encoding::encode_varint(len as u64, buf)
+    })
+}
+
+fn users() -> Vec<user::User> {
+    ["Alice", "Bob", "Charlie"]
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| user::User {
+            name: name.to_string(),
+            id: idx as i32 + 1,
+        })
+        .collect()
+}
+
+fn query_users(conn: &Connection, files: &str, delimiter: &str) -> Result<Vec<(String, i32)>> {
+    let mut stmt = conn.prepare(&format!(
+        "
+            SELECT name, id FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = '{files}',
+                message_type = 'user.User',
+                delimiter = '{delimiter}'
+            );
+        "
+    ))?;
+
+    let results = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(results)
+}
+
+fn connection() -> Result<Connection> {
+    setup();
+
+    let config = Config::default().allow_unsigned_extensions()?;
+    let conn = Connection::open_in_memory_with_flags(config)?;
+    conn.execute("LOAD '../../target/release/protobuf.duckdb_extension'", [])?;
+
+    Ok(conn)
+}
+
+#[test]
+fn test_colliding_column_names_error() -> Result<()> {
+    let conn = connection()?;
+
+    let err = conn
+        .prepare(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/data/**/*.bin',
+                    message_type = 'features.Upload',
+                    delimiter = 'SingleMessagePerFile',
+                    filename = true
+                );
+            ",
+        )
+        .err()
+        .expect("expected bind to fail");
+
+    assert!(
+        err.to_string()
+            .contains("`Filename` and `filename` collide"),
+        "unexpected error: {err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_root_path() -> Result<()> {
+    let conn = connection()?;
+
+    let item = |name: &str, quantity: i64| features::Item {
+        name: name.to_string(),
+        quantity,
+    };
+
+    write_varint_delimited(
+        "tests/generated/root_path/envelopes.bin",
+        &[
+            features::Envelope {
+                request_id: "a".to_string(),
+                response: Some(features::Response {
+                    items: vec![item("apple", 1), item("banana", 2)],
+                }),
+            },
+            features::Envelope {
+                request_id: "b".to_string(),
+                response: None,
+            },
+            features::Envelope {
+                request_id: "c".to_string(),
+                response: Some(features::Response {
+                    items: vec![item("cherry", 3)],
+                }),
+            },
+        ],
+    )?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, quantity FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/root_path/envelopes.bin',
+                message_type = 'features.Envelope',
+                delimiter = 'Varint',
+                root_path = 'response.items'
+            );
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        results,
+        vec![
+            ("apple".to_string(), 1),
+            ("banana".to_string(), 2),
+            ("cherry".to_string(), 3),
+        ]
+    );
+
+    let err = conn
+        .prepare(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/root_path/envelopes.bin',
+                    message_type = 'features.Envelope',
+                    delimiter = 'Varint',
+                    root_path = 'response'
+                );
+            ",
+        )
+        .err()
+        .expect("expected bind to fail");
+
+    assert!(
+        err.to_string().contains("must be repeated"),
+        "unexpected error: {err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_root_message_path() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/root_message_path/envelopes.bin",
+        &[
+            features::Envelope {
+                request_id: "a".to_string(),
+                response: Some(features::Response {
+                    items: vec![features::Item {
+                        name: "apple".to_string(),
+                        quantity: 1,
+                    }],
+                }),
+            },
+            features::Envelope {
+                request_id: "b".to_string(),
+                response: None,
+            },
+        ],
+    )?;
+
+    let query = |select: &str| {
+        format!(
+            "
+                SELECT {select} FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/root_message_path/envelopes.bin',
+                    message_type = 'features.Envelope',
+                    delimiter = 'Varint',
+                    root_message_path = 'response'
+                );
+            "
+        )
+    };
+
+    // the response's fields are the columns
+    let mut stmt = conn.prepare(&query("*"))?;
+    stmt.execute([])?;
+    assert_eq!(stmt.column_names(), vec!["items"]);
+
+    let mut stmt = conn.prepare(&query("CAST(items AS VARCHAR)"))?;
+    let items = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // a row for every record, even those without a response
+    assert_eq!(items, vec!["[{'name': apple, 'quantity': 1}]", "[]"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_on_error_skip_collects_errors() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    user::User {
+        name: "Alice".to_string(),
+        id: 1,
+    }
+    .encode_length_delimited(&mut buf)?;
+
+    // a string field claiming 5 bytes with only 1 present
+    let corrupt = [0x0a, 0x05, 0x41];
+    prost::encoding::encode_varint(corrupt.len() as u64, &mut buf);
+    buf.extend_from_slice(&corrupt);
+
+    user::User {
+        name: "Bob".to_string(),
+        id: 2,
+    }
+    .encode_length_delimited(&mut buf)?;
+
+    std::fs::create_dir_all("tests/generated/on_error")?;
+    File::create("tests/generated/on_error/users.bin")?.write_all(&buf)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/on_error/users.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                on_error = 'skip'
+            );
+        ",
+    )?;
+
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(names, vec!["Alice".to_string(), "Bob".to_string()]);
+
+    let mut stmt = conn.prepare("SELECT filename, position, error FROM protobuf_errors();")?;
+    let errors = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(errors.len(), 1, "expected a single error: {errors:?}");
+    assert!(errors[0].0.ends_with("users.bin"));
+    assert_eq!(errors[0].1, 10);
+
+    Ok(())
+}
+
+#[test]
+fn test_on_error_root_path() -> Result<()> {
+    let conn = connection()?;
+
+    let envelope = |request_id: &str, name: &str| features::Envelope {
+        request_id: request_id.to_string(),
+        response: Some(features::Response {
+            items: vec![features::Item {
+                name: name.to_string(),
+                quantity: 1,
+            }],
+        }),
+    };
+
+    let mut buf = Vec::new();
+    envelope("a", "apple").encode_length_delimited(&mut buf)?;
+    let position = buf.len() as u64;
+
+    // a `response` claiming 5 bytes with only 1 present
+    let corrupt = [0x12, 0x05, 0x0a];
+    prost::encoding::encode_varint(corrupt.len() as u64, &mut buf);
+    buf.extend_from_slice(&corrupt);
+
+    envelope("c", "cherry").encode_length_delimited(&mut buf)?;
+
+    std::fs::create_dir_all("tests/generated/on_error_root_path")?;
+    File::create("tests/generated/on_error_root_path/envelopes.bin")?.write_all(&buf)?;
+
+    let query = |on_error: &str| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/on_error_root_path/envelopes.bin',
+                    message_type = 'features.Envelope',
+                    delimiter = 'Varint',
+                    root_path = 'response.items',
+                    on_error = '{on_error}'
+                );
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    let err = query("fail").expect_err("expected the scan to fail");
+    assert!(
+        err.to_string()
+            .contains(&format!("envelopes.bin: record at position {position}")),
+        "unexpected error: {err}"
+    );
+
+    // scans of tests running alongside replace the most recent scan, retry
+    // until the errors are this one's
+    for _ in 0..100 {
+        assert_eq!(
+            query("skip")?,
+            vec!["apple".to_string(), "cherry".to_string()]
+        );
+
+        let mut stmt = conn.prepare(
+            "
+                SELECT position FROM protobuf_errors()
+                WHERE filename LIKE '%on_error_root_path/envelopes.bin';
+            ",
+        )?;
+        let errors = stmt
+            .query_map([], |row| row.get::<_, u64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !errors.is_empty() {
+            assert_eq!(errors, vec![position]);
+            return Ok(());
+        }
+    }
+
+    panic!("the errors of the scan were always replaced by another's");
+}
+
+#[test]
+fn test_skip_malformed() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    users()[0].encode_length_delimited(&mut buf)?;
+
+    // a string field claiming 5 bytes with only 1 present
+    let corrupt = [0x0a, 0x05, 0x41];
+    prost::encoding::encode_varint(corrupt.len() as u64, &mut buf);
+    buf.extend_from_slice(&corrupt);
+
+    users()[1].encode_length_delimited(&mut buf)?;
+
+    std::fs::create_dir_all("tests/generated/skip_malformed")?;
+    File::create("tests/generated/skip_malformed/users.bin")?.write_all(&buf)?;
+
+    let query = |options: &str| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/skip_malformed/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    {options}
+                );
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    for options in [
+        "skip_malformed = true",
+        "skip_malformed = true, on_error = 'skip'",
+    ] {
+        assert_eq!(query(options)?, vec!["Alice", "Bob"]);
+    }
+
+    let err = query("skip_malformed = false").unwrap_err();
+    assert!(err.to_string().contains("record at position 10"), "{err}");
+
+    let err = query("skip_malformed = true, on_error = 'fail'").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("`skip_malformed` can't be used with `on_error = 'fail'`"),
+        "{err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_on_error_skip_rolls_back_lists() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+
+    // the moves are pushed to the list before the record fails on a fixed32
+    // encoded move
+    let mut corrupt = features::Route {
+        moves: vec![1, 2, 3],
+    }
+    .encode_to_vec();
+    corrupt.extend_from_slice(&[0x0d, 0x00, 0x00, 0x00, 0x00]);
+    prost::encoding::encode_varint(corrupt.len() as u64, &mut buf);
+    buf.extend_from_slice(&corrupt);
+
+    for moves in [vec![4], vec![], vec![5, 6]] {
+        features::Route { moves }.encode_length_delimited(&mut buf)?;
+    }
+
+    std::fs::create_dir_all("tests/generated/on_error_lists")?;
+    File::create("tests/generated/on_error_lists/routes.bin")?.write_all(&buf)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT CAST(moves AS VARCHAR) FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/on_error_lists/routes.bin',
+                message_type = 'features.Route',
+                delimiter = 'Varint',
+                on_error = 'skip'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(rows, vec!["[4]", "[]", "[5, 6]"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_errors_reset_every_scan() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    // a string field claiming 5 bytes with only 1 present
+    let corrupt = [0x0a, 0x05, 0x41];
+    prost::encoding::encode_varint(corrupt.len() as u64, &mut buf);
+    buf.extend_from_slice(&corrupt);
+
+    std::fs::create_dir_all("tests/generated/errors_reset")?;
+    File::create("tests/generated/errors_reset/corrupt.bin")?.write_all(&buf)?;
+    write_varint_delimited("tests/generated/errors_reset/users.bin", &users())?;
+
+    let scan = |file: &str, on_error: &str| -> Result<u64> {
+        Ok(conn.query_row(
+            &format!(
+                "
+                    SELECT count(*) FROM protobuf(
+                        descriptors = './tests/generated/descriptor.pb',
+                        files = './tests/generated/errors_reset/{file}',
+                        message_type = 'user.User',
+                        delimiter = 'Varint',
+                        on_error = '{on_error}'
+                    );
+                "
+            ),
+            [],
+            |row| row.get(0),
+        )?)
+    };
+
+    let errors = |scan_id: &str| -> Result<Vec<(String, u64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT filename, scan_id FROM protobuf_errors({scan_id})
+                WHERE filename LIKE '%errors_reset%';
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    // scans of tests running alongside replace the most recent scan, retry
+    // until the errors are this one's
+    let mut skipped = vec![];
+    for _ in 0..100 {
+        assert_eq!(scan("corrupt.bin", "skip")?, 0);
+
+        skipped = errors("")?;
+        if !skipped.is_empty() {
+            break;
+        }
+    }
+    assert_eq!(skipped.len(), 1, "{skipped:?}");
+    let scan_id = skipped[0].1;
+
+    // a scan which doesn't skip errors still replaces the most recent scan's
+    assert_eq!(scan("users.bin", "fail")?, 3);
+    assert_eq!(errors("")?, vec![]);
+
+    // while the errors of earlier scans are kept by id
+    assert_eq!(errors(&format!("scan_id = {scan_id}"))?, skipped);
+
+    Ok(())
+}
+
+#[test]
+fn test_auto_endian_fixed() -> Result<()> {
+    let conn = connection()?;
+
+    let expected = users()
+        .into_iter()
+        .map(|it| (it.name, it.id))
+        .collect::<Vec<_>>();
+
+    write_delimited(
+        "tests/generated/auto_endian/big.bin",
+        &users(),
+        |len, buf| buf.extend_from_slice(&(len as u32).to_be_bytes()),
+    )?;
+    assert_eq!(
+        query_users(
+            &conn,
+            "./tests/generated/auto_endian/big.bin",
+            "AutoEndianFixed"
+        )?,
+        expected
+    );
+
+    write_delimited(
+        "tests/generated/auto_endian/little.bin",
+        &users(),
+        |len, buf| buf.extend_from_slice(&(len as u32).to_le_bytes()),
+    )?;
+    assert_eq!(
+        query_users(
+            &conn,
+            "./tests/generated/auto_endian/little.bin",
+            "AutoEndianFixed"
+        )?,
+        expected
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_auto_delimiter() -> Result<()> {
+    let conn = connection()?;
+
+    let expected = users()
+        .into_iter()
+        .map(|it| (it.name, it.id))
+        .collect::<Vec<_>>();
+
+    write_delimited(
+        "tests/generated/auto_delimiter/big.bin",
+        &users(),
+        |len, buf| buf.extend_from_slice(&(len as u32).to_be_bytes()),
+    )?;
+    assert_eq!(
+        query_users(&conn, "./tests/generated/auto_delimiter/big.bin", "Auto")?,
+        expected
+    );
+
+    write_varint_delimited("tests/generated/auto_delimiter/varint.bin", &users())?;
+    assert_eq!(
+        query_users(&conn, "./tests/generated/auto_delimiter/varint.bin", "Auto")?,
+        expected
+    );
+
+    File::create("tests/generated/auto_delimiter/single.bin")?
+        .write_all(&users()[0].encode_to_vec())?;
+    assert_eq!(
+        query_users(&conn, "./tests/generated/auto_delimiter/single.bin", "Auto")?,
+        expected[..1]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_delimiter_by_extension() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/delimiter_by_extension/users.bin", &users())?;
+    write_delimited(
+        "tests/generated/delimiter_by_extension/users.fixed",
+        &users(),
+        |len, buf| buf.extend_from_slice(&(len as u32).to_be_bytes()),
+    )?;
+    File::create("tests/generated/delimiter_by_extension/user.pb")?
+        .write_all(&users()[0].encode_to_vec())?;
+
+    let mut rows = query_users(
+        &conn,
+        "./tests/generated/delimiter_by_extension/*",
+        "ext:bin=Varint, fixed=BigEndianFixed, *=SingleMessagePerFile",
+    )?;
+    rows.sort();
+
+    let mut expected = users()
+        .into_iter()
+        .chain(users())
+        .chain(users().into_iter().take(1))
+        .map(|it| (it.name, it.id))
+        .collect::<Vec<_>>();
+    expected.sort();
+
+    assert_eq!(rows, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_little_endian_fixed() -> Result<()> {
+    let conn = connection()?;
+
+    write_delimited(
+        "tests/generated/little_endian/users.bin",
+        &users(),
+        |len, buf| buf.extend_from_slice(&(len as u32).to_le_bytes()),
+    )?;
+
+    assert_eq!(
+        query_users(
+            &conn,
+            "./tests/generated/little_endian/users.bin",
+            "LittleEndianFixed"
+        )?,
+        users()
+            .into_iter()
+            .map(|it| (it.name, it.id))
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_fixed_prefix_widths() -> Result<()> {
+    let conn = connection()?;
+
+    let mut users = users();
+    let query = |file: &str, delimiter: &str| -> Result<Vec<(String, u64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name, size FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/prefix_widths/{file}',
+                    message_type = 'user.User',
+                    delimiter = '{delimiter}',
+                    size = true
+                );
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    write_delimited(
+        "tests/generated/prefix_widths/big16.bin",
+        &users,
+        |len, buf| buf.extend_from_slice(&(len as u16).to_be_bytes()),
+    )?;
+    write_delimited(
+        "tests/generated/prefix_widths/little16.bin",
+        &users,
+        |len, buf| buf.extend_from_slice(&(len as u16).to_le_bytes()),
+    )?;
+
+    let expected = users
+        .iter()
+        .map(|it| (it.name.clone(), it.encoded_len() as u64))
+        .collect::<Vec<_>>();
+    assert_eq!(query("big16.bin", "BigEndianFixed16")?, expected);
+    assert_eq!(query("little16.bin", "LittleEndianFixed16")?, expected);
+
+    // a record whose length doesn't fit in 16 bits
+    users[1].name = "B".repeat(70_000);
+
+    write_delimited(
+        "tests/generated/prefix_widths/big64.bin",
+        &users,
+        |len, buf| buf.extend_from_slice(&(len as u64).to_be_bytes()),
+    )?;
+    write_delimited(
+        "tests/generated/prefix_widths/little64.bin",
+        &users,
+        |len, buf| buf.extend_from_slice(&(len as u64).to_le_bytes()),
+    )?;
+
+    let expected = users
+        .iter()
+        .map(|it| (it.name.clone(), it.encoded_len() as u64))
+        .collect::<Vec<_>>();
+    assert!(expected[1].1 > u16::MAX as u64);
+    assert_eq!(query("big64.bin", "BigEndianFixed64")?, expected);
+    assert_eq!(query("little64.bin", "LittleEndianFixed64")?, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_triple_nested_repeated_fields() -> Result<()> {
+    let conn = connection()?;
+
+    let twig = |leaves: &[i32]| features::Twig {
+        leaves: leaves.to_vec(),
+    };
+    let branch = |twigs: Vec<features::Twig>| features::Branch { twigs };
+
+    let trees = vec![
+        features::Tree {
+            branches: vec![
+                branch(vec![twig(&[1, 2]), twig(&[])]),
+                branch(vec![twig(&[3])]),
+            ],
+        },
+        features::Tree { branches: vec![] },
+        features::Tree {
+            branches: vec![branch(vec![]), branch(vec![twig(&[4, 5, 6])])],
+        },
+        features::Tree {
+            branches: vec![branch(vec![twig(&[7]), twig(&[8, 9])])],
+        },
+    ];
+
+    write_varint_delimited("tests/generated/nested/trees.bin", &trees)?;
+
+    let format_list = |items: Vec<String>| format!("[{}]", items.join(", "));
+    let expected = trees
+        .iter()
+        .map(|tree| {
+            format_list(
+                tree.branches
+                    .iter()
+                    .map(|branch| {
+                        let twigs = branch
+                            .twigs
+                            .iter()
+                            .map(|twig| {
+                                let leaves = twig.leaves.iter().map(|it| it.to_string());
+                                format!("{{'leaves': {}}}", format_list(leaves.collect()))
+                            })
+                            .collect();
+
+                        format!("{{'twigs': {}}}", format_list(twigs))
+                    })
+                    .collect(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT CAST(branches AS VARCHAR) FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/nested/trees.bin',
+                message_type = 'features.Tree',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(results, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_packed_and_unpacked_repeated_scalars() -> Result<()> {
+    let conn = connection()?;
+
+    // leaves 1, then 2 and 3 packed, then 4, all in a single twig
+    let twig = [0x08, 0x01, 0x0a, 0x02, 0x02, 0x03, 0x08, 0x04];
+    let empty_twig = [];
+
+    let mut buf = Vec::new();
+    for record in [&twig[..], &empty_twig[..], &twig[..]] {
+        prost::encoding::encode_varint(record.len() as u64, &mut buf);
+        buf.extend_from_slice(record);
+    }
+
+    std::fs::create_dir_all("tests/generated/packed")?;
+    File::create("tests/generated/packed/twigs.bin")?.write_all(&buf)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT CAST(leaves AS VARCHAR) FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/packed/twigs.bin',
+                message_type = 'features.Twig',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(results, vec!["[1, 2, 3, 4]", "[]", "[1, 2, 3, 4]"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_field_presence() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/presence/users.bin",
+        &[
+            user::User {
+                name: "Alice".to_string(),
+                id: 1,
+            },
+            user::User {
+                name: "".to_string(),
+                id: 2,
+            },
+            user::User {
+                name: "Charlie".to_string(),
+                id: 0,
+            },
+        ],
+    )?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT array_to_string(_presence, ',') FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/presence/users.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                field_presence = true
+            );
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(results, vec!["name,id", "id", "name"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_size_includes_prefix() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/size/users.bin", &users())?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT size FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/size/users.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                size = true,
+                size_includes_prefix = true
+            );
+        ",
+    )?;
+
+    let sizes = stmt
+        .query_map([], |row| row.get::<_, u64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let expected = users()
+        .iter()
+        .map(|it| (it.encoded_len() + 1) as u64)
+        .collect::<Vec<_>>();
+
+    assert_eq!(sizes, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_trailing_bytes() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    for user in users() {
+        user.encode_length_delimited(&mut buf)?;
+    }
+    // a record claiming 10 bytes with only 2 present
+    buf.extend_from_slice(&[0x0a, 0x0a, 0x41]);
+
+    std::fs::create_dir_all("tests/generated/trailing")?;
+    File::create("tests/generated/trailing/users.bin")?.write_all(&buf)?;
+
+    let results = query_users(&conn, "./tests/generated/trailing/users.bin", "Varint")?;
+    assert_eq!(
+        results,
+        vec![
+            ("Alice".to_string(), 1),
+            ("Bob".to_string(), 2),
+            ("Charlie".to_string(), 3),
+        ]
+    );
+
+    let err = conn
+        .prepare(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/trailing/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    trailing_bytes = 'error'
+                );
+            ",
+        )?
+        .query_map([], |_| Ok(()))?
+        .collect::<Result<Vec<_>, _>>()
+        .err()
+        .expect("expected the scan to fail");
+
+    assert!(
+        err.to_string().contains("incomplete record at position"),
+        "unexpected error: {err}"
+    );
+
+    // scans of tests running alongside replace the most recent scan, retry
+    // until the warning is this one's
+    for _ in 0..100 {
+        let count: u64 = conn.query_row(
+            "
+                SELECT count(*) FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/trailing/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    trailing_bytes = 'warn'
+                );
+            ",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(count, 3);
+
+        let mut stmt = conn.prepare(
+            "
+                SELECT position, error FROM protobuf_errors()
+                WHERE filename LIKE '%trailing/users.bin';
+            ",
+        )?;
+        let warnings = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !warnings.is_empty() {
+            assert_eq!(
+                warnings,
+                vec![(
+                    (buf.len() - 3) as u64,
+                    "incomplete record after the last complete record".to_string()
+                )]
+            );
+            return Ok(());
+        }
+    }
+
+    panic!("the warning of the scan was always replaced by another's");
+}
+
+#[test]
+fn test_map_fields() -> Result<()> {
+    let conn = connection()?;
+
+    let inventory = |counts: &[(&str, i64)]| features::Inventory {
+        counts: counts
+            .iter()
+            .map(|(key, value)| (key.to_string(), *value))
+            .collect(),
+    };
+
+    write_varint_delimited(
+        "tests/generated/map/inventories.bin",
+        &[
+            inventory(&[("apple", 3), ("banana", 5)]),
+            inventory(&[]),
+            inventory(&[("cherry", 7)]),
+        ],
+    )?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT entry.key, entry.value FROM (
+                SELECT unnest(counts) AS entry FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/map/inventories.bin',
+                    message_type = 'features.Inventory',
+                    delimiter = 'Varint'
+                )
+            )
+            ORDER BY entry.key;
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        results,
+        vec![
+            ("apple".to_string(), 3),
+            ("banana".to_string(), 5),
+            ("cherry".to_string(), 7),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_map_entries_per_row() -> Result<()> {
+    let conn = connection()?;
+
+    let rows = [
+        (0..50)
+            .map(|idx| (format!("key{idx:02}"), idx as i64 - 25))
+            .collect::<Vec<_>>(),
+        vec![],
+        vec![("only".to_string(), i64::MAX)],
+        vec![("a".to_string(), 1), ("b".to_string(), 2)],
+    ];
+
+    let inventories = rows
+        .iter()
+        .map(|counts| features::Inventory {
+            counts: counts.iter().cloned().collect(),
+        })
+        .collect::<Vec<_>>();
+    write_varint_delimited("tests/generated/map_entries/inventories.bin", &inventories)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT
+                len(counts),
+                array_to_string(
+                    list_sort(list_transform(counts, entry -> entry.key || '=' || entry.value)),
+                    ','
+                )
+            FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/map_entries/inventories.bin',
+                message_type = 'features.Inventory',
+                delimiter = 'Varint',
+                record_index = true
+            )
+            ORDER BY record_index;
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // every entry is its own element, in the row it was encoded in
+    let expected = rows
+        .iter()
+        .map(|counts| {
+            let mut entries = counts
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>();
+            entries.sort();
+
+            (counts.len() as i64, entries.join(","))
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(results, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_unknown_enum() -> Result<()> {
+    let conn = connection()?;
+
+    let paint = |color: i32| features::Paint { color };
+    write_varint_delimited(
+        "tests/generated/enum/paints.bin",
+        &[paint(features::Color::Red as i32), paint(7), paint(0)],
+    )?;
+
+    let query_colors = |unknown_enum: &str| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT CAST(color AS VARCHAR) FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/enum/paints.bin',
+                    message_type = 'features.Paint',
+                    delimiter = 'Varint',
+                    unknown_enum = '{unknown_enum}'
+                );
+            "
+        ))?;
+
+        let results = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(results)
+    };
+
+    assert_eq!(
+        query_colors("default")?,
+        vec!["RED", "COLOR_UNSPECIFIED", "COLOR_UNSPECIFIED"]
+    );
+    assert_eq!(
+        query_colors("preserve")?,
+        vec!["RED", "7", "COLOR_UNSPECIFIED"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_concat_files() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    for user in users() {
+        user.encode_length_delimited(&mut buf)?;
+    }
+
+    // split the stream so records straddle the file boundaries
+    std::fs::create_dir_all("tests/generated/concat")?;
+    for (idx, chunk) in buf.chunks(buf.len() / 3 + 1).enumerate() {
+        File::create(format!("tests/generated/concat/users.{idx:03}"))?.write_all(chunk)?;
+    }
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, id, filename, position FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/concat/users.*',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                concat_files = true,
+                filename = true,
+                position = true
+            );
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, u64>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut position = 0;
+    let mut expected = vec![];
+    for user in users() {
+        expected.push((user.name.clone(), user.id, position));
+        position += (user.encoded_len() + 1) as u64;
+    }
+
+    assert_eq!(
+        results
+            .iter()
+            .map(|(name, id, _, position)| (name.clone(), *id, *position))
+            .collect::<Vec<_>>(),
+        expected
+    );
+    assert!(results
+        .iter()
+        .all(|(_, _, filename, _)| filename.ends_with("users.000")));
+
+    Ok(())
+}
+
+#[test]
+fn test_struct_field_order() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/struct_order/orders.bin",
+        &[features::Order {
+            detail: Some(features::Detail {
+                b: 2,
+                a: "a".to_string(),
+            }),
+        }],
+    )?;
+
+    let query = |struct_field_order: &str| -> Result<(String, String, i32)> {
+        let result = conn.query_row(
+            &format!(
+                "
+                    SELECT typeof(detail), detail.a, detail.b FROM protobuf(
+                        descriptors = './tests/generated/descriptor.pb',
+                        files = './tests/generated/struct_order/orders.bin',
+                        message_type = 'features.Order',
+                        delimiter = 'Varint',
+                        struct_field_order = '{struct_field_order}'
+                    );
+                "
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+
+        Ok(result)
+    };
+
+    assert_eq!(
+        query("declaration")?,
+        (
+            "STRUCT(b INTEGER, a VARCHAR)".to_string(),
+            "a".to_string(),
+            2
+        )
+    );
+    assert_eq!(
+        query("number")?,
+        (
+            "STRUCT(a VARCHAR, b INTEGER)".to_string(),
+            "a".to_string(),
+            2
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_auto_compression() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    for user in users() {
+        user.encode_length_delimited(&mut buf)?;
+    }
+
+    std::fs::create_dir_all("tests/generated/compression")?;
+    File::create("tests/generated/compression/users.bin")?.write_all(&buf)?;
+
+    let mut encoder = flate2::write::GzEncoder::new(
+        File::create("tests/generated/compression/users.bin.gz")?,
+        flate2::Compression::default(),
+    );
+    encoder.write_all(&buf)?;
+    encoder.finish()?;
+
+    File::create("tests/generated/compression/users.bin.zst")?
+        .write_all(&zstd::encode_all(buf.as_slice(), 0)?)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, id FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/compression/users.*',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                compression = 'auto'
+            )
+            ORDER BY id, name;
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let expected = users()
+        .into_iter()
+        .flat_map(|user| std::iter::repeat((user.name, user.id)).take(3))
+        .collect::<Vec<_>>();
+
+    assert_eq!(results, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_zstd_frames() -> Result<()> {
+    let conn = connection()?;
+
+    // each record in a frame of its own, like an appending writer would
+    let mut buf = Vec::new();
+    for user in users() {
+        buf.extend(zstd::encode_all(
+            user.encode_length_delimited_to_vec().as_slice(),
+            0,
+        )?);
+    }
+
+    std::fs::create_dir_all("tests/generated/zstd_frames")?;
+    File::create("tests/generated/zstd_frames/users.bin.zst")?.write_all(&buf)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, id FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/zstd_frames/users.bin.zst',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                compression = 'zstd'
+            );
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        results,
+        users()
+            .into_iter()
+            .map(|it| (it.name, it.id))
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_any_types() -> Result<()> {
+    let conn = connection()?;
+
+    let event = |type_name: &str, value: Vec<u8>| features::Event {
+        payload: Some(prost_types::Any {
+            type_url: format!("type.googleapis.com/{type_name}"),
+            value,
+        }),
+    };
+
+    write_varint_delimited(
+        "tests/generated/any/events.bin",
+        &[
+            event(
+                "features.Item",
+                features::Item {
+                    name: "apple".to_string(),
+                    quantity: 3,
+                }
+                .encode_to_vec(),
+            ),
+            event(
+                "features.Upload",
+                features::Upload {
+                    filename: "a.bin".to_string(),
+                    id: 7,
+                }
+                .encode_to_vec(),
+            ),
+            event("features.Unknown", vec![0x08, 0x01]),
+        ],
+    )?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT
+                union_tag(payload)::VARCHAR,
+                union_extract(payload, 'features.Item').name,
+                union_extract(payload, 'features.Upload').id,
+                union_extract(payload, 'raw').type_url
+            FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/any/events.bin',
+                message_type = 'features.Event',
+                delimiter = 'Varint',
+                any_types = 'features.Item, features.Upload'
+            );
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<i32>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        results,
+        vec![
+            (
+                "features.Item".to_string(),
+                Some("apple".to_string()),
+                None,
+                None
+            ),
+            ("features.Upload".to_string(), None, Some(7), None),
+            (
+                "raw".to_string(),
+                None,
+                None,
+                Some("type.googleapis.com/features.Unknown".to_string())
+            ),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_schema_evolution_lenient() -> Result<()> {
+    let conn = connection()?;
+
+    // name is `a` followed by an invalid UTF-8 byte, color is undeclared
+    let record = [0x0a, 0x02, 0x61, 0xff, 0x18, 0x09];
+
+    let mut buf = Vec::new();
+    prost::encoding::encode_varint(record.len() as u64, &mut buf);
+    buf.extend_from_slice(&record);
+
+    std::fs::create_dir_all("tests/generated/lenient")?;
+    File::create("tests/generated/lenient/legacy.bin")?.write_all(&buf)?;
+
+    let query = |schema_evolution: &str| {
+        format!(
+            "
+                SELECT name, color, typeof(extra) FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/lenient/legacy.bin',
+                    message_type = 'features.Legacy',
+                    delimiter = 'Varint',
+                    schema_evolution = '{schema_evolution}'
+                );
+            "
+        )
+    };
+
+    let err = conn
+        .prepare(&query("strict"))
+        .err()
+        .expect("expected bind to fail");
+    assert!(
+        err.to_string().contains("unhandled field: checksum"),
+        "unexpected error: {err}"
+    );
+
+    let mut stmt = conn.prepare(&query("lenient"))?;
+    let results = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the unsupported `bytes` field is left out
+    assert_eq!(
+        results,
+        vec![(
+            "a\u{FFFD}".to_string(),
+            "9".to_string(),
+            "STRUCT(type_url VARCHAR, \"value\" BLOB)".to_string()
+        )]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_decimal_fields() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/decimal/items.bin",
+        &[features::Item {
+            name: "apple".to_string(),
+            quantity: 12345,
+        }],
+    )?;
+
+    let (logical_type, quantity) = conn.query_row(
+        "
+            SELECT typeof(quantity), CAST(quantity AS VARCHAR) FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/decimal/items.bin',
+                message_type = 'features.Item',
+                delimiter = 'Varint',
+                decimal_fields = 'quantity=4'
+            );
+        ",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    )?;
+
+    assert_eq!(logical_type, "DECIMAL(18,4)");
+    assert_eq!(quantity, "1.2345");
+
+    Ok(())
+}
+
+#[test]
+fn test_malformed_varint_length() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    users()[0].encode_length_delimited(&mut buf)?;
+    let position = buf.len();
+
+    // a varint which never ends, followed by more data
+    buf.extend_from_slice(&[0xff; 11]);
+    buf.extend_from_slice(&[0x01; 4]);
+
+    std::fs::create_dir_all("tests/generated/malformed")?;
+    File::create("tests/generated/malformed/users.bin")?.write_all(&buf)?;
+
+    let err = query_users(&conn, "./tests/generated/malformed/users.bin", "Varint")
+        .err()
+        .expect("expected the scan to fail");
+
+    assert!(
+        err.to_string().contains(&format!(
+            "users.bin: malformed varint length prefix at position {position}"
+        )),
+        "unexpected error: {err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_normalize_names() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/normalize/accounts.bin",
+        &[features::Account {
+            user_id: "a".to_string(),
+            http_server_id: "b".to_string(),
+        }],
+    )?;
+
+    let column_names = |normalize_names: &str| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/normalize/accounts.bin',
+                    message_type = 'features.Account',
+                    delimiter = 'Varint',
+                    normalize_names = '{normalize_names}'
+                );
+            "
+        ))?;
+        stmt.execute([])?;
+
+        Ok(stmt.column_names())
+    };
+
+    assert_eq!(column_names("none")?, vec!["userId", "HTTPServerId"]);
+    assert_eq!(column_names("lower")?, vec!["userid", "httpserverid"]);
+    assert_eq!(column_names("snake")?, vec!["user_id", "http_server_id"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_column_naming() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/column_naming/envelopes.bin",
+        &[features::Envelope {
+            request_id: "a".to_string(),
+            response: None,
+        }],
+    )?;
+
+    let column_names = |column_naming: &str| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/column_naming/envelopes.bin',
+                    message_type = 'features.Envelope',
+                    delimiter = 'Varint',
+                    column_naming = '{column_naming}'
+                );
+            "
+        ))?;
+        stmt.execute([])?;
+
+        Ok(stmt.column_names())
+    };
+
+    assert_eq!(column_names("proto")?, vec!["request_id", "response"]);
+    assert_eq!(column_names("json")?, vec!["requestId", "response"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_text_format() -> Result<()> {
+    let conn = connection()?;
+
+    std::fs::create_dir_all("tests/generated/text")?;
+    File::create("tests/generated/text/users.txtpb")?
+        .write_all(b"name: \"Alice\"\nid: 1\n---\nname: \"Bob\"\nid: 2\n")?;
+    File::create("tests/generated/text/user.txtpb")?.write_all(b"name: \"Charlie\" id: 3")?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, id, position FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/text/*.txtpb',
+                message_type = 'user.User',
+                format = 'text',
+                position = true
+            )
+            ORDER BY id;
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, u64>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![
+            ("Alice".to_string(), 1, 0),
+            ("Bob".to_string(), 2, 24),
+            ("Charlie".to_string(), 3, 0),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_jsonl_format() -> Result<()> {
+    let conn = connection()?;
+
+    std::fs::create_dir_all("tests/generated/jsonl")?;
+    File::create("tests/generated/jsonl/users.jsonl")?.write_all(
+        b"{\"name\": \"Alice\", \"id\": 1}\n\n{\"name\": \"Bob\", \"id\": 2}\n{\"name\": \"Charlie\", \"id\": 3}\n",
+    )?;
+    write_varint_delimited("tests/generated/jsonl/users.bin", &users())?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, id, position FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/jsonl/users.jsonl',
+                message_type = 'user.User',
+                format = 'jsonl',
+                position = true
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, u64>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // blank lines are skipped but still count towards positions
+    assert_eq!(
+        rows.iter()
+            .map(|(_, _, position)| *position)
+            .collect::<Vec<_>>(),
+        vec![0, 28, 53]
+    );
+    assert_eq!(
+        rows.into_iter()
+            .map(|(name, id, _)| (name, id))
+            .collect::<Vec<_>>(),
+        query_users(&conn, "./tests/generated/jsonl/users.bin", "Varint")?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_repeated_messages_as_json() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/json/responses.bin",
+        &[features::Response {
+            items: vec![
+                features::Item {
+                    name: "apple".to_string(),
+                    quantity: 3,
+                },
+                features::Item {
+                    name: "pear".to_string(),
+                    quantity: 0,
+                },
+            ],
+        }],
+    )?;
+
+    let (logical_type, items) = conn.query_row(
+        "
+            SELECT typeof(items), items::VARCHAR FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/json/responses.bin',
+                message_type = 'features.Response',
+                delimiter = 'Varint',
+                repeated_messages_as_json = 'items'
+            );
+        ",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    )?;
+
+    assert_eq!(logical_type, "VARCHAR[]");
+    assert_eq!(
+        items,
+        r#"[{"name":"apple","quantity":"3"}, {"name":"pear"}]"#
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_max_open_files() -> Result<()> {
+    let conn = connection()?;
+
+    for (idx, user) in users().iter().enumerate() {
+        write_varint_delimited(
+            &format!("tests/generated/max_open_files/users_{idx}.bin"),
+            &[user.clone()],
+        )?;
+    }
+
+    conn.execute("SET threads = 4", [])?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/max_open_files/*.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                max_open_files = 1
+            )
+            ORDER BY id;
+        ",
+    )?;
+
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(names, vec!["Alice", "Bob", "Charlie"]);
+
+    let err = conn
+        .prepare(
+            "
+                SELECT name FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/max_open_files/*.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    max_open_files = 0
+                );
+            ",
+        )
+        .unwrap_err();
+
+    assert!(err
+        .to_string()
+        .contains("`max_open_files` must be positive"));
+
+    Ok(())
+}
+
+#[test]
+fn test_enum_mappings() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/enum_mappings/users.bin", &users())?;
+
+    let ids = |unknown_enum: &str| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT id::VARCHAR FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/enum_mappings/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    enum_mappings = 'id=features.Color',
+                    unknown_enum = '{unknown_enum}'
+                );
+            "
+        ))?;
+
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ids)
+    };
+
+    assert_eq!(ids("default")?, vec!["RED", "GREEN", "COLOR_UNSPECIFIED"]);
+    assert_eq!(ids("preserve")?, vec!["RED", "GREEN", "3"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_nested_timestamps() -> Result<()> {
+    let conn = connection()?;
+
+    let timestamp = prost_types::Timestamp {
+        seconds: 1_700_000_000,
+        nanos: 250_000_000,
+    };
+
+    write_varint_delimited(
+        "tests/generated/timestamps/schedules.bin",
+        &[features::Schedule {
+            span: Some(features::Span {
+                start: Some(timestamp.clone()),
+            }),
+            deadlines: [("review".to_string(), timestamp.clone())].into(),
+            history: vec![timestamp],
+        }],
+    )?;
+
+    let row = conn.query_row(
+        "
+            SELECT
+                typeof(span.start),
+                span.start::VARCHAR,
+                typeof(deadlines[1].value),
+                deadlines[1].value::VARCHAR,
+                typeof(history),
+                history[1]::VARCHAR
+            FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/timestamps/schedules.bin',
+                message_type = 'features.Schedule',
+                delimiter = 'Varint'
+            );
+        ",
+        [],
+        |row| {
+            (0..6)
+                .map(|idx| row.get::<_, String>(idx))
+                .collect::<Result<Vec<_>, _>>()
+        },
+    )?;
+
+    assert_eq!(
+        row,
+        vec![
+            "TIMESTAMP",
+            "2023-11-14 22:13:20.25",
+            "TIMESTAMP",
+            "2023-11-14 22:13:20.25",
+            "TIMESTAMP[]",
+            "2023-11-14 22:13:20.25",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_protobuf_validate() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/validate/a_good.bin", &users())?;
+
+    let mut buf = Vec::new();
+    users()[0].encode_length_delimited(&mut buf)?;
+
+    // a string field claiming 5 bytes with only 1 present
+    let corrupt = [0x0a, 0x05, 0x41];
+    prost::encoding::encode_varint(corrupt.len() as u64, &mut buf);
+    buf.extend_from_slice(&corrupt);
+
+    users()[1].encode_length_delimited(&mut buf)?;
+
+    File::create("tests/generated/validate/b_bad.bin")?.write_all(&buf)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT filename, records, ok, first_error FROM protobuf_validate(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/validate/*.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint'
+            )
+            ORDER BY filename;
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, bool>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(rows.len(), 2);
+
+    assert!(rows[0].0.ends_with("a_good.bin"));
+    assert_eq!((rows[0].1, rows[0].2, rows[0].3.clone()), (3, true, None));
+
+    assert!(rows[1].0.ends_with("b_bad.bin"));
+    assert_eq!((rows[1].1, rows[1].2), (3, false));
+    let first_error = rows[1].3.clone().unwrap();
+    assert!(
+        first_error.starts_with("position 10: "),
+        "unexpected error: {first_error}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_fixed_width_integers() -> Result<()> {
+    let conn = connection()?;
+
+    let readings = [
+        features::Reading {
+            service: 5,
+            id: u64::MAX,
+            offset: -7,
+            delta: i64::MIN,
+        },
+        features::Reading::default(),
+    ];
+    write_varint_delimited("tests/generated/fixed/readings.bin", &readings)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT service, id, \"offset\", delta FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/fixed/readings.bin',
+                message_type = 'features.Reading',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(features::Reading {
+                service: row.get(0)?,
+                id: row.get(1)?,
+                offset: row.get(2)?,
+                delta: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(rows, readings);
+
+    Ok(())
+}
+
+#[test]
+fn test_fixed_signedness() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/fixed_signedness/readings.bin",
+        &[features::Reading {
+            service: 1,
+            id: -5i64 as u64,
+            offset: 0,
+            delta: 0,
+        }],
+    )?;
+
+    let (logical_type, id) = conn.query_row(
+        "
+            SELECT typeof(id), id FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/fixed_signedness/readings.bin',
+                message_type = 'features.Reading',
+                delimiter = 'Varint',
+                fixed_signedness = 'id=signed'
+            );
+        ",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+    )?;
+
+    assert_eq!(logical_type, "BIGINT");
+    assert_eq!(id, -5);
+
+    Ok(())
+}
+
+#[test]
+fn test_sint_fields() -> Result<()> {
+    let conn = connection()?;
+
+    let deltas = [
+        features::Delta {
+            small: -1,
+            large: -1,
+            steps: vec![-1, 1, i32::MIN, i32::MAX],
+        },
+        features::Delta {
+            small: 42,
+            large: i64::MIN,
+            steps: vec![],
+        },
+        features::Delta {
+            small: i32::MIN,
+            large: i64::MAX,
+            steps: vec![-300],
+        },
+    ];
+    write_varint_delimited("tests/generated/sint/deltas.bin", &deltas)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT small, large, CAST(steps AS VARCHAR) FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/sint/deltas.bin',
+                message_type = 'features.Delta',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![
+            (-1, -1, "[-1, 1, -2147483648, 2147483647]".to_string()),
+            (42, i64::MIN, "[]".to_string()),
+            (i32::MIN, i64::MAX, "[-300]".to_string()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_global_row_number() -> Result<()> {
+    let conn = connection()?;
+
+    for (idx, chunk) in users().chunks(2).enumerate() {
+        write_varint_delimited(
+            &format!("tests/generated/row_number/users_{idx}.bin"),
+            chunk,
+        )?;
+    }
+
+    let row_numbers = conn.query_row(
+        "
+            SELECT CAST(list(_row_number ORDER BY _row_number) AS VARCHAR) FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/row_number/*.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                global_row_number = true
+            );
+        ",
+        [],
+        |row| row.get::<_, String>(0),
+    )?;
+
+    // numbered across files rather than per file
+    assert_eq!(row_numbers, "[0, 1, 2]");
+
+    Ok(())
+}
+
+#[test]
+fn test_varint64_delimiter() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    for user in users() {
+        user.encode_length_delimited(&mut buf)?;
+    }
+
+    // a record claiming more bytes than fit in 32 bits, cut short
+    prost::encoding::encode_varint(1 << 33, &mut buf);
+    buf.extend_from_slice(&[0x0a, 0x01, 0x41]);
+
+    std::fs::create_dir_all("tests/generated/varint64")?;
+    File::create("tests/generated/varint64/users.bin")?.write_all(&buf)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/varint64/users.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint64'
+            );
+        ",
+    )?;
+
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(names, vec!["Alice", "Bob", "Charlie"]);
+
+    let err = conn
+        .query_row(
+            "
+                SELECT count(*) FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/varint64/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint64',
+                    trailing_bytes = 'error'
+                );
+            ",
+            [],
+            |row| row.get::<_, u64>(0),
+        )
+        .unwrap_err();
+
+    let position = buf.len() - 3 - 5;
+    assert!(
+        err.to_string()
+            .contains(&format!("incomplete record at position {position}")),
+        "unexpected error: {err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_timestamp_instants() -> Result<()> {
+    let conn = connection()?;
+
+    let timestamp = |seconds, nanos| prost_types::Timestamp { seconds, nanos };
+
+    write_varint_delimited(
+        "tests/generated/timestamp_instants/schedules.bin",
+        &[features::Schedule {
+            history: vec![
+                // 2024-02-29T12:34:56.123456789Z, nanos are truncated to micros
+                timestamp(1_709_210_096, 123_456_789),
+                // 1969-12-31T23:59:59.5Z
+                timestamp(-1, 500_000_000),
+                // 1900-01-01T00:00:00Z
+                timestamp(-2_208_988_800, 0),
+                timestamp(0, 999),
+            ],
+            ..Default::default()
+        }],
+    )?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT unnest(history)::VARCHAR FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/timestamp_instants/schedules.bin',
+                message_type = 'features.Schedule',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let instants = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        instants,
+        vec![
+            "2024-02-29 12:34:56.123456",
+            "1969-12-31 23:59:59.5",
+            "1900-01-01 00:00:00",
+            "1970-01-01 00:00:00",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_large_packed_fields() -> Result<()> {
+    let conn = connection()?;
+
+    let samples = [
+        features::Samples {
+            values: (0..1_000_000).map(|it| it as f64 * 0.5).collect(),
+            counts: vec![1, 2, 3],
+        },
+        features::Samples {
+            values: vec![-1.5],
+            counts: (0..100_000).collect(),
+        },
+    ];
+    write_varint_delimited("tests/generated/packed/samples.bin", &samples)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT
+                len(values),
+                list_sum(values),
+                values[1_000_000],
+                len(counts),
+                CAST(list_sum(counts) AS UBIGINT)
+            FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/packed/samples.bin',
+                message_type = 'features.Samples',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, u64>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, Option<f64>>(2)?,
+                row.get::<_, u64>(3)?,
+                row.get::<_, u64>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![
+            (1_000_000, 249_999_750_000.0, Some(499_999.5), 3, 6),
+            (1, -1.5, None, 100_000, 4_999_950_000),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_proto2_defaults() -> Result<()> {
+    let conn = connection()?;
+
+    let tickets = [
+        legacy::Ticket {
+            title: Some("absent".to_string()),
+            priority: None,
+            retries: None,
+        },
+        legacy::Ticket {
+            title: Some("present".to_string()),
+            priority: Some(legacy::Priority::Low as i32),
+            retries: Some(0),
+        },
+    ];
+    write_varint_delimited("tests/generated/proto2/tickets.bin", &tickets)?;
+
+    for unknown_enum in ["default", "preserve"] {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT title, CAST(priority AS VARCHAR), retries FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/proto2/tickets.bin',
+                    message_type = 'legacy.Ticket',
+                    delimiter = 'Varint',
+                    unknown_enum = '{unknown_enum}'
+                );
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i32>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(
+            rows,
+            vec![
+                ("absent".to_string(), "HIGH".to_string(), 3),
+                ("present".to_string(), "LOW".to_string(), 0),
+            ]
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_durations() -> Result<()> {
+    let conn = connection()?;
+
+    let duration = |seconds, nanos| prost_types::Duration { seconds, nanos };
+
+    let jobs = [
+        features::Job {
+            timeout: Some(duration(90 * 60, 0)),
+            backoff: vec![duration(1, 500_000_000), duration(0, 999)],
+        },
+        features::Job {
+            timeout: Some(duration(-1, -500_000_000)),
+            backoff: vec![duration(-90 * 60, 0)],
+        },
+        features::Job {
+            timeout: None,
+            backoff: vec![],
+        },
+    ];
+    write_varint_delimited("tests/generated/durations/jobs.bin", &jobs)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT
+                typeof(timeout),
+                epoch_us(TIMESTAMP '1970-01-01' + timeout),
+                CAST(list_transform(backoff, d -> epoch_us(TIMESTAMP '1970-01-01' + d)) AS VARCHAR)
+            FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/durations/jobs.bin',
+                message_type = 'features.Job',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "INTERVAL".to_string(),
+                5_400_000_000,
+                "[1500000, 0]".to_string()
+            ),
+            (
+                "INTERVAL".to_string(),
+                -1_500_000,
+                "[-5400000000]".to_string()
+            ),
+            ("INTERVAL".to_string(), 0, "[]".to_string()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_crc32_record_trailer() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    let mut positions = vec![];
+    for (idx, user) in users().iter().enumerate() {
+        positions.push(buf.len() as u64);
+
+        let mut message = user.encode_to_vec();
+        let crc = crc32fast::hash(&message);
+        // corrupt Bob's record after computing its checksum
+        if idx == 1 {
+            *message.last_mut().unwrap() ^= 0x20;
+        }
+
+        prost::encoding::encode_varint(message.len() as u64, &mut buf);
+        buf.extend_from_slice(&message);
+        buf.extend_from_slice(&crc.to_be_bytes());
+    }
+
+    std::fs::create_dir_all("tests/generated/record_trailer")?;
+    File::create("tests/generated/record_trailer/users.bin")?.write_all(&buf)?;
+
+    let query = |on_error: &str| -> Result<Vec<(String, u64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name, position FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/record_trailer/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    record_trailer = 'crc32',
+                    on_error = '{on_error}',
+                    position = true
+                );
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    assert_eq!(
+        query("skip")?,
+        vec![
+            ("Alice".to_string(), positions[0]),
+            ("Charlie".to_string(), positions[2]),
+        ]
+    );
+
+    let mut stmt = conn.prepare("SELECT position, error FROM protobuf_errors();")?;
+    let errors = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(errors.len(), 1, "expected a single error: {errors:?}");
+    assert_eq!(errors[0].0, positions[1]);
+    assert!(errors[0].1.contains("crc32 mismatch"), "{errors:?}");
+
+    let err = query("fail").unwrap_err();
+    assert!(err.to_string().contains("crc32 mismatch"), "{err}");
+
+    Ok(())
+}
+
+#[test]
+fn test_wrapper_types() -> Result<()> {
+    let conn = connection()?;
+
+    let profiles = [
+        features::Profile {
+            age: Some(0),
+            nickname: Some("".to_string()),
+            verified: Some(false),
+            scores: vec![0.0, 1.5],
+        },
+        features::Profile {
+            age: None,
+            nickname: None,
+            verified: None,
+            scores: vec![],
+        },
+        features::Profile {
+            age: Some(42),
+            nickname: Some("al".to_string()),
+            verified: Some(true),
+            scores: vec![-2.0],
+        },
+    ];
+    write_varint_delimited("tests/generated/wrappers/profiles.bin", &profiles)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT typeof(age), age, nickname, verified, CAST(scores AS VARCHAR) FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/wrappers/profiles.bin',
+                message_type = 'features.Profile',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<i32>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<bool>>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "INTEGER".to_string(),
+                Some(0),
+                Some("".to_string()),
+                Some(false),
+                "[0.0, 1.5]".to_string()
+            ),
+            ("INTEGER".to_string(), None, None, None, "[]".to_string()),
+            (
+                "INTEGER".to_string(),
+                Some(42),
+                Some("al".to_string()),
+                Some(true),
+                "[-2.0]".to_string()
+            ),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_enum_as_int() -> Result<()> {
+    let conn = connection()?;
+
+    let paints = [1, 2, 0, 7].map(|color| features::Paint { color });
+    write_varint_delimited("tests/generated/enum_as_int/paints.bin", &paints)?;
+
+    let colors = |enum_as_int: bool| -> Result<Vec<(String, String)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT typeof(color), color::VARCHAR FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/enum_as_int/paints.bin',
+                    message_type = 'features.Paint',
+                    delimiter = 'Varint',
+                    enum_as_int = {enum_as_int}
+                );
+            "
+        ))?;
+
+        let colors = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(colors)
+    };
+
+    assert_eq!(
+        colors(false)?
+            .into_iter()
+            .map(|(_, color)| color)
+            .collect::<Vec<_>>(),
+        vec!["RED", "GREEN", "COLOR_UNSPECIFIED", "COLOR_UNSPECIFIED"]
+    );
+
+    assert_eq!(
+        colors(true)?,
+        [
+            ("INTEGER", "1"),
+            ("INTEGER", "2"),
+            ("INTEGER", "0"),
+            ("INTEGER", "7")
+        ]
+        .map(|(ty, color)| (ty.to_string(), color.to_string()))
+        .to_vec()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_schema() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/validate_schema/users.bin", &users())?;
+
+    // reading is unaffected either way
+    let count = |message_type: &str| -> Result<u64> {
+        Ok(conn.query_row(
+            &format!(
+                "
+                    SELECT count(*) FROM protobuf(
+                        descriptors = './tests/generated/descriptor.pb',
+                        files = './tests/generated/validate_schema/users.bin',
+                        message_type = '{message_type}',
+                        delimiter = 'Varint',
+                        validate_schema = true
+                    );
+                "
+            ),
+            [],
+            |row| row.get(0),
+        )?)
+    };
+
+    let errors = || -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(
+            "
+                SELECT error FROM protobuf_errors()
+                WHERE filename LIKE '%validate_schema/users.bin';
+            ",
+        )?;
+
+        let rows = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    assert_eq!(count("user.User")?, 3);
+    assert_eq!(errors()?, Vec::<String>::new());
+
+    // scans of tests running alongside replace the most recent scan, retry
+    // until the errors are this one's
+    for _ in 0..100 {
+        assert_eq!(count("features.Paint")?, 3);
+
+        let errors = errors()?;
+        if !errors.is_empty() {
+            assert_eq!(errors.len(), 1, "{errors:?}");
+            assert!(
+                errors[0].contains("are fields of `features.Paint`"),
+                "unexpected error: {}",
+                errors[0]
+            );
+            return Ok(());
+        }
+    }
+
+    panic!("the errors of the scan were always replaced by another's");
+}
+
+#[test]
+fn test_unknown_enum_null() -> Result<()> {
+    let conn = connection()?;
+
+    // 7 isn't a value of `features.Color`, like one added by a newer schema
+    let paints = [1, 7, 0].map(|color| features::Paint { color });
+    write_varint_delimited("tests/generated/unknown_enum_null/paints.bin", &paints)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT color::VARCHAR FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/unknown_enum_null/paints.bin',
+                message_type = 'features.Paint',
+                delimiter = 'Varint',
+                unknown_enum = 'null'
+            );
+        ",
+    )?;
+
+    let colors = stmt
+        .query_map([], |row| row.get::<_, Option<String>>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        colors,
+        vec![
+            Some("RED".to_string()),
+            None,
+            Some("COLOR_UNSPECIFIED".to_string())
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_proto2_groups() -> Result<()> {
+    let conn = connection()?;
+
+    let reply = |text: &str| legacy::thread::Reply {
+        text: Some(text.to_string()),
+    };
+
+    let threads = [
+        legacy::Thread {
+            subject: Some("groups".to_string()),
+            author: Some(legacy::thread::Author {
+                name: Some("Alice".to_string()),
+                karma: Some(7),
+            }),
+            reply: vec![reply("first"), reply("second")],
+        },
+        legacy::Thread {
+            subject: Some("empty".to_string()),
+            author: None,
+            reply: vec![],
+        },
+    ];
+    write_varint_delimited("tests/generated/groups/threads.bin", &threads)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT subject, author.name, author.karma, CAST(reply AS VARCHAR) FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/groups/threads.bin',
+                message_type = 'legacy.Thread',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i32>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "groups".to_string(),
+                "Alice".to_string(),
+                7,
+                "[{'text': first}, {'text': second}]".to_string()
+            ),
+            ("empty".to_string(), "".to_string(), 0, "[]".to_string()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_root_path_groups() -> Result<()> {
+    let conn = connection()?;
+
+    let threads = [legacy::Thread {
+        subject: Some("groups".to_string()),
+        author: Some(legacy::thread::Author {
+            name: Some("Alice".to_string()),
+            karma: Some(7),
+        }),
+        reply: ["first", "second"]
+            .into_iter()
+            .map(|text| legacy::thread::Reply {
+                text: Some(text.to_string()),
+            })
+            .collect(),
+    }];
+    write_varint_delimited("tests/generated/root_path_groups/threads.bin", &threads)?;
+
+    let query = |sql: &str| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    // group fields are encoded between start and end group keys rather than
+    // length delimited, their elements are still rows
+    let texts = query(
+        "
+            SELECT text FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/root_path_groups/threads.bin',
+                message_type = 'legacy.Thread',
+                delimiter = 'Varint',
+                root_path = 'reply'
+            );
+        ",
+    )?;
+    assert_eq!(texts, vec!["first", "second"]);
+
+    let names = query(
+        "
+            SELECT name FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/root_path_groups/threads.bin',
+                message_type = 'legacy.Thread',
+                delimiter = 'Varint',
+                root_message_path = 'author'
+            );
+        ",
+    )?;
+    assert_eq!(names, vec!["Alice"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_default_message_type() -> Result<()> {
+    let conn = connection()?;
+
+    let descriptors = prost_types::FileDescriptorSet::decode(
+        std::fs::read("tests/generated/descriptor.pb")?.as_slice(),
+    )?;
+
+    let mut single = descriptors.clone();
+    single.file.retain(|file| file.name() == "user.proto");
+
+    std::fs::create_dir_all("tests/generated/default_message_type")?;
+    File::create("tests/generated/default_message_type/user.pb")?
+        .write_all(&single.encode_to_vec())?;
+    write_varint_delimited("tests/generated/default_message_type/users.bin", &users())?;
+
+    let query = |descriptors: &str| -> Result<Vec<(String, i32)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name, id FROM protobuf(
+                    descriptors = '{descriptors}',
+                    files = './tests/generated/default_message_type/users.bin',
+                    delimiter = 'Varint'
+                );
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    assert_eq!(
+        query("./tests/generated/default_message_type/user.pb")?,
+        users()
+            .into_iter()
+            .map(|it| (it.name, it.id))
+            .collect::<Vec<_>>()
+    );
+
+    let err = query("./tests/generated/descriptor.pb").unwrap_err();
+    for file in &descriptors.file {
+        for message in &file.message_type {
+            let name = format!("{}.{}", file.package(), message.name());
+            assert!(err.to_string().contains(&name), "{name} missing: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_message_type_not_found() -> Result<()> {
+    let conn = connection()?;
+
+    let err = conn
+        .prepare(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/data/**/*.bin',
+                    message_type = 'User',
+                    delimiter = 'SingleMessagePerFile'
+                );
+            ",
+        )
+        .unwrap_err();
+
+    assert!(
+        err.to_string().contains("message type `User` not found"),
+        "{err}"
+    );
+    assert!(err.to_string().contains("user.User"), "{err}");
+
+    Ok(())
+}
+
+#[test]
+fn test_proto_files() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/proto_files/users.bin", &users())?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, id FROM protobuf(
+                proto_files = './tests/protos/user.proto',
+                files = './tests/generated/proto_files/users.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        users()
+            .into_iter()
+            .map(|it| (it.name, it.id))
+            .collect::<Vec<_>>()
+    );
+
+    // imports are found next to the file, and the well known types are
+    // built in
+    let mut stmt = conn.prepare(
+        "
+            SELECT count(*) FROM protobuf(
+                proto_files = './tests/protos/features.proto',
+                import_paths = './tests/protos',
+                files = './tests/generated/proto_files/users.bin',
+                message_type = 'features.Paint',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+    assert_eq!(stmt.query_row([], |row| row.get::<_, u64>(0))?, 3);
+
+    std::fs::write(
+        "tests/generated/proto_files/broken.proto",
+        "syntax = \"proto3\";\n\nmessage Broken {\n    string name = 1\n}\n",
+    )?;
+
+    let err = conn
+        .prepare(
+            "
+                SELECT * FROM protobuf(
+                    proto_files = './tests/generated/proto_files/broken.proto',
+                    files = './tests/generated/proto_files/users.bin',
+                    delimiter = 'Varint'
+                );
+            ",
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("proto_files"), "{err}");
+
+    Ok(())
+}
+
+#[test]
+fn test_merged_descriptors() -> Result<()> {
+    let conn = connection()?;
+
+    let descriptors = prost_types::FileDescriptorSet::decode(
+        std::fs::read("tests/generated/descriptor.pb")?.as_slice(),
+    )?;
+
+    let write_set = |path: &str, files: Vec<prost_types::FileDescriptorProto>| -> Result<()> {
+        File::create(path)?
+            .write_all(&prost_types::FileDescriptorSet { file: files }.encode_to_vec())?;
+        Ok(())
+    };
+
+    std::fs::create_dir_all("tests/generated/merged_descriptors")?;
+
+    // the message's file and its imports in separate files, with the message's
+    // file also in the second one
+    let (features, others): (Vec<_>, Vec<_>) = descriptors
+        .file
+        .iter()
+        .cloned()
+        .partition(|file| file.name() == "features.proto");
+    write_set(
+        "tests/generated/merged_descriptors/features.pb",
+        features.clone(),
+    )?;
+    write_set(
+        "tests/generated/merged_descriptors/all.pb",
+        descriptors.file.clone(),
+    )?;
+    write_set("tests/generated/merged_descriptors/imports.pb", others)?;
+
+    let timestamp = prost_types::Timestamp {
+        seconds: 1_700_000_000,
+        nanos: 0,
+    };
+    write_varint_delimited(
+        "tests/generated/merged_descriptors/schedules.bin",
+        &[features::Schedule {
+            span: Some(features::Span {
+                start: Some(timestamp),
+            }),
+            deadlines: Default::default(),
+            history: vec![],
+        }],
+    )?;
+
+    let query = |descriptors: &str| -> Result<String> {
+        Ok(conn.query_row(
+            &format!(
+                "
+                    SELECT span.start::VARCHAR FROM protobuf(
+                        descriptors = '{descriptors}',
+                        files = './tests/generated/merged_descriptors/schedules.bin',
+                        message_type = 'features.Schedule',
+                        delimiter = 'Varint'
+                    );
+                "
+            ),
+            [],
+            |row| row.get(0),
+        )?)
+    };
+
+    let dir = "./tests/generated/merged_descriptors";
+    assert_eq!(
+        query(&format!("{dir}/features.pb, {dir}/imports.pb"))?,
+        "2023-11-14 22:13:20"
+    );
+    assert_eq!(
+        query(&format!("{dir}/features.pb,{dir}/all.pb"))?,
+        "2023-11-14 22:13:20"
+    );
+
+    // the same file declaring a message differently
+    let mut changed = features;
+    changed[0].message_type[0].name = Some("Renamed".to_string());
+    write_set("tests/generated/merged_descriptors/changed.pb", changed)?;
+
+    let err = query(&format!("{dir}/features.pb,{dir}/changed.pb")).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("`features.proto` is declared differently"),
+        "{err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_oneof_as_struct() -> Result<()> {
+    let conn = connection()?;
+
+    let events = [
+        Some(features::notification::Payload::Text("hello".to_string())),
+        Some(features::notification::Payload::Count(3)),
+        None,
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(idx, payload)| features::Notification {
+        id: format!("e{idx}"),
+        payload,
+    })
+    .collect::<Vec<_>>();
+    write_varint_delimited("tests/generated/oneof_as_struct/notifications.bin", &events)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT id, payload.tag, payload.value.text, payload.value.count, typeof(payload)
+            FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/oneof_as_struct/notifications.bin',
+                message_type = 'features.Notification',
+                delimiter = 'Varint',
+                oneof_as_struct = true
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let ty = "STRUCT(tag VARCHAR, \"value\" UNION(text VARCHAR, count BIGINT))".to_string();
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "e0".to_string(),
+                Some("text".to_string()),
+                Some("hello".to_string()),
+                None,
+                ty.clone()
+            ),
+            (
+                "e1".to_string(),
+                Some("count".to_string()),
+                None,
+                Some(3),
+                ty.clone()
+            ),
+            ("e2".to_string(), None, None, None, ty),
+        ]
+    );
+
+    // by default the fields of a oneof are columns of their own
+    let mut stmt = conn.prepare(
+        "
+            SELECT text, count FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/oneof_as_struct/notifications.bin',
+                message_type = 'features.Notification',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![
+            ("hello".to_string(), 0),
+            ("".to_string(), 3),
+            ("".to_string(), 0)
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_file_index() -> Result<()> {
+    let conn = connection()?;
+
+    // written out of order, indices follow the sorted file names
+    for (name, chunk) in ["c", "a", "b"].iter().zip(users().chunks(1)) {
+        write_varint_delimited(&format!("tests/generated/file_index/{name}.bin"), chunk)?;
+    }
+
+    let query = |concat_files: bool| -> Result<Vec<(String, u64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT filename, file_index FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/file_index/*.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    filename = true,
+                    file_index = true,
+                    concat_files = {concat_files}
+                )
+                ORDER BY filename;
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    let expected = [
+        ("./tests/generated/file_index/a.bin", 0),
+        ("./tests/generated/file_index/b.bin", 1),
+        ("./tests/generated/file_index/c.bin", 2),
+    ]
+    .map(|(name, idx)| (name.to_string(), idx))
+    .to_vec();
+
+    // stable across scans, whichever thread reads which file
+    assert_eq!(query(false)?, expected);
+    assert_eq!(query(false)?, expected);
+
+    // a single stream reported by its first file
+    assert_eq!(
+        query(true)?,
+        vec![("./tests/generated/file_index/a.bin".to_string(), 0); 3]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_record_index() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/record_index/a.bin", &users())?;
+    write_varint_delimited("tests/generated/record_index/b.bin", &users()[..2])?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT filename, position, record_index FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/record_index/*.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                filename = true,
+                position = true,
+                record_index = true
+            )
+            ORDER BY filename, position;
+        ",
+    )?;
+
+    let indices = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, u64>(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // restarts at 0 for every file
+    assert_eq!(
+        indices,
+        [
+            ("a.bin", 0),
+            ("a.bin", 1),
+            ("a.bin", 2),
+            ("b.bin", 0),
+            ("b.bin", 1)
+        ]
+        .map(|(name, idx)| (format!("./tests/generated/record_index/{name}"), idx))
+        .to_vec()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_file_mtime() -> Result<()> {
+    let conn = connection()?;
+
+    let path = "tests/generated/file_mtime/users.bin";
+    write_varint_delimited(path, &users())?;
+
+    let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+    File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(mtime)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT DISTINCT file_mtime::VARCHAR FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/file_mtime/users.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                file_mtime = true
+            );
+        ",
+    )?;
+
+    let mtimes = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(mtimes, vec!["2023-11-14 22:13:20"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_truncated_length_prefix() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    for user in users() {
+        let bytes = user.encode_to_vec();
+        buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&bytes);
+    }
+    // only half of the next record's length prefix was written
+    buf.extend_from_slice(&[0x00, 0x00]);
+
+    std::fs::create_dir_all("tests/generated/truncated_prefix")?;
+    File::create("tests/generated/truncated_prefix/users.bin")?.write_all(&buf)?;
+
+    let query = |trailing_bytes: &str| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/truncated_prefix/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'BigEndianFixed',
+                    trailing_bytes = '{trailing_bytes}'
+                );
+            "
+        ))?;
+
+        let names = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(names)
+    };
+
+    for trailing_bytes in ["ignore", "warn"] {
+        assert_eq!(query(trailing_bytes)?, vec!["Alice", "Bob", "Charlie"]);
+    }
+
+    let err = query("error").expect_err("expected the scan to fail");
+    assert!(
+        err.to_string().contains("incomplete record at position"),
+        "unexpected error: {err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_max_depth() -> Result<()> {
+    let conn = connection()?;
+
+    let chain = |names: &[&str]| {
+        names.iter().rev().fold(None, |child, name| {
+            Some(Box::new(features::Node {
+                name: name.to_string(),
+                child,
+            }))
+        })
+    };
+
+    let nodes = [chain(&["a", "b", "c", "d"]), chain(&["x"])].map(|it| *it.unwrap());
+    write_varint_delimited("tests/generated/max_depth/nodes.bin", &nodes)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, child.child.name, child.child.child FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/max_depth/nodes.bin',
+                message_type = 'features.Node',
+                delimiter = 'Varint',
+                max_depth = 2
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<Vec<u8>>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // past the second level the remaining nodes are left encoded
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "a".to_string(),
+                "c".to_string(),
+                Some(
+                    features::Node {
+                        name: "d".to_string(),
+                        child: None,
+                    }
+                    .encode_to_vec()
+                )
+            ),
+            ("x".to_string(), "".to_string(), None),
+        ]
+    );
+
+    // the default depth doesn't overflow the stack binding the scan either
+    let names = conn
+        .prepare(
+            "
+                SELECT name FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/max_depth/nodes.bin',
+                    message_type = 'features.Node',
+                    delimiter = 'Varint'
+                );
+            ",
+        )?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(names, vec!["a", "x"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_max_depth_branching() -> Result<()> {
+    let conn = connection()?;
+
+    let leaf = |op: &str| {
+        Some(Box::new(features::Expr {
+            op: op.to_string(),
+            lhs: None,
+            rhs: None,
+        }))
+    };
+    let exprs = [features::Expr {
+        op: "+".to_string(),
+        lhs: leaf("1"),
+        rhs: Some(Box::new(features::Expr {
+            op: "*".to_string(),
+            lhs: leaf("2"),
+            rhs: leaf("3"),
+        })),
+    }];
+    write_varint_delimited("tests/generated/max_depth_branching/exprs.bin", &exprs)?;
+
+    let query = |max_depth: &str| {
+        conn.prepare(&format!(
+            "
+                SELECT op, lhs.op, rhs.op, rhs.rhs.op FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/max_depth_branching/exprs.bin',
+                    message_type = 'features.Expr',
+                    delimiter = 'Varint'
+                    {max_depth}
+                );
+            "
+        ))?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+    };
+
+    // each level doubles the fields of the type, at the default depth it
+    // would have billions of them
+    let err = query("").unwrap_err();
+    assert!(
+        err.to_string().contains(
+            "message `features.Expr` nested 32 levels deep makes a type of more than 10000 fields"
+        ),
+        "{err}"
+    );
+
+    assert_eq!(
+        query(", max_depth = 4")?,
+        vec![(
+            "+".to_string(),
+            "1".to_string(),
+            "*".to_string(),
+            "3".to_string()
+        )]
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "remote")]
+fn test_url_files() -> Result<()> {
+    let conn = connection()?;
+
+    for (idx, chunk) in users().chunks(2).enumerate() {
+        write_varint_delimited(&format!("tests/generated/url/users_{idx}.bin"), chunk)?;
+    }
+
+    // file:// URLs go through the same object store code as s3:// ones
+    let dir = std::env::current_dir()?.join("tests/generated/url");
+    let mut stmt = conn.prepare(&format!(
+        "
+            SELECT name, id, filename FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = 'file://{}/*.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                filename = true
+            )
+            ORDER BY id;
+        ",
+        dir.display()
+    ))?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let url = |name: &str| format!("file://{}/{name}", dir.display());
+    assert_eq!(
+        rows,
+        vec![
+            ("Alice".to_string(), 1, url("users_0.bin")),
+            ("Bob".to_string(), 2, url("users_0.bin")),
+            ("Charlie".to_string(), 3, url("users_1.bin")),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "remote"))]
+fn test_url_files_without_remote_feature() -> Result<()> {
+    let conn = connection()?;
+
+    let err = conn
+        .prepare(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = 's3://bucket/dumps/*.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint'
+                );
+            ",
+        )
+        .unwrap_err();
+
+    assert!(
+        err.to_string()
+            .contains("s3://bucket/dumps/*.bin: reading URLs requires building the extension with the `remote` feature"),
+        "{err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_mmap() -> Result<()> {
+    let conn = connection()?;
+
+    for (idx, chunk) in users().chunks(2).enumerate() {
+        write_varint_delimited(&format!("tests/generated/mmap/users_{idx}.bin"), chunk)?;
+    }
+
+    let query = |mmap: bool, concat_files: bool| -> Result<Vec<(String, i32, u64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name, id, position FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/mmap/*.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    position = true,
+                    mmap = {mmap},
+                    concat_files = {concat_files}
+                )
+                ORDER BY id;
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    for concat_files in [false, true] {
+        let expected = query(false, concat_files)?;
+        assert_eq!(expected.len(), 3);
+        assert_eq!(query(true, concat_files)?, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_records_of_varying_sizes() -> Result<()> {
+    let conn = connection()?;
+
+    // records are read into a reused buffer, so a short record following a
+    // long one mustn't pick up the long one's leftover bytes
+    let expected = [
+        "a".repeat(4096),
+        "Bob".to_string(),
+        String::new(),
+        "Al".to_string(),
+        "c".repeat(100),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(idx, name)| (name, idx as i32))
+    .collect::<Vec<_>>();
+
+    let mut buf = Vec::new();
+    for (name, id) in &expected {
+        let bytes = user::User {
+            name: name.clone(),
+            id: *id,
+        }
+        .encode_to_vec();
+        buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&bytes);
+    }
+
+    std::fs::create_dir_all("tests/generated/varying_sizes")?;
+    File::create("tests/generated/varying_sizes/users.bin")?.write_all(&buf)?;
+
+    let results = query_users(
+        &conn,
+        "./tests/generated/varying_sizes/users.bin",
+        "BigEndianFixed",
+    )?;
+    assert_eq!(results, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_index_cache() -> Result<()> {
+    let conn = connection()?;
+
+    let cache_dir = Path::new("tests/generated/index_cache/cache");
+    let _ = std::fs::remove_dir_all(cache_dir);
+    std::fs::create_dir_all(cache_dir)?;
+
+    write_varint_delimited("tests/generated/index_cache/users.bin", &users())?;
+
+    let query = || -> Result<Vec<(String, i32, u64, u64)>> {
+        let mut stmt = conn.prepare(
+            "
+                SELECT name, id, record_index, position FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/index_cache/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    record_index = true,
+                    position = true,
+                    index_cache = './tests/generated/index_cache/cache'
+                )
+                ORDER BY id;
+            ",
+        )?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    let sidecar = || -> Result<std::path::PathBuf> {
+        let entries = std::fs::read_dir(cache_dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 1, "expected a single sidecar: {entries:?}");
+
+        Ok(entries.into_iter().next().unwrap())
+    };
+
+    let expected = query()?;
+    assert_eq!(
+        expected
+            .iter()
+            .map(|(name, _, index, _)| (name.as_str(), *index))
+            .collect::<Vec<_>>(),
+        vec![("Alice", 0), ("Bob", 1), ("Charlie", 2)]
+    );
+
+    // the first scan writes the index, mark it so a rewrite would show
+    let written = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1);
+    File::options()
+        .write(true)
+        .open(sidecar()?)?
+        .set_modified(written)?;
+
+    assert_eq!(query()?, expected);
+    assert_eq!(sidecar()?.metadata()?.modified()?, written);
+
+    // changing the file invalidates its index
+    let mut more_users = users();
+    more_users.push(user::User {
+        name: "Dave".to_string(),
+        id: 4,
+    });
+    write_varint_delimited("tests/generated/index_cache/users.bin", &more_users)?;
+
+    let rows = query()?;
+    assert_eq!(rows.len(), 4);
+    assert_eq!(&rows[..3], &expected[..]);
+    assert_ne!(sidecar()?.metadata()?.modified()?, written);
+
+    Ok(())
+}
+
+#[test]
+fn test_index_cache_write_failure() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/index_cache_missing/users.bin", &users())?;
+
+    // scans of tests running alongside replace the most recent scan, retry
+    // until the errors are this one's
+    for _ in 0..100 {
+        let count: u64 = conn.query_row(
+            "
+                SELECT count(*) FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/index_cache_missing/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    index_cache = './tests/generated/index_cache_missing/cache'
+                );
+            ",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(count, 3);
+
+        let mut stmt = conn.prepare(
+            "
+                SELECT error FROM protobuf_errors()
+                WHERE filename LIKE '%index_cache_missing/users.bin';
+            ",
+        )?;
+        let errors = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !errors.is_empty() {
+            assert_eq!(errors.len(), 1, "{errors:?}");
+            assert!(
+                errors[0].starts_with("failed to write index"),
+                "unexpected error: {}",
+                errors[0]
+            );
+            return Ok(());
+        }
+    }
+
+    panic!("the errors of the scan were always replaced by another's");
+}
+
+#[test]
+fn test_limit_stops_before_next_file() -> Result<()> {
+    let conn = connection()?;
+    conn.execute("SET threads = 1", [])?;
+
+    write_varint_delimited("tests/generated/limit/users_0.bin", &users())?;
+    // later files have a malformed length prefix, so the scan fails if it
+    // opens any of them
+    for idx in 1..10 {
+        File::create(format!("tests/generated/limit/users_{idx}.bin"))?.write_all(&[0xff; 12])?;
+    }
+
+    let query = |limit: &str| -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/limit/*.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint'
+                )
+                {limit};
+            "
+        ))?;
+
+        let names = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(names)
+    };
+
+    assert_eq!(query("LIMIT 2")?, vec!["Alice", "Bob"]);
+
+    let err = query("").expect_err("expected the scan to fail");
+    assert!(
+        err.to_string().contains("malformed varint length prefix"),
+        "unexpected error: {err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_use_defaults() -> Result<()> {
+    let conn = connection()?;
+
+    let users = [
+        user::User {
+            name: "Alice".to_string(),
+            id: 0,
+        },
+        user::User {
+            name: String::new(),
+            id: 2,
+        },
+    ];
+    write_varint_delimited("tests/generated/use_defaults/users.bin", &users)?;
+
+    let query = |use_defaults: bool| -> Result<Vec<(Option<String>, Option<i32>)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name, id FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/use_defaults/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    use_defaults = {use_defaults}
+                )
+                ORDER BY id NULLS FIRST;
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    assert_eq!(
+        query(true)?,
+        vec![
+            (Some("Alice".to_string()), Some(0)),
+            (Some("".to_string()), Some(2)),
+        ]
+    );
+
+    assert_eq!(
+        query(false)?,
+        vec![(Some("Alice".to_string()), None), (None, Some(2))]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_proto3_optional_presence() -> Result<()> {
+    let conn = connection()?;
+
+    let measurements = [
+        features::Measurement {
+            sensor: "set".to_string(),
+            value: Some(7),
+        },
+        features::Measurement {
+            sensor: "zero".to_string(),
+            value: Some(0),
+        },
+        features::Measurement {
+            sensor: "unset".to_string(),
+            value: None,
+        },
+    ];
+    write_varint_delimited("tests/generated/presence/measurements.bin", &measurements)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT sensor, value FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/presence/measurements.bin',
+                message_type = 'features.Measurement',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(String, Option<i32>)>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![
+            ("set".to_string(), Some(7)),
+            ("zero".to_string(), Some(0)),
+            ("unset".to_string(), None),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_nested_absent_fields() -> Result<()> {
+    let conn = connection()?;
+
+    let orders = [
+        features::Order {
+            detail: Some(features::Detail {
+                b: 5,
+                a: String::new(),
+            }),
+        },
+        features::Order {
+            detail: Some(features::Detail {
+                b: 0,
+                a: "x".to_string(),
+            }),
+        },
+        features::Order { detail: None },
+    ];
+    write_varint_delimited("tests/generated/nested_absent/orders.bin", &orders)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT detail.a, detail.b, detail IS NULL FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/nested_absent/orders.bin',
+                message_type = 'features.Order',
+                delimiter = 'Varint',
+                record_index = true,
+                use_defaults = false
+            )
+            ORDER BY record_index;
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<Vec<(Option<String>, Option<i32>, bool)>, _>>()?;
+
+    // fields absent from a submessage are null in the struct, which itself
+    // stays valid, and only the row without the submessage is null
+    assert_eq!(
+        rows,
+        vec![
+            (None, Some(5), false),
+            (Some("x".to_string()), None, false),
+            (None, None, true),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_allow_empty() -> Result<()> {
+    let conn = connection()?;
+
+    let scan = |allow_empty: bool| {
+        format!(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/allow_empty/*.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    allow_empty = {allow_empty}
+                )
+            "
+        )
+    };
+
+    let err = conn
+        .prepare(&scan(false))
+        .and_then(|mut stmt| {
+            let rows = stmt.query_map([], |_| Ok(()))?.count();
+            Ok(rows)
+        })
+        .expect_err("expected the query to fail");
+    assert!(
+        err.to_string().contains("no files matching glob found"),
+        "unexpected error: {err}"
+    );
+
+    let mut stmt = conn.prepare(&scan(true))?;
+    assert_eq!(stmt.query_map([], |_| Ok(()))?.count(), 0);
+
+    // the columns still come from the descriptor
+    let mut stmt = conn.prepare(&format!("DESCRIBE {}", scan(true)))?;
+    let columns = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(String, String)>, _>>()?;
+    assert_eq!(
+        columns,
+        vec![
+            ("name".to_string(), "VARCHAR".to_string()),
+            ("id".to_string(), "INTEGER".to_string()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_raw_column() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/raw/users.bin", &users())?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, id, _raw FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/raw/users.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                raw = true
+            )
+            ORDER BY id;
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<Vec<(String, i32, Vec<u8>)>, _>>()?;
+
+    assert_eq!(rows.len(), 3);
+    for ((name, id, raw), user) in rows.into_iter().zip(users()) {
+        assert_eq!(raw, user.encode_to_vec());
+
+        let decoded = user::User::decode(raw.as_slice())?;
+        assert_eq!((decoded.name, decoded.id), (name, id));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_protobuf_messages() -> Result<()> {
+    let conn = connection()?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT full_name, field_count, file
+            FROM protobuf_messages(descriptors = './tests/generated/descriptor.pb');
+        ",
+    )?;
+
+    let messages = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<Vec<(String, i32, String)>, _>>()?;
+
+    assert!(
+        messages.contains(&("user.User".to_string(), 2, "user.proto".to_string())),
+        "{messages:?}"
+    );
+    // generated for map fields, not messages of their own
+    assert!(!messages
+        .iter()
+        .any(|(name, _, _)| name.starts_with("features.Inventory.")));
+
+    Ok(())
+}
+
+#[test]
+fn test_protobuf_schema() -> Result<()> {
+    let conn = connection()?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, number, proto_type, duckdb_type, repeated
+            FROM protobuf_schema(
+                descriptors = './tests/generated/descriptor.pb',
+                message_type = 'user.User'
+            );
+        ",
+    )?;
+
+    let columns = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<(String, i32, String, String, bool)>, _>>()?;
+
+    assert_eq!(
+        columns,
+        vec![
+            (
+                "name".to_string(),
+                1,
+                "string".to_string(),
+                "VARCHAR".to_string(),
+                false
+            ),
+            (
+                "id".to_string(),
+                2,
+                "int32".to_string(),
+                "INTEGER".to_string(),
+                false
+            ),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_decode_error_context() -> Result<()> {
+    let conn = connection()?;
+
+    let mut buf = Vec::new();
+    features::Order {
+        detail: Some(features::Detail {
+            b: 5,
+            a: String::new(),
+        }),
+    }
+    .encode_length_delimited(&mut buf)?;
+    let position = buf.len();
+
+    // `detail.b` is an int32, encoded here as a string
+    buf.extend_from_slice(&[0x05, 0x0a, 0x03, 0x12, 0x01, b'x']);
+
+    std::fs::create_dir_all("tests/generated/decode_error")?;
+    File::create("tests/generated/decode_error/orders.bin")?.write_all(&buf)?;
+
+    let query = || -> Result<Vec<()>> {
+        let mut stmt = conn.prepare(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/decode_error/orders.bin',
+                    message_type = 'features.Order',
+                    delimiter = 'Varint'
+                );
+            ",
+        )?;
+
+        let rows = stmt
+            .query_map([], |_| Ok(()))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    let err = query().err().expect("expected the scan to fail");
+
+    let message = err.to_string();
+    assert!(
+        message.contains(&format!("orders.bin: record at position {position}")),
+        "{message}"
+    );
+    assert!(
+        message.contains("field `detail`: field `b`: expected wire type Varint"),
+        "{message}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_wire_type_mismatch() -> Result<()> {
+    let conn = connection()?;
+
+    // `id` is an int32, encoded here as a string
+    let record = [0x0a, 0x01, b'a', 0x12, 0x01, b'x'];
+    let mut buf = vec![record.len() as u8];
+    buf.extend_from_slice(&record);
+
+    std::fs::create_dir_all("tests/generated/wire_type_mismatch")?;
+    File::create("tests/generated/wire_type_mismatch/users.bin")?.write_all(&buf)?;
+
+    let query = |lenient: bool| -> Result<Vec<(String, Option<i32>)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name, id FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/wire_type_mismatch/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    lenient = {lenient}
+                );
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    let err = query(false).err().expect("expected the scan to fail");
+    assert!(
+        err.to_string()
+            .contains("field `id`: expected wire type Varint, got LengthDelimited"),
+        "{err}"
+    );
+
+    assert_eq!(query(true)?, vec![("a".to_string(), None)]);
+
+    Ok(())
+}
+
+/// Set for the child process of `test_stdin`, which reads the records piped
+/// in by its parent.
+const STDIN_CHILD: &str = "DUCKDB_PROTOBUF_STDIN_CHILD";
+
+#[test]
+fn test_stdin() -> Result<()> {
+    if std::env::var_os(STDIN_CHILD).is_some() {
+        let conn = connection()?;
+        println!("rows: {:?}", query_users(&conn, "-", "Varint")?);
+
+        return Ok(());
+    }
+
+    let mut buf = Vec::new();
+    for user in users() {
+        user.encode_length_delimited(&mut buf)?;
+    }
+
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(["--exact", "test_stdin", "--nocapture"])
+        .env(STDIN_CHILD, "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    // closing the pipe ends the stream
+    child.stdin.take().unwrap().write_all(&buf)?;
+    let output = child.wait_with_output()?;
+
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(output.status.success(), "{stdout}");
+
+    let expected = users()
+        .into_iter()
+        .map(|it| (it.name, it.id))
+        .collect::<Vec<_>>();
+    assert!(stdout.contains(&format!("rows: {expected:?}")), "{stdout}");
+
+    Ok(())
+}
+
+#[test]
+fn test_int64_as_string() -> Result<()> {
+    let conn = connection()?;
+
+    // above 2^53, the largest integer a double holds exactly
+    let readings = [features::Reading {
+        service: 1,
+        id: u64::MAX,
+        offset: 0,
+        delta: (1 << 53) + 1,
+    }];
+    write_varint_delimited("tests/generated/int64_as_string/readings.bin", &readings)?;
+
+    let row = conn.query_row(
+        "
+            SELECT typeof(id), id, typeof(service), delta FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/int64_as_string/readings.bin',
+                message_type = 'features.Reading',
+                delimiter = 'Varint',
+                int64_as_string = true
+            );
+        ",
+        [],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        },
+    )?;
+
+    assert_eq!(
+        row,
+        (
+            "VARCHAR".to_string(),
+            "18446744073709551615".to_string(),
+            "UINTEGER".to_string(),
+            "9007199254740993".to_string(),
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_decimal_scale_option() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/decimal_scale_option/quotes.bin",
+        &[features::Quote {
+            symbol: "ACME".to_string(),
+            price: 1234567,
+        }],
+    )?;
+
+    // `features.Quote.price` is annotated with `(features.scale) = 4`
+    let (logical_type, price) = conn.query_row(
+        "
+            SELECT typeof(price), CAST(price AS VARCHAR) FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/decimal_scale_option/quotes.bin',
+                message_type = 'features.Quote',
+                delimiter = 'Varint',
+                decimal_scale_option = 50001
+            );
+        ",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    )?;
+
+    assert_eq!(logical_type, "DECIMAL(18,4)");
+    assert_eq!(price, "123.4567");
+
+    Ok(())
+}
+
+#[test]
+fn test_any_without_any_types() -> Result<()> {
+    let conn = connection()?;
+
+    let item = features::Item {
+        name: "apple".to_string(),
+        quantity: 3,
+    };
+    write_varint_delimited(
+        "tests/generated/any_raw/events.bin",
+        &[features::Event {
+            payload: Some(prost_types::Any {
+                type_url: "type.googleapis.com/features.Item".to_string(),
+                value: item.encode_to_vec(),
+            }),
+        }],
+    )?;
+
+    let (type_url, value) = conn.query_row(
+        "
+            SELECT payload.type_url, payload.value FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/any_raw/events.bin',
+                message_type = 'features.Event',
+                delimiter = 'Varint'
+            );
+        ",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)),
+    )?;
+
+    assert_eq!(type_url, "type.googleapis.com/features.Item");
+    assert_eq!(features::Item::decode(value.as_slice())?, item);
+
+    Ok(())
+}
+
+#[test]
+fn test_struct_as_json() -> Result<()> {
+    use prost_types::value::Kind;
+
+    let conn = connection()?;
+
+    let value = |kind: Kind| prost_types::Value { kind: Some(kind) };
+    // a single key per object, as the order of the keys isn't kept
+    let object = |key: &str, value: prost_types::Value| prost_types::Struct {
+        fields: [(key.to_string(), value)].into_iter().collect(),
+    };
+    let list = |values: Vec<prost_types::Value>| Kind::ListValue(prost_types::ListValue { values });
+
+    write_varint_delimited(
+        "tests/generated/struct_json/documents.bin",
+        &[
+            features::Document {
+                id: "a".to_string(),
+                attributes: Some(object(
+                    "values",
+                    value(list(vec![
+                        value(Kind::NullValue(0)),
+                        value(Kind::NumberValue(1.5)),
+                        value(Kind::BoolValue(true)),
+                        value(Kind::StringValue("text".to_string())),
+                        value(Kind::StructValue(object("nested", value(list(vec![]))))),
+                    ])),
+                )),
+            },
+            features::Document {
+                id: "b".to_string(),
+                attributes: None,
+            },
+        ],
+    )?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT id, typeof(attributes), attributes FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/struct_json/documents.bin',
+                message_type = 'features.Document',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![
+            (
+                "a".to_string(),
+                "JSON".to_string(),
+                Some(r#"{"values":[null,1.5,true,"text",{"nested":[]}]}"#.to_string())
+            ),
+            ("b".to_string(), "JSON".to_string(), None),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_timestamp_unit() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/timestamp_unit/spans.bin",
+        &[
+            features::Span {
+                start: Some(prost_types::Timestamp {
+                    seconds: 1700000000,
+                    nanos: 987654321,
+                }),
+            },
+            // half a second before the epoch
+            features::Span {
+                start: Some(prost_types::Timestamp {
+                    seconds: -1,
+                    nanos: 500000001,
+                }),
+            },
+        ],
+    )?;
+
+    let query = |unit: &str| -> Result<Vec<(String, Value)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT typeof(start), start FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/timestamp_unit/spans.bin',
+                    message_type = 'features.Span',
+                    delimiter = 'Varint',
+                    timestamp_unit = '{unit}'
+                );
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    // finer parts of `nanos` are truncated, which rounds down as `nanos` is
+    // never negative
+    let cases = [
+        ("s", "TIMESTAMP_S", TimeUnit::Second, 1700000000, -1),
+        (
+            "ms",
+            "TIMESTAMP_MS",
+            TimeUnit::Millisecond,
+            1700000000987,
+            -500,
+        ),
+        (
+            "us",
+            "TIMESTAMP",
+            TimeUnit::Microsecond,
+            1700000000987654,
+            -500000,
+        ),
+        (
+            "ns",
+            "TIMESTAMP_NS",
+            TimeUnit::Nanosecond,
+            1700000000987654321,
+            -499999999,
+        ),
+    ];
+
+    for (unit, logical_type, time_unit, value, before_epoch) in cases {
+        assert_eq!(
+            query(unit)?,
+            vec![
+                (logical_type.to_string(), Value::Timestamp(time_unit, value)),
+                (
+                    logical_type.to_string(),
+                    Value::Timestamp(time_unit, before_epoch)
+                ),
+            ],
+            "{unit}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_date_and_time_fields() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/date_fields/uploads.bin",
+        &[features::Upload {
+            filename: "a.bin".to_string(),
+            id: 19723,
+        }],
+    )?;
+    write_varint_delimited(
+        "tests/generated/time_fields/items.bin",
+        &[features::Item {
+            name: "apple".to_string(),
+            quantity: 45296000001,
+        }],
+    )?;
+
+    let date = conn.query_row(
+        "
+            SELECT typeof(id), id::VARCHAR FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/date_fields/uploads.bin',
+                message_type = 'features.Upload',
+                delimiter = 'Varint',
+                date_fields = 'id'
+            );
+        ",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    )?;
+    assert_eq!(date, ("DATE".to_string(), "2024-01-01".to_string()));
+
+    let time = conn.query_row(
+        "
+            SELECT typeof(quantity), quantity::VARCHAR FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/time_fields/items.bin',
+                message_type = 'features.Item',
+                delimiter = 'Varint',
+                time_fields = 'features.Item.quantity'
+            );
+        ",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    )?;
+    assert_eq!(time, ("TIME".to_string(), "12:34:56.000001".to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn test_strict_schema() -> Result<()> {
+    let conn = connection()?;
+
+    // a user followed by field 9, which `user.User` doesn't declare
+    let mut record = users()[0].encode_to_vec();
+    record.extend_from_slice(&[0x48, 0x01]);
+
+    let mut buf = Vec::new();
+    prost::encoding::encode_varint(record.len() as u64, &mut buf);
+    buf.extend_from_slice(&record);
+
+    std::fs::create_dir_all("tests/generated/strict_schema")?;
+    File::create("tests/generated/strict_schema/users.bin")?.write_all(&buf)?;
+
+    let query = |strict_schema: bool| {
+        format!(
+            "
+                SELECT name, id FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/strict_schema/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    strict_schema = {strict_schema}
+                );
+            "
+        )
+    };
+
+    let err = conn
+        .prepare(&query(true))
+        .err()
+        .expect("expected bind to fail");
+    assert!(
+        err.to_string()
+            .contains("the first record has fields [9] which `user.User` doesn't declare"),
+        "unexpected error: {err}"
+    );
+
+    // unknown fields are skipped otherwise
+    let row = conn.query_row(&query(false), [], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?))
+    })?;
+    assert_eq!(row, ("Alice".to_string(), 1));
+
+    Ok(())
+}
+
+#[test]
+fn test_protobuf_benchmark() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/benchmark/users.bin", &users())?;
+
+    let row = conn.query_row(
+        "
+            SELECT records, bytes, records_per_second > 0, bytes_per_second > 0
+            FROM protobuf_benchmark(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/benchmark/users.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint'
+            );
+        ",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+
+    let bytes = users()
+        .iter()
+        .map(|it| it.encoded_len() as u64)
+        .sum::<u64>();
+    assert_eq!(row, (3u64, bytes, true, true));
+
+    Ok(())
+}
+
+#[test]
+fn test_sibling_nested_lists() -> Result<()> {
+    let conn = connection()?;
+
+    // more rows than a single chunk holds, with lists of varying lengths so
+    // the offsets of the two columns drift apart
+    let twig = |leaves: Vec<i32>| features::Twig { leaves };
+    let groves = (0..5000)
+        .map(|idx: i32| features::Grove {
+            left: (0..idx % 3)
+                .map(|twig_idx| twig((0..twig_idx + 1).map(|leaf| idx + leaf).collect()))
+                .collect(),
+            right: (0..idx % 2 + 1)
+                .map(|twig_idx| twig(vec![-idx; (idx % 4 + twig_idx) as usize]))
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    write_varint_delimited("tests/generated/sibling_lists/groves.bin", &groves)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT
+                list_transform(\"left\", twig -> twig.leaves)::VARCHAR,
+                list_transform(\"right\", twig -> twig.leaves)::VARCHAR
+            FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/sibling_lists/groves.bin',
+                message_type = 'features.Grove',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(String, String)>, _>>()?;
+
+    let render = |twigs: &[features::Twig]| {
+        let twigs = twigs
+            .iter()
+            .map(|twig| {
+                let leaves = twig
+                    .leaves
+                    .iter()
+                    .map(|it| it.to_string())
+                    .collect::<Vec<_>>();
+                format!("[{}]", leaves.join(", "))
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", twigs.join(", "))
+    };
+    let expected = groves
+        .iter()
+        .map(|grove| (render(&grove.left), render(&grove.right)))
+        .collect::<Vec<_>>();
+
+    assert_eq!(rows.len(), expected.len());
+    for (idx, (row, expected)) in rows.iter().zip(&expected).enumerate() {
+        assert_eq!(row, expected, "row {idx}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_lists_across_chunks() -> Result<()> {
+    let conn = connection()?;
+
+    // three chunks worth of rows, the first rows of every chunk start at the
+    // beginning of that chunk's child vectors
+    let samples = (0..6000u32)
+        .map(|idx| features::Samples {
+            values: (0..idx % 5).map(|it| (idx + it) as f64).collect(),
+            counts: (0..idx % 3).map(|it| idx * 10 + it).collect(),
+        })
+        .collect::<Vec<_>>();
+    write_varint_delimited("tests/generated/chunked_lists/samples.bin", &samples)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT
+                list_transform(values, it -> CAST(it AS BIGINT))::VARCHAR,
+                counts::VARCHAR
+            FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/chunked_lists/samples.bin',
+                message_type = 'features.Samples',
+                delimiter = 'Varint',
+                record_index = true
+            )
+            ORDER BY record_index;
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(String, String)>, _>>()?;
+
+    let render = |values: Vec<String>| format!("[{}]", values.join(", "));
+    let expected = samples
+        .iter()
+        .map(|sample| {
+            (
+                render(sample.values.iter().map(|it| it.to_string()).collect()),
+                render(sample.counts.iter().map(|it| it.to_string()).collect()),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(rows.len(), expected.len());
+    for (idx, (row, expected)) in rows.iter().zip(&expected).enumerate() {
+        assert_eq!(row, expected, "row {idx}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_size() -> Result<()> {
+    let conn = connection()?;
+
+    let users = (1..=2500)
+        .map(|id| user::User {
+            name: format!("user{id}"),
+            id,
+        })
+        .collect::<Vec<_>>();
+    write_varint_delimited("tests/generated/batch_size/users.bin", &users)?;
+
+    let query = |batch_size: usize| -> Result<(u64, i64)> {
+        Ok(conn.query_row(
+            &format!(
+                "
+                    SELECT count(*), sum(id) FROM protobuf(
+                        descriptors = './tests/generated/descriptor.pb',
+                        files = './tests/generated/batch_size/users.bin',
+                        message_type = 'user.User',
+                        delimiter = 'Varint',
+                        batch_size = {batch_size}
+                    );
+                "
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?)
+    };
+
+    // one row per call, and a batch larger than a chunk holds
+    for batch_size in [1, 1000, 100_000] {
+        assert_eq!(query(batch_size)?, (2500, 2500 * 2501 / 2), "{batch_size}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_brace_expansion() -> Result<()> {
+    let conn = connection()?;
+
+    let users = users();
+    for (idx, year) in ["2023", "2024", "2025"].iter().enumerate() {
+        write_varint_delimited(
+            &format!("tests/generated/braces/{year}/users.bin"),
+            &users[idx..idx + 1],
+        )?;
+    }
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT name, filename FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/braces/{2023,2024,202[34]}/*.bin',
+                message_type = 'user.User',
+                delimiter = 'Varint',
+                filename = true
+            )
+            ORDER BY name;
+        ",
+    )?;
+
+    let results = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // 2025 isn't matched, and files matched twice are read once
+    assert_eq!(
+        results,
+        vec![
+            (
+                "Alice".to_string(),
+                "./tests/generated/braces/2023/users.bin".to_string()
+            ),
+            (
+                "Bob".to_string(),
+                "./tests/generated/braces/2024/users.bin".to_string()
+            ),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_order() -> Result<()> {
+    let conn = connection()?;
+
+    for (name, chunk) in ["c", "a", "b"].iter().zip(users().chunks(1)) {
+        write_varint_delimited(&format!("tests/generated/order/{name}.bin"), chunk)?;
+    }
+
+    let query = |order: &str| -> Result<Vec<(String, u64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "
+                SELECT name, file_index FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/order/{{c,a,b}}.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    file_index = true,
+                    order = '{order}'
+                )
+                ORDER BY file_index;
+            "
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(rows)
+    };
+
+    // a.bin holds Bob, b.bin Charlie and c.bin Alice
+    assert_eq!(
+        query("name")?,
+        [("Bob", 0), ("Charlie", 1), ("Alice", 2)]
+            .map(|(name, idx)| (name.to_string(), idx))
+            .to_vec()
+    );
+
+    // in the order of the alternatives
+    assert_eq!(
+        query("none")?,
+        [("Alice", 0), ("Bob", 1), ("Charlie", 2)]
+            .map(|(name, idx)| (name.to_string(), idx))
+            .to_vec()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_columns() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/columns/readings.bin",
+        &[features::Reading {
+            service: 1,
+            id: 2,
+            offset: 3,
+            delta: 4,
+        }],
+    )?;
+
+    let scan = |columns: &str| {
+        format!(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/columns/readings.bin',
+                    message_type = 'features.Reading',
+                    delimiter = 'Varint',
+                    columns = '{columns}'
+                )
+            "
+        )
+    };
+
+    let mut stmt = conn.prepare(&format!("DESCRIBE {}", scan("delta, id")))?;
+    let columns = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(String, String)>, _>>()?;
+    assert_eq!(
+        columns,
+        vec![
+            ("id".to_string(), "UBIGINT".to_string()),
+            ("delta".to_string(), "BIGINT".to_string()),
+        ]
+    );
+
+    let row = conn.query_row(&scan("delta, id"), [], |row| {
+        Ok((row.get::<_, u64>(0)?, row.get::<_, i64>(1)?))
+    })?;
+    assert_eq!(row, (2, 4));
+
+    let err = conn
+        .prepare(&scan("id, missing"))
+        .expect_err("expected binding to fail");
+    assert!(
+        err.to_string()
+            .contains("`columns`: `missing` isn't a column of `features.Reading`"),
+        "unexpected error: {err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rename() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited("tests/generated/rename/users.bin", &users())?;
+
+    let scan = |rename: &str| {
+        format!(
+            "
+                SELECT user_id, name FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/rename/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint',
+                    rename = '{rename}'
+                )
+                WHERE user_id > 1
+                ORDER BY user_id;
+            "
+        )
+    };
+
+    let mut stmt = conn.prepare(&scan("id=user_id"))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(i32, String)>, _>>()?;
+    assert_eq!(
+        rows,
+        vec![(2, "Bob".to_string()), (3, "Charlie".to_string())]
+    );
+
+    // renamed columns are checked for collisions like any other
+    let err = conn
+        .prepare(&scan("id=user_id, name=user_id"))
+        .expect_err("expected binding to fail");
+    assert!(
+        err.to_string()
+            .contains("names `user_id` and `user_id` collide"),
+        "unexpected error: {err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_corrupt_descriptors() -> Result<()> {
+    let conn = connection()?;
+
+    std::fs::create_dir_all("tests/generated/corrupt_descriptors")?;
+    // a varint which never ends
+    File::create("tests/generated/corrupt_descriptors/random.pb")?.write_all(&[0xff; 16])?;
+    File::create("tests/generated/corrupt_descriptors/empty.pb")?;
+
+    let bind = |descriptors: &str| {
+        conn.prepare(&format!(
+            "
+                SELECT * FROM protobuf(
+                    descriptors = './tests/generated/corrupt_descriptors/{descriptors}',
+                    files = './tests/generated/data/**/*.bin',
+                    message_type = 'user.User',
+                    delimiter = 'SingleMessagePerFile'
+                );
+            "
+        ))
+        .map(|_| ())
+        .expect_err("expected binding to fail")
+        .to_string()
+    };
+
+    let err = bind("random.pb");
+    assert!(
+        err.contains("failed to parse `descriptors` as a FileDescriptorSet: ")
+            && err.contains("; ensure it was produced by `protoc --descriptor_set_out`"),
+        "unexpected error: {err}"
+    );
+
+    let err = bind("empty.pb");
+    assert!(
+        err.contains(
+            "empty.pb: the file is empty, ensure it was produced by `protoc --descriptor_set_out`"
+        ),
+        "unexpected error: {err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_decode_bytes_as() -> Result<()> {
+    let conn = connection()?;
+
+    let parcels = users()
+        .into_iter()
+        .map(|user| features::Parcel {
+            kind: "user".to_string(),
+            payload: user.encode_to_vec(),
+        })
+        .collect::<Vec<_>>();
+    write_varint_delimited("tests/generated/decode_bytes_as/parcels.bin", &parcels)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT kind, typeof(payload) LIKE 'STRUCT(%', payload.name, payload.id FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/decode_bytes_as/parcels.bin',
+                message_type = 'features.Parcel',
+                delimiter = 'Varint',
+                decode_bytes_as = 'payload=user.User'
+            )
+            ORDER BY payload.id;
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, bool>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i32>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        users()
+            .into_iter()
+            .map(|user| ("user".to_string(), true, user.name, user.id))
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_field_numbers() -> Result<()> {
+    let conn = connection()?;
+
+    write_varint_delimited(
+        "tests/generated/field_numbers/orders.bin",
+        &[features::Order {
+            detail: Some(features::Detail {
+                b: 2,
+                a: "a".to_string(),
+            }),
+        }],
+    )?;
+
+    let mut stmt = conn.prepare(
+        "
+            DESCRIBE SELECT * FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/field_numbers/orders.bin',
+                message_type = 'features.Order',
+                delimiter = 'Varint',
+                field_numbers = true
+            );
+        ",
+    )?;
+
+    let columns = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(String, String)>, _>>()?;
+    assert_eq!(
+        columns,
+        vec![(
+            "detail#1".to_string(),
+            "STRUCT(\"b#2\" INTEGER, \"a#1\" VARCHAR)".to_string()
+        )]
+    );
+
+    let row = conn.query_row(
+        "
+            SELECT \"detail#1\".\"a#1\", \"detail#1\".\"b#2\" FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/field_numbers/orders.bin',
+                message_type = 'features.Order',
+                delimiter = 'Varint',
+                field_numbers = true
+            );
+        ",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?)),
+    )?;
+    assert_eq!(row, ("a".to_string(), 2));
+
+    Ok(())
+}
+
+#[test]
+fn test_packed_sint64() -> Result<()> {
+    let conn = connection()?;
+
+    let routes = [
+        features::Route {
+            moves: vec![1, -1, 0, -64, 64, i64::MIN, i64::MAX],
+        },
+        features::Route {
+            moves: vec![-300, 300],
+        },
+    ];
+
+    // proto3 packs repeated scalars, a single record holds all the moves
+    assert_eq!(
+        routes[1].encode_to_vec(),
+        [0x0a, 0x04, 0xd7, 0x04, 0xd8, 0x04]
+    );
+    write_varint_delimited("tests/generated/packed_sint64/routes.bin", &routes)?;
+
+    let mut stmt = conn.prepare(
+        "
+            SELECT CAST(moves AS VARCHAR) FROM protobuf(
+                descriptors = './tests/generated/descriptor.pb',
+                files = './tests/generated/packed_sint64/routes.bin',
+                message_type = 'features.Route',
+                delimiter = 'Varint'
+            );
+        ",
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![
+            "[1, -1, 0, -64, 64, -9223372036854775808, 9223372036854775807]".to_string(),
+            "[-300, 300]".to_string(),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_last_scan_stats() -> Result<()> {
+    let conn = connection()?;
+
+    let users = (1..=5000)
+        .map(|id| user::User {
+            name: format!("user{id}"),
+            id,
+        })
+        .collect::<Vec<_>>();
+    write_varint_delimited("tests/generated/last_scan_stats/users.bin", &users)?;
+
+    let expected = (
+        "./tests/generated/last_scan_stats/users.bin".to_string(),
+        5000,
+        users.iter().map(|it| it.encoded_len() as u64).sum::<u64>(),
+    );
+
+    // scans of tests running alongside replace the last scan, retry until
+    // the stats are this one's
+    for _ in 0..100 {
+        let count: u64 = conn.query_row(
+            "
+                SELECT count(*) FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/last_scan_stats/users.bin',
+                    message_type = 'user.User',
+                    delimiter = 'Varint'
+                );
+            ",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(count, 5000);
+
+        let stats = conn.query_row(
+            "SELECT files, records, bytes FROM protobuf_last_scan_stats();",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, u64>(1)?,
+                    row.get::<_, u64>(2)?,
+                ))
+            },
+        )?;
+
+        if stats.0 == expected.0 {
+            assert_eq!(stats, expected);
+            return Ok(());
+        }
+    }
+
+    panic!("the stats of the scan were always replaced by another's");
+}
+
+#[test]
+fn test_last_scan_stats_reset_every_scan() -> Result<()> {
+    let conn = connection()?;
+
+    let users = (1..=100)
+        .map(|id| user::User {
+            name: format!("user{id}"),
+            id,
+        })
+        .collect::<Vec<_>>();
+    write_varint_delimited("tests/generated/last_scan_stats_reset/many.bin", &users)?;
+    write_varint_delimited("tests/generated/last_scan_stats_reset/few.bin", &users[..2])?;
+
+    let scan = |file: &str| -> Result<u64> {
+        Ok(conn.query_row(
+            &format!(
+                "
+                SELECT count(*) FROM protobuf(
+                    descriptors = './tests/generated/descriptor.pb',
+                    files = './tests/generated/last_scan_stats_reset/{file}',
+                    message_type = 'user.User',
+                    delimiter = 'Varint'
+                );
+            "
+            ),
+            [],
+            |row| row.get(0),
+        )?)
+    };
+
+    let stats = |scan_id: &str| -> Result<(String, u64, u64)> {
+        Ok(conn.query_row(
+            &format!("SELECT files, records, scan_id FROM protobuf_last_scan_stats({scan_id});"),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?)
+    };
+
+    // scans of tests running alongside replace the last scan, retry until
+    // both scans' stats are this test's
+    for _ in 0..100 {
+        assert_eq!(scan("many.bin")?, 100);
+        let many = stats("")?;
+        assert_eq!(scan("few.bin")?, 2);
+        let few = stats("")?;
+
+        if !(many.0.ends_with("many.bin") && few.0.ends_with("few.bin")) {
+            continue;
+        }
+
+        // the second scan counts from zero rather than adding to the first
+        assert_eq!(many.1, 100);
+        assert_eq!(few.1, 2);
+        assert!(few.2 > many.2, "{many:?} {few:?}");
+
+        // and the first scan's counters are still there by id
+        assert_eq!(stats(&format!("scan_id = {}", many.2))?, many);
+
+        return Ok(());
+    }
+
+    panic!("the stats of the scans were always replaced by another's");
+}
@@ -0,0 +1,67 @@
+// This file is @generated by prost-build.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Ticket {
+    #[prost(string, optional, tag = "1")]
+    pub title: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(enumeration = "Priority", optional, tag = "2", default = "High")]
+    pub priority: ::core::option::Option<i32>,
+    #[prost(int32, optional, tag = "3", default = "3")]
+    pub retries: ::core::option::Option<i32>,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Priority {
+    Low = 0,
+    Medium = 1,
+    High = 2,
+}
+impl Priority {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Priority::Low => "LOW",
+            Priority::Medium => "MEDIUM",
+            Priority::High => "HIGH",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "LOW" => Some(Self::Low),
+            "MEDIUM" => Some(Self::Medium),
+            "HIGH" => Some(Self::High),
+            _ => None,
+        }
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Thread {
+    #[prost(string, optional, tag = "1")]
+    pub subject: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(group, optional, tag = "2")]
+    pub author: ::core::option::Option<thread::Author>,
+    #[prost(group, repeated, tag = "5")]
+    pub reply: ::prost::alloc::vec::Vec<thread::Reply>,
+}
+/// Nested message and enum types in `Thread`.
+pub mod thread {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Author {
+        #[prost(string, optional, tag = "3")]
+        pub name: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(int32, optional, tag = "4")]
+        pub karma: ::core::option::Option<i32>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Reply {
+        #[prost(string, optional, tag = "6")]
+        pub text: ::core::option::Option<::prost::alloc::string::String>,
+    }
+}